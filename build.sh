@@ -14,9 +14,10 @@ curl -L --proto '=https' --tlsv1.2 -sSf https://raw.githubusercontent.com/cargo-
 # Install trunk
 cargo binstall trunk@0.21.14
 
-# Build yew app with trunk
+# Build yew app with trunk. WebAssembly SIMD speeds up the HSL transforms and
+# raises the browser baseline to Chrome 91, Firefox 89 and Safari 16.4.
 cd hnb-app
-trunk build --public-url=/hnb-app/ --release
+RUSTFLAGS="-C target-feature=+simd128" trunk build --public-url=/hnb-app/ --release
 find dist # debug: what was built?
 cd ..
 
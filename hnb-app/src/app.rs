@@ -1,21 +1,135 @@
+mod chromaticity;
+mod classification;
+mod clouds;
+mod correction;
+mod optimizer;
+mod plots;
+mod regions;
+mod white_balance;
+
 use gloo_file::callbacks::FileReader;
-use js_sys::{Array, Uint8Array};
+use js_sys::{Array, Reflect, Uint8Array, Uint8ClampedArray};
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use wasm_bindgen::JsCast;
-use wasm_bindgen::{closure::Closure, JsValue};
-use web_sys::{Blob, HtmlImageElement, Url};
-use yew::{html, Component, Context, Html, Properties};
+use wasm_bindgen::{closure::Closure, Clamped, JsValue};
+use web_sys::{
+    Blob, Event, HtmlImageElement, HtmlSelectElement, ImageData, MessageEvent, Url, Worker,
+};
+use yew::{html, Component, Context, Html, Properties, TargetCast};
+
+use hnb_app::worker_protocol::{Job, Reply, TransformSpec, WORKER_URL};
 
-use crate::image_container::{ImCanvasWrapper, ImType, ImageContainer};
+use self::chromaticity::{Chromaticity, ChromaticityMsg};
+use self::classification::{Classifier, ClassifierMsg};
+use self::clouds::{Clouds, CloudsMsg};
+use self::correction::{ColorCorrection, CorrectionMsg};
+use self::optimizer::{Optimizer, OptimizerMsg};
+use self::plots::{plot_of, view_plot, Plots, PlotsMsg};
+use self::regions::{Regions, RegionsMsg};
+use self::white_balance::{WhiteBalanceMsg, WhiteBalancing};
+use crate::download::download_bytes;
+use crate::hue_wheel::HueWheel;
+use crate::image_container::{CanvasPointer, ImCanvasWrapper, ImageContainer};
+use crate::param_input::ParamInput;
+use crate::presets::load_presets;
+use crate::select::view_select;
+use hnb_core::{
+    autotune::{auto_stretch, HueCluster},
+    color_transform::{ColorStretch, ColorTransform, HslPipeline, Registry},
+    cube::{transform_to_cube, CubeLut, DEFAULT_CUBE_SIZE},
+    hue_chroma::HueChroma,
+    image::RgbaImage,
+    lut::{Interpolation, LutCache, Processing},
+    optimize::subsample,
+};
 
 use crate::{file_input::FileInput, PositionInfo};
 
+/// The choices offered for how transforms are applied to the image. The
+/// grids are faster but approximate, so they are opt-in.
+const PROCESSING_CHOICES: &[Processing] = &[
+    Processing::Direct,
+    Processing::Full,
+    Processing::Grid {
+        size: 33,
+        interpolation: Interpolation::Tetrahedral,
+    },
+    Processing::Grid {
+        size: 65,
+        interpolation: Interpolation::Tetrahedral,
+    },
+    Processing::Grid {
+        size: 65,
+        interpolation: Interpolation::Trilinear,
+    },
+];
+
+/// The node counts offered for the downloaded `.cube` LUTs. Larger LUTs are
+/// closer to the transforms, but not all software reads more than 33.
+const CUBE_SIZE_CHOICES: &[usize] = &[17, DEFAULT_CUBE_SIZE, 65];
+
+/// Pixels used to find the hues for [App::auto_stretch], to keep it fast.
+const MAX_AUTO_PIXELS: usize = 100_000;
+
+/// The pipeline of a Color Stretch transform, `None` for other transforms.
+fn stretch_pipeline(transform: &dyn ColorTransform) -> Option<HslPipeline> {
+    match transform.slug() {
+        "stretch" => Some(HslPipeline::Legacy),
+        "stretch-encoded" => Some(HslPipeline::SrgbEncoded),
+        _ => None,
+    }
+}
+
+/// Prefix of the presets in the list of views to add, followed by the index.
+const PRESET_PREFIX: &str = "preset:";
+
+/// The name of the original image in the table of statistics.
+const ORIGINAL: &str = "Original";
+
 pub struct App {
     readers: HashMap<String, FileReader>,
     file_info: Option<FileInfo>,
     im_orig: Rc<RefCell<ImCanvasWrapper>>,
-    im_rotated: Rc<RefCell<ImCanvasWrapper>>,
-    im_stretch: Rc<RefCell<ImCanvasWrapper>>,
+    registry: Registry,
+    views: Vec<View>,
+    next_view_id: u32,
+    /// How the transforms are applied to the pixels.
+    processing: Processing,
+    /// Number of nodes per axis of the downloaded `.cube` LUTs.
+    cube_size: usize,
+    /// The Web Worker doing the pixel processing. If it cannot be started,
+    /// the processing is done on the main thread.
+    worker: Option<PixelWorker>,
+    /// Lookup tables of the transforms when processing on the main thread.
+    lut_cache: LutCache,
+    /// Identifies the most recent round of processing.
+    job_id: u32,
+    /// Whether the original image needs to be drawn again.
+    redraw_original: bool,
+    /// The pixels of the original image, without region outlines.
+    raw_data: Option<ImageData>,
+    /// The pixels which are transformed and measured: the original image,
+    /// color corrected and white balanced if set, see [App::pre_stages].
+    orig_data: Option<ImageData>,
+    correction: ColorCorrection,
+    white_balance: WhiteBalancing,
+    plots: Plots,
+    clouds: Clouds,
+    regions: Regions,
+    /// Whether the region outlines need to be drawn again.
+    redraw_rois: bool,
+    /// Whether the statistics of the regions are out of date.
+    roi_stats_dirty: bool,
+    /// Whether the plots and clouds are out of date, e.g. after the regions
+    /// changed. Those of a view are otherwise updated when it is drawn.
+    plots_dirty: bool,
+    /// The view whose parameter slider is being dragged. Its plots and
+    /// clouds, and the statistics of the regions, are updated when the
+    /// slider is released.
+    dragging: Option<u32>,
+    chromaticity: Chromaticity,
+    classifier: Classifier,
+    optimizer: Optimizer,
     state: AppState,
     error_log: Vec<String>,
     /// A count that changes when the image is updated, to force calling the
@@ -24,10 +138,85 @@ pub struct App {
     count: u8,
 }
 
+/// An output view: a color transform and the canvas showing its result.
+struct View {
+    /// Unique identifier, used as key when rendering the list of views.
+    id: u32,
+    transform: Box<dyn ColorTransform>,
+    canvas_wrapper: Rc<RefCell<ImCanvasWrapper>>,
+    /// Whether the transform or the image changed since the view was drawn.
+    dirty: bool,
+    /// Whether its processing was cancelled, so that the canvas shows an
+    /// outdated result until the view is processed again.
+    cancelled: bool,
+    /// The hue clusters found when the stretch center was last set
+    /// automatically.
+    hue_clusters: Option<Vec<HueCluster>>,
+    /// The hues and chromas of the result.
+    plot: Option<HueChroma>,
+}
+
+/// The pixel processing worker and the callbacks receiving its messages.
+struct PixelWorker {
+    worker: Worker,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+    _onerror: Closure<dyn FnMut(JsValue)>,
+}
+
+impl PixelWorker {
+    fn spawn(ctx: &Context<App>) -> Result<Self, JsValue> {
+        let worker = Worker::new(WORKER_URL)?;
+        let link = ctx.link().clone();
+        let onmessage =
+            Closure::<dyn FnMut(MessageEvent)>::new(
+                move |event: MessageEvent| match Reply::from_js(&event.data()) {
+                    Some(reply) => link.send_message(Msg::WorkerReply(reply)),
+                    None => log::error!("invalid message from worker {:?}", event.data()),
+                },
+            );
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        let link = ctx.link().clone();
+        let onerror = Closure::<dyn FnMut(JsValue)>::new(move |err: JsValue| {
+            log::error!("worker error {:?}", err);
+            link.send_message(Msg::WorkerErrored);
+        });
+        worker.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        Ok(Self {
+            worker,
+            _onmessage: onmessage,
+            _onerror: onerror,
+        })
+    }
+}
+
+impl Drop for PixelWorker {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
+
 pub enum AppState {
     Ready,
     ReadingFile,
     DecodingImage(FileInfo),
+    Processing(ProcessingInfo),
+}
+
+/// Progress of processing the views in the worker.
+pub struct ProcessingInfo {
+    job_id: u32,
+    /// Views not done yet, with the fraction done so far.
+    pending: Vec<(u32, f32)>,
+    n_views: usize,
+}
+
+impl ProcessingInfo {
+    /// The fraction of the total work done.
+    fn fraction(&self) -> f32 {
+        let n_done = self.n_views - self.pending.len();
+        let partial: f32 = self.pending.iter().map(|(_, f)| f).sum();
+        (n_done as f32 + partial) / self.n_views.max(1) as f32
+    }
 }
 
 pub struct FileData {
@@ -45,6 +234,46 @@ pub enum Msg {
     Files(Vec<gloo_file::File>),
     ImageLoaded,
     ImageErrored(String),
+    SetParameter {
+        view_id: u32,
+        slug: &'static str,
+        value: f32,
+    },
+    /// A parameter slider of the view was released.
+    ParameterReleased(u32),
+    /// Process the view again after its processing was cancelled.
+    ProcessView(u32),
+    AddView(String),
+    /// The user has selected `.cube` LUT file(s).
+    CubeFiles(Vec<gloo_file::File>),
+    /// The bytes of a `.cube` file have been read.
+    CubeLoaded(FileData),
+    RemoveView(u32),
+    /// Download the transform of a view as `.cube` LUT.
+    DownloadCube(u32),
+    /// Set the center of the Color Stretch of a view from the hues of the
+    /// regions, or of the whole image without regions.
+    AutoStretch(u32),
+    SetProcessing(Processing),
+    SetCubeSize(usize),
+    WorkerReply(Reply),
+    /// The worker could not be loaded or crashed.
+    WorkerErrored,
+    /// Stop processing the views.
+    CancelProcessing,
+    /// A pointer event on the original image, for drawing regions or picking
+    /// the white.
+    Pointer(CanvasPointer),
+    /// The statistics of the regions have been computed.
+    RoiStatsUpdated,
+    Regions(RegionsMsg),
+    Correction(CorrectionMsg),
+    WhiteBalance(WhiteBalanceMsg),
+    Plots(PlotsMsg),
+    Clouds(CloudsMsg),
+    Chromaticity(ChromaticityMsg),
+    Classifier(ClassifierMsg),
+    Optimizer(OptimizerMsg),
 }
 
 #[derive(PartialEq, Properties)]
@@ -57,29 +286,77 @@ impl Component for App {
     type Properties = AppProps;
 
     fn create(ctx: &Context<Self>) -> Self {
-        Self {
+        let worker = match PixelWorker::spawn(ctx) {
+            Ok(worker) => Some(worker),
+            Err(e) => {
+                log::error!("cannot start worker, processing on main thread: {:?}", e);
+                None
+            }
+        };
+        let (presets, error_log) = load_presets();
+        let position_info = &ctx.props().position_info;
+        let mut result = Self {
             im_orig: Rc::new(RefCell::new(ImCanvasWrapper::new(
-                ImType::Original,
-                ctx.props().position_info.clone(),
-            ))),
-            im_rotated: Rc::new(RefCell::new(ImCanvasWrapper::new(
-                ImType::Rotated,
-                ctx.props().position_info.clone(),
-            ))),
-            im_stretch: Rc::new(RefCell::new(ImCanvasWrapper::new(
-                ImType::Stretch,
-                ctx.props().position_info.clone(),
+                "original",
+                "Original",
+                position_info.clone(),
             ))),
+            registry: Registry::default(),
+            views: Vec::new(),
+            next_view_id: 0,
+            processing: Processing::default(),
+            cube_size: DEFAULT_CUBE_SIZE,
+            worker,
+            lut_cache: LutCache::default(),
+            job_id: 0,
+            redraw_original: false,
+            raw_data: None,
+            orig_data: None,
+            correction: ColorCorrection::default(),
+            white_balance: WhiteBalancing::default(),
+            plots: Plots::default(),
+            clouds: Clouds::default(),
+            regions: Regions::default(),
+            redraw_rois: false,
+            roi_stats_dirty: false,
+            plots_dirty: false,
+            dragging: None,
+            chromaticity: Chromaticity::default(),
+            classifier: Classifier::new(position_info.clone()),
+            optimizer: Optimizer::new(presets),
             file_info: None,
             state: AppState::Ready,
-            error_log: vec![],
+            error_log,
             readers: Default::default(),
             count: 0,
-        }
+        };
+        result.add_view(ctx, "rotated");
+        result.add_view(ctx, "stretch");
+        result
     }
 
-    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        // Only now the canvases of new views exist and have the size of the
+        // image.
         self.update_canvas_contents();
+        if self.orig_data.is_none() {
+            return;
+        }
+        let mut updated = false;
+        // Every region is transformed for its statistics, too slow to do for
+        // each step of a slider.
+        if self.roi_stats_dirty && self.dragging.is_none() {
+            self.update_roi_stats();
+            updated = true;
+        }
+        if self.plots_dirty {
+            self.update_plots();
+            self.update_clouds();
+            updated = true;
+        }
+        if updated {
+            ctx.link().send_message(Msg::RoiStatsUpdated);
+        }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -94,9 +371,17 @@ impl Component for App {
                         .position_info
                         .borrow_mut()
                         .update_for_image(&file_info.img);
-
                     self.file_info = Some(file_info);
-                    self.update_canvas_contents();
+                    self.redraw_original = true;
+                    self.orig_data = None;
+                    self.white_balance.estimates.clear();
+                    // Regions of the previous image are kept, e.g. for
+                    // photos of the same plate.
+                    self.roi_stats_dirty = true;
+                    self.plots_dirty = true;
+                    self.mark_all_dirty();
+                    // Force ImageContainer::view() to be called.
+                    self.count = self.count.wrapping_add(1);
                 }
             }
             Msg::ImageErrored(err_str) => {
@@ -147,26 +432,128 @@ impl Component for App {
 
                 self.state = AppState::DecodingImage(FileInfo { file_data, img });
             }
+            Msg::SetParameter {
+                view_id,
+                slug,
+                value,
+            } => {
+                // The canvases are redrawn with the new parameters in
+                // `rendered()`.
+                if let Some(view) = self.views.iter_mut().find(|v| v.id == view_id) {
+                    view.transform.set_parameter(slug, value);
+                    view.dirty = true;
+                    self.roi_stats_dirty = true;
+                    self.dragging = Some(view_id);
+                }
+            }
+            Msg::ParameterReleased(view_id) => {
+                if self.dragging == Some(view_id) {
+                    // Plot the view now if its last result was drawn while
+                    // dragging, otherwise when it is drawn. The statistics
+                    // of the regions are still marked as out of date.
+                    self.dragging = None;
+                    self.plots_dirty = true;
+                }
+            }
+            Msg::AddView(slug) => {
+                self.add_view(ctx, &slug);
+            }
+            Msg::CubeFiles(files) => self.read_files(ctx, files, Msg::CubeLoaded),
+            Msg::CubeLoaded(file_data) => {
+                log::debug!("Msg::CubeLoaded {}", file_data.name);
+                self.readers.remove(&file_data.name);
+                let text = String::from_utf8_lossy(&file_data.content);
+                match CubeLut::parse(&file_data.name, &text) {
+                    Ok(lut) => self.push_view(ctx, Box::new(lut)),
+                    Err(e) => self.error_log.push(format!("{}: {e}", file_data.name)),
+                }
+            }
+            Msg::RemoveView(view_id) => {
+                self.views.retain(|v| v.id != view_id);
+                self.roi_stats_dirty = true;
+                self.plots_dirty = true;
+            }
+            Msg::DownloadCube(view_id) => {
+                if let Some(view) = self.views.iter().find(|v| v.id == view_id) {
+                    let cube = transform_to_cube(view.transform.as_ref(), self.cube_size);
+                    let file_name = format!("hnb-{}.cube", view.transform.slug());
+                    download_bytes(&file_name, cube.as_bytes());
+                }
+                return false;
+            }
+            Msg::AutoStretch(view_id) => self.auto_stretch(view_id),
+            Msg::SetCubeSize(size) => self.cube_size = size,
+            Msg::SetProcessing(processing) => {
+                self.processing = processing;
+                self.roi_stats_dirty = true;
+                self.mark_all_dirty();
+            }
+            Msg::WorkerReply(reply) => self.handle_reply(reply),
+            Msg::WorkerErrored => {
+                self.error_log.push(
+                    "The image processing worker failed, processing on the main thread.".into(),
+                );
+                self.worker = None;
+                if let AppState::Processing(_) = self.state {
+                    self.state = AppState::Ready;
+                    self.mark_all_dirty();
+                }
+            }
+            Msg::CancelProcessing => {
+                let state = std::mem::replace(&mut self.state, AppState::Ready);
+                if let AppState::Processing(info) = state {
+                    // A busy worker does not receive messages, so replace it.
+                    self.worker = None;
+                    match PixelWorker::spawn(ctx) {
+                        Ok(worker) => self.worker = Some(worker),
+                        Err(e) => {
+                            log::error!("cannot restart worker: {:?}", e);
+                            self.error_log.push(
+                                "The image processing worker could not be restarted, \
+                                 processing on the main thread."
+                                    .into(),
+                            );
+                        }
+                    }
+                    for view in self.views.iter_mut() {
+                        if info.pending.iter().any(|(id, _)| *id == view.id) {
+                            view.cancelled = true;
+                        }
+                    }
+                } else {
+                    self.state = state;
+                }
+            }
+            Msg::ProcessView(view_id) => {
+                if let Some(view) = self.views.iter_mut().find(|v| v.id == view_id) {
+                    view.dirty = true;
+                }
+            }
+            Msg::Pointer(event) => {
+                if !self.white_balance.picking {
+                    return self.draw_region(event);
+                }
+                return match event {
+                    CanvasPointer::Down(x, y) => {
+                        self.pick_white(x, y);
+                        true
+                    }
+                    _ => false,
+                };
+            }
+            Msg::RoiStatsUpdated => {}
+            Msg::Regions(msg) => return self.handle_regions(msg),
+            Msg::Correction(msg) => return self.handle_correction(ctx, msg),
+            Msg::WhiteBalance(msg) => return self.handle_white_balance(msg),
+            Msg::Plots(msg) => return self.handle_plots(msg),
+            Msg::Clouds(msg) => return self.handle_clouds(msg),
+            Msg::Chromaticity(msg) => return self.handle_chromaticity(msg),
+            Msg::Classifier(msg) => return self.handle_classifier(msg),
+            Msg::Optimizer(msg) => return self.handle_optimizer(ctx, msg),
             Msg::Files(files) => {
                 // The user has selected file(s).
                 self.error_log.clear();
-
-                for file in files.into_iter() {
-                    log::debug!("Msg::Files: file {}", file.name());
-                    let file_name = file.name();
-                    let task = {
-                        let file_name = file_name.clone();
-                        let link = ctx.link().clone();
-                        gloo_file::callbacks::read_as_bytes(&file, move |res| {
-                            link.send_message(Msg::FileLoaded(FileData {
-                                name: file_name,
-                                content: res.expect("failed to read file"),
-                            }))
-                        })
-                    };
-                    self.readers.insert(file_name, task);
-                }
-
+                self.read_files(ctx, files, Msg::FileLoaded);
                 self.state = AppState::ReadingFile;
             }
         }
@@ -178,6 +565,8 @@ impl Component for App {
             AppState::Ready => ("Ready", "display-none"),
             AppState::ReadingFile => ("Reading file", "compute-modal"),
             AppState::DecodingImage(_) => ("Decoding image", "compute-modal"),
+            // Shown without blocking the page, see `view_progress()`.
+            AppState::Processing(_) => ("Processing", "display-none"),
         };
 
         // Hmm, on iOS we do not get the original image but a lower quality
@@ -195,7 +584,7 @@ impl Component for App {
                 HNB (Hydroxy naphthol blue) dye."}</p>
                 <h3>{"Color Rotate"}</h3>
                 <p>{"In a Hue-Saturation-Lightness colorspace, the color of each pixel will be \
-                increased 4x in saturation and rotated 180 degrees in Hue. "}
+                increased 4x in saturation and rotated 180 degrees in Hue (by default). "}
                 <a href="https://doi.org/10.1101/2020.06.23.166397 ">{"Kellner et al. (2020)"}</a>
                 {" found that this increases the perceptual ability to distinguish positive vs \
                 negative outcomes of SARS-CoV-2 tests using an isothermal LAMP reaction with \
@@ -229,13 +618,24 @@ impl Component for App {
 
                 { self.view_file_info() }
                 <div id="hnb-app-canvas-div">
-                    <h2><span class="stage">{"2"}</span>{"View the original, Color Stretched and Color Rotated images."}</h2>
+                    <h2><span class="stage">{"2"}</span>{"View the original and the color transformed images."}</h2>
+                    { self.view_add_view(ctx) }
+                    { self.view_progress(ctx) }
+                    { self.view_roi_tools(ctx) }
+                    { self.view_correction(ctx) }
+                    { self.view_white_balance(ctx) }
                     <div id="hnb-app-canvas-container">
-                        <ImageContainer count={self.count} im_type={ImType::Original} canvas_wrapper={self.im_orig.clone()}/>
-                        <ImageContainer count={self.count} im_type={ImType::Rotated} canvas_wrapper={self.im_rotated.clone()}/>
-                        <ImageContainer count={self.count} im_type={ImType::Stretch} canvas_wrapper={self.im_stretch.clone()}/>
+                        <div class="view">
+                            <ImageContainer count={self.count} canvas_wrapper={self.im_orig.clone()}
+                                on_pointer={ctx.link().callback(Msg::Pointer)}/>
+                            { view_plot(&self.plots.orig) }
+                        </div>
+                        { for self.views.iter().map(|view| self.view_view(ctx, view)) }
                     </div>
+                    { self.view_roi_table(ctx) }
+                    { self.view_clouds(ctx) }
                 </div>
+                { self.view_calls(ctx) }
                 { self.view_errors() }
             </div>
         }
@@ -259,6 +659,285 @@ impl App {
         }
     }
 
+    /// Create a new view showing the transform kind `slug`.
+    fn add_view(&mut self, ctx: &Context<Self>, slug: &str) {
+        if let Some(transform) = self.registry.create(slug) {
+            self.push_view(ctx, transform);
+        } else {
+            log::error!("unknown transform {}", slug);
+        }
+    }
+
+    /// Create a new view showing `transform`.
+    fn push_view(&mut self, ctx: &Context<Self>, transform: Box<dyn ColorTransform>) {
+        let canvas_wrapper = Rc::new(RefCell::new(ImCanvasWrapper::new(
+            transform.slug(),
+            transform.name(),
+            ctx.props().position_info.clone(),
+        )));
+        self.views.push(View {
+            id: self.next_view_id,
+            transform,
+            canvas_wrapper,
+            dirty: true,
+            cancelled: false,
+            hue_clusters: None,
+            plot: None,
+        });
+        self.next_view_id += 1;
+        self.roi_stats_dirty = true;
+    }
+
+    /// Read the bytes of `files`, each of which is then sent as `loaded`.
+    fn read_files(
+        &mut self,
+        ctx: &Context<Self>,
+        files: Vec<gloo_file::File>,
+        loaded: fn(FileData) -> Msg,
+    ) {
+        for file in files.into_iter() {
+            log::debug!("reading file {}", file.name());
+            let file_name = file.name();
+            let task = {
+                let file_name = file_name.clone();
+                let link = ctx.link().clone();
+                gloo_file::callbacks::read_as_bytes(&file, move |res| {
+                    link.send_message(loaded(FileData {
+                        name: file_name,
+                        content: res.expect("failed to read file"),
+                    }))
+                })
+            };
+            self.readers.insert(file_name, task);
+        }
+    }
+
+    fn view_add_view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="add-view">
+                <select
+                    onchange={ctx.link().batch_callback(|e: Event| {
+                        let select: HtmlSelectElement = e.target_unchecked_into();
+                        let slug = select.value();
+                        // Show the placeholder again.
+                        select.set_value("");
+                        if slug.is_empty() {
+                            None
+                        } else if let Some(i) = slug.strip_prefix(PRESET_PREFIX) {
+                            i.parse().ok().map(|i| OptimizerMsg::AddPresetView(i).into())
+                        } else {
+                            Some(Msg::AddView(slug))
+                        }
+                    })}
+                >
+                    <option value="" selected=true>{"Add a view..."}</option>
+                    { for self.registry.kinds().iter().map(|kind| html! {
+                        <option value={kind.slug}>{kind.name}</option>
+                    })}
+                    { for self.optimizer.presets.iter().enumerate().map(|(i, preset)| html! {
+                        <option value={format!("{PRESET_PREFIX}{i}")}>
+                            {format!("Preset: {}", preset.name)}
+                        </option>
+                    })}
+                </select>
+                <label>
+                    {"Processing: "}
+                    { view_select(PROCESSING_CHOICES, &self.processing, |p| p.to_string(),
+                        ctx.link().callback(Msg::SetProcessing)) }
+                </label>
+                <label title="The downloaded LUTs approximate the transforms, more closely \
+                    with more nodes.">
+                    {"LUT nodes: "}
+                    { view_select(CUBE_SIZE_CHOICES, &self.cube_size,
+                        |size| format!("{size} per axis"),
+                        ctx.link().callback(Msg::SetCubeSize)) }
+                </label>
+                <FileInput
+                    button_text={"Load a .cube LUT..."}
+                    multiple=true
+                    accept={".cube"}
+                    on_changed={ctx.link().callback(Msg::CubeFiles)}
+                />
+            </div>
+        }
+    }
+
+    fn view_view(&self, ctx: &Context<Self>, view: &View) -> Html {
+        let view_id = view.id;
+        html! {
+            <div class="view" key={view_id}>
+                <ImageContainer count={self.count} canvas_wrapper={view.canvas_wrapper.clone()}/>
+                if view.cancelled {
+                    <p class="stale">
+                        {"Processing was cancelled, the image is out of date. "}
+                        <button
+                            class="btn"
+                            onclick={ctx.link().callback(move |_| Msg::ProcessView(view_id))}
+                        >
+                            {"Process again"}
+                        </button>
+                    </p>
+                }
+                <div class="params">
+                    { for view.transform.parameters().into_iter().map(|p| {
+                        let slug = p.slug;
+                        html! {
+                            <ParamInput label={p.name} value={p.value}
+                                min={p.min} max={p.max} step={p.step}
+                                on_changed={ctx.link().callback(move |value| {
+                                    Msg::SetParameter { view_id, slug, value }
+                                })}
+                                on_released={ctx.link().callback(move |_| {
+                                    Msg::ParameterReleased(view_id)
+                                })}/>
+                        }
+                    })}
+                    if stretch_pipeline(view.transform.as_ref()).is_some()
+                        && self.orig_data.is_some()
+                    {
+                        <button
+                            class="btn"
+                            title="Place the center between the two main hues of the regions, \
+                            or of the whole image without regions."
+                            onclick={ctx.link().callback(move |_| Msg::AutoStretch(view_id))}
+                        >
+                            {"Auto center"}
+                        </button>
+                    }
+                    <button
+                        class="btn"
+                        onclick={ctx.link().callback(move |_| Msg::DownloadCube(view_id))}
+                    >
+                        {"Download .cube LUT"}
+                    </button>
+                    <button
+                        class="btn"
+                        onclick={ctx.link().callback(move |_| Msg::RemoveView(view_id))}
+                    >
+                        {"Remove view"}
+                    </button>
+                </div>
+                { view_plot(&view.plot) }
+                { self.view_hue_wheel(view) }
+            </div>
+        }
+    }
+
+    /// The hue clusters and the stretch center of a view, after the center
+    /// was set automatically.
+    fn view_hue_wheel(&self, view: &View) -> Html {
+        let clusters = match &view.hue_clusters {
+            Some(clusters) => clusters.clone(),
+            None => return html! {},
+        };
+        let value = |slug: &str| {
+            view.transform
+                .parameters()
+                .iter()
+                .find(|p| p.slug == slug)
+                .map_or(0.0, |p| p.value)
+        };
+        html! {
+            <div class="hue-wheel-container">
+                <HueWheel clusters={clusters} center_hue={value("center_hue")}
+                    radius={value("radius")}/>
+                <p>{"Hue clusters (dots, by share of chroma) and the stretch center (cross). \
+                The center is placed to push the two largest clusters furthest apart."}</p>
+            </div>
+        }
+    }
+
+    /// The transforms applied to the original image before the views and
+    /// measurements, in order.
+    fn pre_stages(&self) -> Vec<&dyn ColorTransform> {
+        let mut stages: Vec<&dyn ColorTransform> = Vec::new();
+        if let Some(correction) = &self.correction.correction {
+            stages.push(&correction.ccm);
+        }
+        if let Some(wb) = &self.white_balance.balance {
+            stages.push(wb);
+        }
+        stages
+    }
+
+    /// The uncorrected original image, for finding the chart.
+    fn raw_image(&self) -> Option<RgbaImage> {
+        let image_data = self.raw_data.as_ref()?;
+        RgbaImage::from_raw(image_data.width(), image_data.height(), image_data.data().0).ok()
+    }
+
+    /// Correct the original again and update everything computed from it.
+    fn correction_changed(&mut self) {
+        self.redraw_original = true;
+        self.roi_stats_dirty = true;
+        self.plots_dirty = true;
+        self.mark_all_dirty();
+    }
+
+    fn auto_stretch(&mut self, view_id: u32) {
+        let image_data = match &self.orig_data {
+            Some(image_data) => image_data,
+            None => return,
+        };
+        let view = match self.views.iter_mut().find(|v| v.id == view_id) {
+            Some(view) => view,
+            None => return,
+        };
+        let pipeline = match stretch_pipeline(view.transform.as_ref()) {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        let (width, height) = (image_data.width(), image_data.height());
+        let data = image_data.data();
+        let rois = &self.regions.rois;
+        let pixels = if rois.is_empty() {
+            subsample(&data, MAX_AUTO_PIXELS)
+        } else {
+            let mut pixels = Vec::new();
+            for roi in rois.iter() {
+                pixels.extend(roi.shape.extract_pixels(&data, width, height));
+            }
+            subsample(&pixels, MAX_AUTO_PIXELS)
+        };
+        let mut start = ColorStretch {
+            pipeline,
+            ..Default::default()
+        };
+        for p in view.transform.parameters() {
+            start.set_parameter(p.slug, p.value);
+        }
+        match auto_stretch(&pixels, &start) {
+            Some(auto) => {
+                view.transform
+                    .set_parameter("center_hue", auto.transform.center_hue);
+                view.hue_clusters = Some(auto.clusters);
+                view.dirty = true;
+                self.roi_stats_dirty = true;
+            }
+            None => self
+                .error_log
+                .push("The image does not have two different hues to stretch apart.".into()),
+        }
+    }
+
+    fn view_progress(&self, ctx: &Context<Self>) -> Html {
+        if let AppState::Processing(info) = &self.state {
+            let percent = (100.0 * info.fraction()).round();
+            html! {
+                <div class="progress">
+                    <progress max="100" value={percent.to_string()}></progress>
+                    {format!(" Processing {} of {} views ({percent}%) ",
+                        info.n_views - info.pending.len() + 1, info.n_views)}
+                    <button class="btn" onclick={ctx.link().callback(|_| Msg::CancelProcessing)}>
+                        {"Cancel"}
+                    </button>
+                </div>
+            }
+        } else {
+            html! {}
+        }
+    }
+
     fn view_errors(&self) -> Html {
         if self.error_log.is_empty() {
             html! {}
@@ -271,29 +950,222 @@ impl App {
         }
     }
 
-    /// Redraw the canvases.
+    fn mark_all_dirty(&mut self) {
+        for view in self.views.iter_mut() {
+            view.dirty = true;
+        }
+    }
+
+    /// Redraw the original and start processing the views which changed.
     ///
-    /// We need to do this either when we get a new image decoded or
-    /// when the dimensions of our container change.
+    /// While the worker is busy, changes are collected and processed when
+    /// it is done.
     fn update_canvas_contents(&mut self) {
-        log::debug!("App::update_canvas_contents");
-        if let Some(file_info) = &self.file_info {
-            let im_orig = &mut self.im_orig;
-            let fname = file_info.file_data.name.as_str();
-            im_orig.borrow_mut().draw_image(&file_info.img, fname);
-            let image_data = im_orig.borrow().get_data();
-
-            if let Some(image_data) = image_data {
-                log::debug!("App::update_canvas_contents got image data");
-                let im_rotated = &mut self.im_rotated;
-                im_rotated.borrow_mut().draw_data(&image_data, fname);
-
-                let im_stretch = &mut self.im_stretch;
-                im_stretch.borrow_mut().draw_data(&image_data, fname);
+        let file_info = match &self.file_info {
+            Some(file_info) => file_info,
+            None => return,
+        };
+        let fname = file_info.file_data.name.as_str();
+        if self.redraw_original {
+            log::debug!("App::update_canvas_contents drawing original");
+            self.im_orig.borrow_mut().draw_image(&file_info.img, fname);
+            self.raw_data = self.im_orig.borrow().get_data();
+            let stages = self.pre_stages();
+            let orig_data = match &self.raw_data {
+                Some(raw_data) if !stages.is_empty() => Some(apply_pre_stages(&stages, raw_data)),
+                raw_data => raw_data.clone(),
+            };
+            self.orig_data = orig_data;
+            self.redraw_original = false;
+            self.redraw_rois = true;
+            self.classifier.redraw = true;
+        }
+        // The corrected image, if there are pre-stages.
+        let corrected = self
+            .orig_data
+            .as_ref()
+            .filter(|_| !self.pre_stages().is_empty());
+        if self.redraw_rois {
+            let mut im_orig = self.im_orig.borrow_mut();
+            match corrected {
+                Some(data) => im_orig.draw_data(data, fname, &format!("{fname} (color corrected)")),
+                None => im_orig.draw_image(&file_info.img, fname),
+            }
+            let correction = self.correction.correction.as_ref();
+            if let Some(c) = correction.filter(|c| c.image == fname) {
+                im_orig.draw_chart(&c.chart);
             }
+            let draft = self.regions.draft_shape();
+            im_orig.draw_rois(&self.regions.rois, draft.as_ref());
+            self.redraw_rois = false;
+        }
+        self.classifier
+            .draw(file_info, corrected, &self.regions.rois);
+        if !matches!(self.state, AppState::Ready) || !self.views.iter().any(|v| v.dirty) {
+            return;
+        }
+        let image_data = match &self.orig_data {
+            Some(image_data) => image_data.clone(),
+            None => return,
+        };
+        log::debug!("App::update_canvas_contents processing");
 
-            // Force ImageContainer::view() to be called.
-            self.count = self.count.wrapping_add(1);
+        self.job_id = self.job_id.wrapping_add(1);
+        let (width, height) = (image_data.width(), image_data.height());
+        let mut pending = Vec::new();
+        let plot_rois = self.plots.rois(&self.regions.rois);
+        let mut cloud_view_drawn = false;
+        let cloud_view_id = self.cloud_view().map(|v| v.id);
+        for view in self.views.iter_mut().filter(|v| v.dirty) {
+            view.dirty = false;
+            view.cancelled = false;
+            if let Some(worker) = &self.worker {
+                // A copy of the pixels for each view, moved to the worker.
+                let data: Uint8ClampedArray = Reflect::get(&image_data, &"data".into())
+                    .unwrap()
+                    .unchecked_into();
+                let job = Job {
+                    job_id: self.job_id,
+                    view_id: view.id,
+                    width,
+                    height,
+                    processing: self.processing,
+                    transform: TransformSpec::of(view.transform.as_ref()),
+                    pixels: data.slice(0, data.length()).buffer(),
+                };
+                match job.post(&worker.worker) {
+                    Ok(()) => {
+                        pending.push((view.id, 0.0));
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("posting to the worker failed: {:?}", e);
+                        self.error_log.push(format!(
+                            "{} could not be sent to the image processing worker, \
+                            processing it on the main thread.",
+                            view.transform.name()
+                        ));
+                    }
+                }
+            }
+            // Without a worker, or if the job could not be sent to it.
+            let mut data = image_data.data();
+            self.lut_cache
+                .apply(view.transform.as_ref(), self.processing, &mut data);
+            let result = ImageData::new_with_u8_clamped_array_and_sh(
+                Clamped(data.as_slice()),
+                width,
+                height,
+            )
+            .unwrap();
+            draw_view(view, &result, fname);
+            if self.dragging != Some(view.id) {
+                view.plot = Some(plot_of(&result, plot_rois));
+                cloud_view_drawn |= cloud_view_id == Some(view.id);
+            }
+        }
+        if cloud_view_drawn && self.clouds.is_shown() {
+            self.update_clouds();
         }
+        if !pending.is_empty() {
+            self.state = AppState::Processing(ProcessingInfo {
+                job_id: self.job_id,
+                n_views: pending.len(),
+                pending,
+            });
+        }
+    }
+
+    /// Whether the canvas of `view` shows an outdated result, which is
+    /// replaced when the view is processed.
+    fn is_stale(&self, view: &View) -> bool {
+        view.dirty
+            || view.cancelled
+            || match &self.state {
+                AppState::Processing(info) => info.pending.iter().any(|(id, _)| *id == view.id),
+                _ => false,
+            }
     }
+
+    fn handle_reply(&mut self, reply: Reply) {
+        let info = match &mut self.state {
+            AppState::Processing(info) => info,
+            _ => return,
+        };
+        match reply {
+            Reply::Progress {
+                job_id,
+                view_id,
+                fraction,
+            } if job_id == info.job_id => {
+                if let Some(p) = info.pending.iter_mut().find(|(id, _)| *id == view_id) {
+                    p.1 = fraction;
+                }
+            }
+            Reply::Done {
+                job_id,
+                view_id,
+                width,
+                height,
+                pixels,
+            } if job_id == info.job_id => {
+                info.pending.retain(|(id, _)| *id != view_id);
+                let plot_rois = self.plots.rois(&self.regions.rois);
+                let view = self.views.iter_mut().find(|v| v.id == view_id);
+                if let (Some(view), Some(file_info)) = (view, &self.file_info) {
+                    let data = Uint8ClampedArray::new(&pixels);
+                    let result =
+                        ImageData::new_with_js_u8_clamped_array_and_sh(&data, width, height)
+                            .unwrap();
+                    draw_view(view, &result, &file_info.file_data.name);
+                    if self.dragging != Some(view_id) {
+                        view.plot = Some(plot_of(&result, plot_rois));
+                    }
+                }
+                if self.clouds.is_shown()
+                    && self.dragging != Some(view_id)
+                    && self.cloud_view().map(|v| v.id) == Some(view_id)
+                {
+                    self.update_clouds();
+                }
+            }
+            Reply::Failed {
+                job_id,
+                view_id,
+                message,
+            } if job_id == info.job_id => {
+                info.pending.retain(|(id, _)| *id != view_id);
+                self.error_log.push(format!("Processing failed: {message}"));
+            }
+            _ => {}
+        }
+        if let AppState::Processing(info) = &self.state {
+            if info.pending.is_empty() {
+                self.state = AppState::Ready;
+            }
+        }
+    }
+}
+
+/// Apply the pre-stages to a copy of the original pixels.
+fn apply_pre_stages(stages: &[&dyn ColorTransform], image_data: &ImageData) -> ImageData {
+    let mut data = image_data.data();
+    for stage in stages {
+        stage.apply(&mut data);
+    }
+    ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(data.as_slice()),
+        image_data.width(),
+        image_data.height(),
+    )
+    .unwrap()
+}
+
+/// Draw the transformed pixels of `view`, with a caption.
+fn draw_view(view: &View, result: &ImageData, fname: &str) {
+    let transform = view.transform.as_ref();
+    let caption = format!("{fname}: {} ({})", transform.name(), transform.summary());
+    view.canvas_wrapper
+        .borrow_mut()
+        .draw_data(result, fname, &caption);
 }
@@ -0,0 +1,131 @@
+//! The chromaticity diagram of the regions in the original image.
+
+use web_sys::{Event, HtmlInputElement};
+use yew::{html, Context, Html, TargetCast};
+
+use hnb_core::{
+    chromaticity::{ControlSeparation, Diagram, RegionChromaticity},
+    classify::{Call, Control},
+};
+
+use super::{App, Msg};
+use crate::chromaticity_plot::{ChromaticityPlot, PlottedRegion};
+use crate::roi::{call_color, roi_caption};
+use crate::select::view_select;
+
+/// Pixels of each region in the chromaticity diagram.
+const MAX_CHROMATICITY_POINTS: usize = 2_000;
+
+/// The color of the regions which are not controls in the chromaticity
+/// diagram.
+const REGION_COLOR: &str = "#333";
+
+pub struct Chromaticity {
+    diagram: Diagram,
+    /// Whether the diagram shows only the neighborhood of the regions.
+    zoom: bool,
+    /// The chromaticities of the regions in the original image, in the order
+    /// of the regions.
+    regions: Vec<Option<RegionChromaticity>>,
+}
+
+impl Default for Chromaticity {
+    fn default() -> Self {
+        Self {
+            diagram: Diagram::default(),
+            zoom: true,
+            regions: Vec::new(),
+        }
+    }
+}
+
+impl Chromaticity {
+    pub(super) fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Add the chromaticity of the next region, from its pixels.
+    pub(super) fn add_region(&mut self, pixels: &[u8]) {
+        self.regions.push(RegionChromaticity::of_pixels(
+            pixels,
+            MAX_CHROMATICITY_POINTS,
+        ));
+    }
+}
+
+pub enum ChromaticityMsg {
+    SetDiagram(Diagram),
+    Zoom(bool),
+}
+
+impl From<ChromaticityMsg> for Msg {
+    fn from(msg: ChromaticityMsg) -> Self {
+        Msg::Chromaticity(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_chromaticity(&mut self, msg: ChromaticityMsg) -> bool {
+        match msg {
+            ChromaticityMsg::SetDiagram(diagram) => self.chromaticity.diagram = diagram,
+            ChromaticityMsg::Zoom(zoom) => self.chromaticity.zoom = zoom,
+        }
+        true
+    }
+
+    /// The chromaticity diagram of the regions in the original image, with the
+    /// controls.
+    pub(super) fn view_chromaticity(&self, ctx: &Context<Self>) -> Html {
+        let chromaticity = &self.chromaticity;
+        let regions: Vec<PlottedRegion> = self
+            .regions
+            .rois
+            .iter()
+            .zip(chromaticity.regions.iter())
+            .filter_map(|(roi, region)| {
+                let color = match roi.control {
+                    Some(Control::Positive) => call_color(Call::Positive),
+                    Some(Control::Negative) => call_color(Call::Negative),
+                    None => REGION_COLOR,
+                };
+                Some(PlottedRegion {
+                    label: roi_caption(roi),
+                    color,
+                    chromaticity: region.clone()?,
+                })
+            })
+            .collect();
+        if regions.is_empty() {
+            return html! {};
+        }
+        let controls = self
+            .classifier
+            .reference
+            .as_ref()
+            .map(ControlSeparation::new);
+        html! {
+            <div class="chromaticity">
+                <h3>{"Chromaticity of the regions"}</h3>
+                <label>
+                    {"Diagram: "}
+                    { view_select(&Diagram::ALL, &chromaticity.diagram, |d| d.name().to_string(),
+                        ctx.link().callback(ChromaticityMsg::SetDiagram)) }
+                </label>
+                {" "}
+                <label>
+                    <input type="checkbox" checked={chromaticity.zoom}
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            ChromaticityMsg::Zoom(input.checked())
+                        })}/>
+                    {"Zoom to the regions"}
+                </label>
+                <ChromaticityPlot diagram={chromaticity.diagram} regions={regions}
+                    controls={controls} zoom={chromaticity.zoom}/>
+                <p class="hint">{"Dots are pixels and circles the mean colors of the regions. \
+                The squares are the controls, with the distance between them in the diagram \
+                and in CIELAB."}</p>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Calling the regions positive or negative, and drawing the calls.
+
+use std::{cell::RefCell, rc::Rc};
+
+use web_sys::ImageData;
+use yew::{html, Context, Html};
+
+use hnb_core::{
+    classify::{
+        classify_regions, CallCounts, Classification, ClassifierParams, Control, ControlReference,
+    },
+    roi::Roi,
+    stats::ColorStats,
+};
+
+use super::{App, FileInfo, Msg, ORIGINAL};
+use crate::image_container::{ImCanvasWrapper, ImageContainer};
+use crate::param_input::ParamInput;
+use crate::PositionInfo;
+
+pub struct Classifier {
+    params: ClassifierParams,
+    /// The calls of the regions, in the order of the regions, from the
+    /// original image.
+    pub(super) calls: Vec<Option<Classification>>,
+    /// The colors of the controls, if there are controls of both kinds.
+    pub(super) reference: Option<ControlReference>,
+    /// The original image with the calls.
+    image: Rc<RefCell<ImCanvasWrapper>>,
+    /// Whether the calls need to be drawn again.
+    pub(super) redraw: bool,
+}
+
+impl Classifier {
+    pub(super) fn new(position_info: Rc<RefCell<PositionInfo>>) -> Self {
+        Self {
+            params: ClassifierParams::default(),
+            calls: Vec::new(),
+            reference: None,
+            image: Rc::new(RefCell::new(ImCanvasWrapper::new(
+                "calls",
+                "Calls",
+                position_info,
+            ))),
+            redraw: false,
+        }
+    }
+
+    /// E.g. "5 positive / 3 negative / 0 inconclusive".
+    fn summary(&self) -> String {
+        CallCounts::of(self.calls.iter().flatten().map(|c| &c.call)).to_string()
+    }
+
+    /// Draw the calls on the original image, or on its corrected pixels, if
+    /// they changed.
+    pub(super) fn draw(
+        &mut self,
+        file_info: &FileInfo,
+        corrected: Option<&ImageData>,
+        rois: &[Roi],
+    ) {
+        if !self.redraw {
+            return;
+        }
+        let fname = file_info.file_data.name.as_str();
+        let caption = format!("{fname}: {}", self.summary());
+        let mut image = self.image.borrow_mut();
+        match corrected {
+            Some(data) => image.draw_data(data, fname, &caption),
+            None => image.draw_image_captioned(&file_info.img, fname, &caption),
+        }
+        image.draw_badges(rois, &self.calls);
+        self.redraw = false;
+    }
+}
+
+pub enum ClassifierMsg {
+    SetParameter { slug: &'static str, value: f32 },
+}
+
+impl From<ClassifierMsg> for Msg {
+    fn from(msg: ClassifierMsg) -> Self {
+        Msg::Classifier(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_classifier(&mut self, msg: ClassifierMsg) -> bool {
+        match msg {
+            ClassifierMsg::SetParameter { slug, value } => {
+                self.classifier.params.set_parameter(slug, value);
+                self.update_calls();
+            }
+        }
+        true
+    }
+
+    /// Call the regions other than the controls from their statistics in the
+    /// original image, relative to the controls if there are both kinds.
+    pub(super) fn update_calls(&mut self) {
+        let rows = &self.regions.rows;
+        let regions: Vec<(Option<Control>, Option<&ColorStats>)> = self
+            .regions
+            .rois
+            .iter()
+            .map(|roi| {
+                let stats = rows
+                    .iter()
+                    .find(|r| r.roi == roi.label && r.image == ORIGINAL)
+                    .and_then(|r| r.stats.as_ref());
+                (roi.control, stats)
+            })
+            .collect();
+        let classifier = &mut self.classifier;
+        let (calls, reference) = classify_regions(&regions, &classifier.params);
+        classifier.calls = calls;
+        classifier.reference = reference;
+        classifier.redraw = true;
+    }
+
+    /// The calls drawn on the image, with the settings of the classifier.
+    ///
+    /// Always rendered, but hidden without regions, so that the canvas stays
+    /// the same.
+    pub(super) fn view_calls(&self, ctx: &Context<Self>) -> Html {
+        let classifier = &self.classifier;
+        let class = if self.regions.rois.is_empty() {
+            "display-none"
+        } else {
+            ""
+        };
+        let parameters = match classifier.reference {
+            Some(_) => classifier.params.control_parameters(),
+            None => classifier.params.parameters(),
+        };
+        html! {
+            <div class={class}>
+                <h2><span class="stage">{"3"}</span>{"Call the reactions."}</h2>
+                { view_call_method(classifier.reference.as_ref()) }
+                <p class="call-summary">{classifier.summary()}</p>
+                <div class="params">
+                    { for parameters.into_iter().map(|p| {
+                        let slug = p.slug;
+                        html! {
+                            <ParamInput label={p.name} value={p.value}
+                                min={p.min} max={p.max} step={p.step}
+                                on_changed={ctx.link().callback(move |value| {
+                                    ClassifierMsg::SetParameter { slug, value }
+                                })}/>
+                        }
+                    })}
+                </div>
+                <ImageContainer count={self.count} canvas_wrapper={classifier.image.clone()}/>
+                { self.view_chromaticity(ctx) }
+                { self.view_optimizer(ctx) }
+            </div>
+        }
+    }
+}
+
+/// How the calls are made: relative to the controls or by a fixed hue.
+fn view_call_method(reference: Option<&ControlReference>) -> Html {
+    match reference {
+        Some(reference) => html! {
+            <p>{format!("Each region is scored by the position of its median color \
+            between the negative (0) and the positive (1) controls in CIELAB, which are \
+            ΔE*ab {:.1} apart. Scores above 0.5 are positive and below negative. Scores \
+            close to 0.5 and colors far from the line through the controls are \
+            inconclusive.", reference.separation())}</p>
+        },
+        None => html! {
+            <p>{"A region is positive (sky blue) if the CIELAB hue of its median color is \
+            below the boundary hue and negative (violet) if above. Regions close to the \
+            boundary, with little chroma or a hue outside the range of HNB are \
+            inconclusive. Mark regions as positive and negative controls in the table \
+            to call relative to the controls instead."}</p>
+        },
+    }
+}
@@ -0,0 +1,180 @@
+//! The colors of the original and of a view as rotatable 3D clouds.
+
+use std::rc::Rc;
+
+use web_sys::{Event, HtmlInputElement, ImageData};
+use yew::{html, Context, Html, TargetCast};
+
+use hnb_core::{
+    point_cloud::{CloudSpace, PointCloud},
+    roi::Roi,
+};
+
+use super::{App, Msg, View, ORIGINAL};
+use crate::color_cloud::ColorCloud;
+use crate::select::view_select;
+
+/// Points of each 3D cloud of colors, to keep turning it smooth.
+const MAX_CLOUD_POINTS: usize = 30_000;
+
+/// The colors of the regions in the 3D clouds, in turn.
+const REGION_COLORS: [[u8; 3]; 8] = [
+    [255, 140, 0],
+    [0, 200, 255],
+    [255, 60, 200],
+    [255, 230, 0],
+    [90, 230, 60],
+    [255, 50, 50],
+    [120, 120, 255],
+    [240, 240, 240],
+];
+
+#[derive(Default)]
+pub struct Clouds {
+    /// Whether the colors are shown in 3D.
+    show: bool,
+    space: CloudSpace,
+    /// The view whose colors are shown next to those of the original, the
+    /// first view if not set or removed.
+    view: Option<u32>,
+    /// The titles and 3D clouds of the colors of the original and the view.
+    clouds: Rc<Vec<(String, PointCloud)>>,
+}
+
+impl Clouds {
+    pub(super) fn is_shown(&self) -> bool {
+        self.show
+    }
+}
+
+pub enum CloudsMsg {
+    Show(bool),
+    SetSpace(CloudSpace),
+    /// Show the colors of the view with this id next to the original.
+    SetView(u32),
+}
+
+impl From<CloudsMsg> for Msg {
+    fn from(msg: CloudsMsg) -> Self {
+        Msg::Clouds(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_clouds(&mut self, msg: CloudsMsg) -> bool {
+        match msg {
+            CloudsMsg::Show(show) => self.clouds.show = show,
+            CloudsMsg::SetSpace(space) => self.clouds.space = space,
+            CloudsMsg::SetView(view_id) => self.clouds.view = Some(view_id),
+        }
+        self.update_clouds();
+        true
+    }
+
+    /// The view whose colors are shown next to those of the original.
+    pub(super) fn cloud_view(&self) -> Option<&View> {
+        self.views
+            .iter()
+            .find(|v| Some(v.id) == self.clouds.view)
+            .or_else(|| self.views.first())
+    }
+
+    /// Make the 3D clouds of the colors of the original and of the view
+    /// compared with it, if they are shown.
+    pub(super) fn update_clouds(&mut self) {
+        let orig_data = match (&self.orig_data, self.clouds.show) {
+            (Some(orig_data), true) => orig_data,
+            _ => {
+                self.clouds.clouds = Rc::new(Vec::new());
+                return;
+            }
+        };
+        let rois = &self.regions.rois;
+        let space = self.clouds.space;
+        let mut clouds = vec![(ORIGINAL.to_string(), cloud_of(orig_data, rois, space))];
+        if let Some(view) = self.cloud_view().filter(|v| !self.is_stale(v)) {
+            // A stale view is shown when it is drawn.
+            if let Some(data) = view.canvas_wrapper.borrow().get_data() {
+                clouds.push((
+                    view.transform.name().to_string(),
+                    cloud_of(&data, rois, space),
+                ));
+            }
+        }
+        self.clouds.clouds = Rc::new(clouds);
+    }
+
+    pub(super) fn view_clouds(&self, ctx: &Context<Self>) -> Html {
+        if self.orig_data.is_none() {
+            return html! {};
+        }
+        let clouds = &self.clouds;
+        let rois = &self.regions.rois;
+        let view_ids: Vec<u32> = self.views.iter().map(|v| v.id).collect();
+        let compared = self.cloud_view().map_or(0, |v| v.id);
+        let view_name = |id: &u32| {
+            self.views
+                .iter()
+                .find(|v| v.id == *id)
+                .map_or_else(String::new, |v| v.transform.name().to_string())
+        };
+        html! {
+            <div class="clouds">
+                <label>
+                    <input type="checkbox" checked={clouds.show}
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            CloudsMsg::Show(input.checked())
+                        })}/>
+                    {"Show the colors in 3D"}
+                </label>
+                if clouds.show {
+                    {" "}
+                    <label>
+                        {"Space: "}
+                        { view_select(&CloudSpace::ALL, &clouds.space, |s| s.name().to_string(),
+                            ctx.link().callback(CloudsMsg::SetSpace)) }
+                    </label>
+                    if !view_ids.is_empty() {
+                        {" "}
+                        <label>
+                            {"Compare with: "}
+                            { view_select(&view_ids, &compared, view_name,
+                                ctx.link().callback(CloudsMsg::SetView)) }
+                        </label>
+                    }
+                    <ColorCloud clouds={clouds.clouds.clone()}/>
+                    if !rois.is_empty() {
+                        <p class="legend">
+                            { for rois.iter().zip(REGION_COLORS.iter().cycle()).map(|(roi, [r, g, b])| html! {
+                                <span>
+                                    <span class="swatch"
+                                        style={format!("background: rgb({r}, {g}, {b})")}/>
+                                    {roi.label.as_str()}
+                                </span>
+                            })}
+                        </p>
+                    }
+                    <p class="hint">{"Drag to turn, scroll to zoom, double click to reset."}</p>
+                }
+            </div>
+        }
+    }
+}
+
+/// The 3D cloud of the colors of the regions of an image, each region in its
+/// own color, or of the whole image without regions.
+fn cloud_of(image_data: &ImageData, rois: &[Roi], space: CloudSpace) -> PointCloud {
+    let data = image_data.data();
+    let mut cloud = PointCloud::new(space);
+    if rois.is_empty() {
+        cloud.add_pixels(&data, MAX_CLOUD_POINTS, None);
+    } else {
+        let (width, height) = (image_data.width(), image_data.height());
+        for (roi, color) in rois.iter().zip(REGION_COLORS.iter().cycle()) {
+            let pixels = roi.shape.extract_pixels(&data, width, height);
+            cloud.add_pixels(&pixels, MAX_CLOUD_POINTS / rois.len(), Some(*color));
+        }
+    }
+    cloud
+}
@@ -0,0 +1,199 @@
+//! Correcting the colors with a reference chart in the image.
+
+use yew::{html, Context, Html};
+
+use hnb_core::calibration::{detect_chart, CcmModel, ChartDetection, ChartLayout};
+
+use super::{App, FileData, Msg};
+use crate::calibration::{chart_polygon, Correction};
+use crate::file_input::FileInput;
+use crate::select::view_select;
+
+pub struct ColorCorrection {
+    /// The built-in reference charts and those loaded by the user.
+    layouts: Vec<ChartLayout>,
+    /// The chart to look for.
+    layout: ChartLayout,
+    model: CcmModel,
+    /// The color correction, kept for the next images, e.g. taken with the
+    /// same camera and light.
+    pub(super) correction: Option<Correction>,
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        let layout = ChartLayout::color_checker_classic();
+        Self {
+            layouts: vec![layout.clone()],
+            layout,
+            model: CcmModel::default(),
+            correction: None,
+        }
+    }
+}
+
+pub enum CorrectionMsg {
+    /// Find the reference chart in the image and correct the colors with it.
+    Detect,
+    /// Correct the colors with the chart whose corner patches are marked by
+    /// the last polygon.
+    FromPolygon,
+    Remove,
+    SetLayout(ChartLayout),
+    SetModel(CcmModel),
+    /// The user has selected file(s) describing reference charts.
+    Files(Vec<gloo_file::File>),
+    /// The bytes of a chart description have been read.
+    Loaded(FileData),
+}
+
+impl From<CorrectionMsg> for Msg {
+    fn from(msg: CorrectionMsg) -> Self {
+        Msg::Correction(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_correction(&mut self, ctx: &Context<Self>, msg: CorrectionMsg) -> bool {
+        match msg {
+            CorrectionMsg::Detect => {
+                let image = match self.raw_image() {
+                    Some(image) => image,
+                    None => return false,
+                };
+                let layout = &self.correction.layout;
+                match detect_chart(&image, layout) {
+                    Some(chart) => self.set_correction(chart),
+                    None => self.error_log.push(format!(
+                        "No {} was found in the image. Mark the centers of its corner \
+                        patches with a polygon instead.",
+                        layout.name
+                    )),
+                }
+            }
+            CorrectionMsg::FromPolygon => {
+                let polygon = chart_polygon(&self.regions.rois);
+                let (image, (i, corners)) = match (self.raw_image(), polygon) {
+                    (Some(image), Some(polygon)) => (image, polygon),
+                    _ => return false,
+                };
+                let chart = ChartDetection::from_corners(&image, &self.correction.layout, corners);
+                self.regions.rois.remove(i);
+                self.regions_changed();
+                self.set_correction(chart);
+            }
+            CorrectionMsg::Remove => {
+                self.correction.correction = None;
+                self.correction_changed();
+            }
+            CorrectionMsg::SetLayout(layout) => self.correction.layout = layout,
+            CorrectionMsg::SetModel(model) => {
+                self.correction.model = model;
+                if let Some(c) = self.correction.correction.take() {
+                    self.correction.correction =
+                        Correction::fit(&c.image, &c.layout, c.chart, model);
+                    self.correction_changed();
+                }
+            }
+            CorrectionMsg::Files(files) => self.read_files(ctx, files, |file_data| {
+                CorrectionMsg::Loaded(file_data).into()
+            }),
+            CorrectionMsg::Loaded(file_data) => {
+                log::debug!("CorrectionMsg::Loaded {}", file_data.name);
+                self.readers.remove(&file_data.name);
+                let text = String::from_utf8_lossy(&file_data.content);
+                match ChartLayout::parse(&file_data.name, &text) {
+                    Ok(layout) => {
+                        self.correction.layouts.push(layout.clone());
+                        self.correction.layout = layout;
+                    }
+                    Err(e) => self.error_log.push(format!("{}: {e}", file_data.name)),
+                }
+            }
+        }
+        true
+    }
+
+    /// Correct the colors with the chart found in the current image.
+    fn set_correction(&mut self, chart: ChartDetection) {
+        let image = match &self.file_info {
+            Some(file_info) => file_info.file_data.name.clone(),
+            None => return,
+        };
+        let correction = &mut self.correction;
+        correction.correction =
+            Correction::fit(&image, &correction.layout, chart, correction.model);
+        if correction.correction.is_none() {
+            self.error_log.push(format!(
+                "The colors of the {} do not determine a correction.",
+                correction.layout.name
+            ));
+        }
+        self.correction_changed();
+    }
+
+    pub(super) fn view_correction(&self, ctx: &Context<Self>) -> Html {
+        let correction = &self.correction;
+        let report = match &correction.correction {
+            Some(c) => html! {
+                <p>
+                    {format!("{} with {} patches of the {} in {}: ΔE*ab {:.1} on average, at \
+                        most {:.1} (without correction {:.1}, at most {:.1}). ",
+                        c.ccm.model.name(), c.layout.reference.len(), c.layout.name, c.image,
+                        c.after.0, c.after.1, c.before.0, c.before.1)}
+                    <button class="btn" onclick={ctx.link().callback(|_| CorrectionMsg::Remove)}>
+                        {"Remove correction"}
+                    </button>
+                </p>
+            },
+            None => html! {},
+        };
+        html! {
+            <div class="correction">
+                <h3>{"Color correction"}</h3>
+                <p>{"With a reference chart in the photo, the colors are corrected to those of \
+                the chart before all views and measurements, making photos of different \
+                cameras and lights comparable. The chart is found automatically, or mark the \
+                centers of its top left, top right, bottom right and bottom left patches, in \
+                this order, with a polygon. A chart file has a line with the numbers of rows \
+                and columns, then the sRGB color of each patch, row by row, as three numbers \
+                from 0 to 255 per line."}</p>
+                <p>
+                    <label>
+                        {"Chart: "}
+                        { view_select(&correction.layouts, &correction.layout,
+                            |l| format!("{} ({}x{})", l.name, l.rows, l.cols),
+                            ctx.link().callback(CorrectionMsg::SetLayout)) }
+                    </label>
+                    <FileInput
+                        button_text={"Load a chart..."}
+                        multiple=false
+                        accept={".txt,.csv"}
+                        on_changed={ctx.link().callback(CorrectionMsg::Files)}
+                    />
+                    <label>
+                        {" Model: "}
+                        { view_select(&CcmModel::ALL, &correction.model, |m| m.name().to_string(),
+                            ctx.link().callback(CorrectionMsg::SetModel)) }
+                    </label>
+                </p>
+                if self.raw_data.is_some() {
+                    <p>
+                        <button class="btn"
+                            onclick={ctx.link().callback(|_| CorrectionMsg::Detect)}>
+                            {"Detect chart"}
+                        </button>
+                        if chart_polygon(&self.regions.rois).is_some() {
+                            {" "}
+                            <button class="btn"
+                                onclick={ctx.link().callback(|_| CorrectionMsg::FromPolygon)}>
+                                {"Use the polygon as chart corners"}
+                            </button>
+                        }
+                    </p>
+                }
+                {report}
+            </div>
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Tuning Color Stretch with the controls, and the saved presets.
+
+use yew::{html, Context, Html};
+
+use hnb_core::{
+    classify::Control,
+    color_transform::{ColorStretch, ColorTransform, Preset},
+    optimize::{optimize_stretch, StretchFit},
+};
+
+use super::{App, Msg};
+use crate::presets::{control_regions, save_presets, TrainingRegion};
+
+pub struct Optimizer {
+    /// Control regions, possibly of several images, to tune Color Stretch.
+    training: Vec<TrainingRegion>,
+    fit: Option<StretchFit>,
+    /// Saved parameters of transforms, also kept in the browser.
+    pub(super) presets: Vec<Preset>,
+}
+
+impl Optimizer {
+    pub(super) fn new(presets: Vec<Preset>) -> Self {
+        Self {
+            training: Vec::new(),
+            fit: None,
+            presets,
+        }
+    }
+}
+
+pub enum OptimizerMsg {
+    /// Add the controls of the image to the training set and tune Color
+    /// Stretch to separate the controls.
+    Optimize,
+    ClearTraining,
+    /// Add a view with the optimized Color Stretch.
+    AddFitView,
+    /// Save the optimized Color Stretch as preset.
+    SaveFitPreset,
+    AddPresetView(usize),
+    RemovePreset(usize),
+}
+
+impl From<OptimizerMsg> for Msg {
+    fn from(msg: OptimizerMsg) -> Self {
+        Msg::Optimizer(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_optimizer(&mut self, ctx: &Context<Self>, msg: OptimizerMsg) -> bool {
+        match msg {
+            OptimizerMsg::Optimize => self.optimize_stretch(),
+            OptimizerMsg::ClearTraining => {
+                self.optimizer.training.clear();
+                self.optimizer.fit = None;
+            }
+            OptimizerMsg::AddFitView => {
+                if let Some(fit) = &self.optimizer.fit {
+                    let transform = Box::new(fit.transform.clone());
+                    self.push_view(ctx, transform);
+                }
+            }
+            OptimizerMsg::SaveFitPreset => {
+                if let Some(fit) = &self.optimizer.fit {
+                    let presets = &mut self.optimizer.presets;
+                    let name = (1..)
+                        .map(|i| format!("Optimized Color Stretch {i}"))
+                        .find(|name| presets.iter().all(|p| &p.name != name))
+                        .unwrap();
+                    presets.push(Preset::new(&name, &fit.transform));
+                    self.save_presets();
+                }
+            }
+            OptimizerMsg::AddPresetView(i) => {
+                let preset = match self.optimizer.presets.get(i) {
+                    Some(preset) => preset,
+                    None => return false,
+                };
+                match preset.create(&self.registry) {
+                    Some(transform) => self.push_view(ctx, transform),
+                    None => self
+                        .error_log
+                        .push(format!("Unknown transform in preset {}.", preset.name)),
+                }
+            }
+            OptimizerMsg::RemovePreset(i) => {
+                if i < self.optimizer.presets.len() {
+                    self.optimizer.presets.remove(i);
+                    self.save_presets();
+                }
+            }
+        }
+        true
+    }
+
+    fn optimize_stretch(&mut self) {
+        let optimizer = &mut self.optimizer;
+        if let (Some(file_info), Some(image_data)) = (&self.file_info, &self.orig_data) {
+            let image = file_info.file_data.name.as_str();
+            optimizer.training.retain(|t| t.image != image);
+            optimizer.training.extend(control_regions(
+                image,
+                &self.regions.rois,
+                &image_data.data(),
+                image_data.width(),
+                image_data.height(),
+            ));
+        }
+        let samples: Vec<_> = optimizer
+            .training
+            .iter()
+            .map(|t| t.sample.clone())
+            .collect();
+        optimizer.fit = optimize_stretch(&ColorStretch::default(), &samples);
+        if optimizer.fit.is_none() {
+            self.error_log.push(
+                "Mark regions as positive and negative controls to optimize Color Stretch.".into(),
+            );
+        }
+    }
+
+    fn save_presets(&mut self) {
+        if let Err(e) = save_presets(&self.optimizer.presets) {
+            self.error_log.push(e);
+        }
+    }
+
+    /// Tuning Color Stretch with the controls, and the saved presets.
+    pub(super) fn view_optimizer(&self, ctx: &Context<Self>) -> Html {
+        let optimizer = &self.optimizer;
+        let count = |control| {
+            optimizer
+                .training
+                .iter()
+                .filter(|t| t.sample.control == control)
+                .count()
+        };
+        let mut images: Vec<&str> = optimizer
+            .training
+            .iter()
+            .map(|t| t.image.as_str())
+            .collect();
+        images.dedup();
+        let fit = match &optimizer.fit {
+            Some(fit) => html! {
+                <p>
+                    {format!("{}: separation {:.2} (default parameters {:.2}). ",
+                        fit.transform.summary(), fit.separation, fit.initial_separation)}
+                    <button class="btn"
+                        onclick={ctx.link().callback(|_| OptimizerMsg::AddFitView)}>
+                        {"Add as view"}
+                    </button>
+                    {" "}
+                    <button class="btn"
+                        onclick={ctx.link().callback(|_| OptimizerMsg::SaveFitPreset)}>
+                        {"Save as preset"}
+                    </button>
+                </p>
+            },
+            None => html! {},
+        };
+        html! {
+            <div class="optimizer">
+                <h3>{"Optimize Color Stretch"}</h3>
+                <p>{"Find the center hue, radius and saturation gain of Color Stretch which \
+                best separate the colors of the positive from the negative controls. The \
+                controls of each image are added to the training set, so the parameters can \
+                be tuned for several images."}</p>
+                <p>
+                    {format!("Training set: {} positive and {} negative controls from {} \
+                        images. ", count(Control::Positive), count(Control::Negative),
+                        images.len())}
+                    <button class="btn" onclick={ctx.link().callback(|_| OptimizerMsg::Optimize)}>
+                        {"Add the controls and optimize"}
+                    </button>
+                    {" "}
+                    <button class="btn"
+                        onclick={ctx.link().callback(|_| OptimizerMsg::ClearTraining)}>
+                        {"Clear"}
+                    </button>
+                </p>
+                {fit}
+                { self.view_presets(ctx) }
+            </div>
+        }
+    }
+
+    fn view_presets(&self, ctx: &Context<Self>) -> Html {
+        let presets = &self.optimizer.presets;
+        if presets.is_empty() {
+            return html! {};
+        }
+        html! {
+            <>
+                <p>{"Saved presets, which are also in the list of views to add. The options \
+                apply them with the command line tool."}</p>
+                <ul class="presets">
+                    { for presets.iter().enumerate().map(|(i, preset)| html! {
+                        <li>
+                            {preset.name.as_str()}{": "}<code>{preset.cli_args()}</code>{" "}
+                            <button class="btn"
+                                onclick={ctx.link().callback(move |_| OptimizerMsg::RemovePreset(i))}>
+                                {"Remove"}
+                            </button>
+                        </li>
+                    })}
+                </ul>
+            </>
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! The hue and chroma plots of the original and of the views.
+
+use web_sys::{Event, HtmlInputElement, ImageData};
+use yew::{html, Context, Html, TargetCast};
+
+use hnb_core::{hue_chroma::HueChroma, roi::Roi};
+
+use super::{App, Msg};
+use crate::hue_plot::HuePlot;
+
+/// Pixels counted for the hue and chroma plots, to keep them fast.
+const MAX_PLOT_PIXELS: usize = 200_000;
+
+#[derive(Default)]
+pub struct Plots {
+    /// Whether the plots show the regions rather than the whole images.
+    regions: bool,
+    pub(super) orig: Option<HueChroma>,
+}
+
+impl Plots {
+    /// The regions to plot, `None` to plot the whole images.
+    pub(super) fn rois<'a>(&self, rois: &'a [Roi]) -> Option<&'a [Roi]> {
+        (self.regions && !rois.is_empty()).then_some(rois)
+    }
+}
+
+pub enum PlotsMsg {
+    SetRegions(bool),
+}
+
+impl From<PlotsMsg> for Msg {
+    fn from(msg: PlotsMsg) -> Self {
+        Msg::Plots(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_plots(&mut self, msg: PlotsMsg) -> bool {
+        match msg {
+            PlotsMsg::SetRegions(regions) => self.plots.regions = regions,
+        }
+        self.update_plots();
+        true
+    }
+
+    /// Plot the hues and chromas of the original and of the views again, e.g.
+    /// after the regions changed.
+    pub(super) fn update_plots(&mut self) {
+        self.plots_dirty = false;
+        let rois = self.plots.rois(&self.regions.rois);
+        self.plots.orig = self.orig_data.as_ref().map(|data| plot_of(data, rois));
+        let current: Vec<u32> = self
+            .views
+            .iter()
+            .filter(|v| !self.is_stale(v))
+            .map(|v| v.id)
+            .collect();
+        for view in self.views.iter_mut().filter(|v| current.contains(&v.id)) {
+            // Stale views are plotted when they are drawn.
+            if let Some(data) = view.canvas_wrapper.borrow().get_data() {
+                view.plot = Some(plot_of(&data, rois));
+            }
+        }
+    }
+
+    /// The choice between plotting the regions and the whole images.
+    pub(super) fn view_plot_regions(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <label>
+                <input type="checkbox" checked={self.plots.regions}
+                    onchange={ctx.link().callback(|e: Event| {
+                        let input: HtmlInputElement = e.target_unchecked_into();
+                        PlotsMsg::SetRegions(input.checked())
+                    })}/>
+                {"Plot the hues of the regions only"}
+            </label>
+        }
+    }
+}
+
+pub(super) fn view_plot(plot: &Option<HueChroma>) -> Html {
+    match plot {
+        Some(plot) => html! { <HuePlot plot={plot.clone()}/> },
+        None => html! {},
+    }
+}
+
+/// The hues and chromas of the regions of an image, or of the whole image
+/// without regions.
+pub(super) fn plot_of(image_data: &ImageData, rois: Option<&[Roi]>) -> HueChroma {
+    let data = image_data.data();
+    match rois {
+        Some(rois) => {
+            let (width, height) = (image_data.width(), image_data.height());
+            let pixels: Vec<u8> = rois
+                .iter()
+                .flat_map(|roi| roi.shape.extract_pixels(&data, width, height))
+                .collect();
+            HueChroma::of_pixels(&pixels, MAX_PLOT_PIXELS)
+        }
+        None => HueChroma::of_pixels(&data, MAX_PLOT_PIXELS),
+    }
+}
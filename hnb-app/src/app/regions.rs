@@ -0,0 +1,261 @@
+//! The regions of interest: drawing and detecting them, and their statistics.
+
+use yew::{html, Context, Html};
+
+use hnb_core::{
+    classify::Control,
+    detect::{detect_tubes, DetectionParams},
+    image::RgbaImage,
+    roi::{Roi, Shape},
+    stats::ColorStats,
+};
+
+use super::{App, Msg, ORIGINAL};
+use crate::image_container::CanvasPointer;
+use crate::roi::{handle_pointer, view_roi_table, RoiDraft, RoiRow, RoiTool};
+use crate::select::view_select;
+
+pub struct Regions {
+    pub(super) rois: Vec<Roi>,
+    tool: RoiTool,
+    /// The region being drawn.
+    draft: Option<RoiDraft>,
+    /// Number of the next region, used in its label.
+    next_roi: u32,
+    /// The statistics of the regions in the original and in each view.
+    pub(super) rows: Vec<RoiRow>,
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Self {
+            rois: Vec::new(),
+            tool: RoiTool::Rect,
+            draft: None,
+            next_roi: 1,
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl Regions {
+    /// The shape of the region being drawn, if any.
+    pub(super) fn draft_shape(&self) -> Option<Shape> {
+        self.draft.as_ref().map(RoiDraft::shape)
+    }
+}
+
+pub enum RegionsMsg {
+    SetTool(RoiTool),
+    Remove(usize),
+    Clear,
+    /// Add the automatically detected tubes not yet marked as regions,
+    /// keeping the other regions.
+    DetectTubes,
+    /// Mark a region as a control, or not.
+    SetControl(usize, Option<Control>),
+}
+
+impl From<RegionsMsg> for Msg {
+    fn from(msg: RegionsMsg) -> Self {
+        Msg::Regions(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_regions(&mut self, msg: RegionsMsg) -> bool {
+        match msg {
+            RegionsMsg::SetTool(tool) => {
+                self.regions.tool = tool;
+                if self.regions.draft.take().is_some() {
+                    self.redraw_rois = true;
+                }
+            }
+            RegionsMsg::Remove(index) => {
+                if index < self.regions.rois.len() {
+                    self.regions.rois.remove(index);
+                    self.regions_changed();
+                }
+            }
+            RegionsMsg::Clear => {
+                self.regions.rois.clear();
+                self.regions.draft = None;
+                self.regions.next_roi = 1;
+                self.regions_changed();
+            }
+            RegionsMsg::DetectTubes => self.detect_tubes(),
+            RegionsMsg::SetControl(i, control) => {
+                if let Some(roi) = self.regions.rois.get_mut(i) {
+                    roi.control = control;
+                    self.update_calls();
+                    self.redraw_rois = true;
+                }
+            }
+        }
+        true
+    }
+
+    /// Draw a region with a pointer event on the original image. Returns
+    /// whether the page needs to be rendered again.
+    pub(super) fn draw_region(&mut self, event: CanvasPointer) -> bool {
+        let regions = &mut self.regions;
+        let drawing = regions.draft.is_some();
+        if let Some(shape) = handle_pointer(&mut regions.draft, regions.tool, event) {
+            self.add_roi("ROI", shape);
+            self.roi_stats_dirty = true;
+            self.plots_dirty = true;
+        }
+        if !drawing && self.regions.draft.is_none() {
+            // Not drawing a region, nothing changes.
+            return false;
+        }
+        self.redraw_rois = true;
+        // Redraw the outlines right away. The page only needs to be rendered
+        // again when a region was added, for the table.
+        self.update_canvas_contents();
+        self.roi_stats_dirty
+    }
+
+    /// Add a region labelled `prefix` and a number not used by another
+    /// region.
+    fn add_roi(&mut self, prefix: &str, shape: Shape) {
+        let regions = &mut self.regions;
+        let label = loop {
+            let label = format!("{prefix} {}", regions.next_roi);
+            regions.next_roi += 1;
+            if !regions.rois.iter().any(|roi| roi.label == label) {
+                break label;
+            }
+        };
+        regions.rois.push(Roi::new(label, shape));
+    }
+
+    /// Update everything computed from the regions after they changed.
+    pub(super) fn regions_changed(&mut self) {
+        self.redraw_rois = true;
+        self.roi_stats_dirty = true;
+        self.plots_dirty = true;
+    }
+
+    fn detect_tubes(&mut self) {
+        let image_data = match &self.orig_data {
+            Some(image_data) => image_data,
+            None => return,
+        };
+        let image =
+            RgbaImage::from_raw(image_data.width(), image_data.height(), image_data.data().0)
+                .unwrap();
+        let found = detect_tubes(&image, &DetectionParams::default());
+        if found.is_empty() {
+            // Keep the regions, e.g. drawn by hand.
+            self.error_log
+                .push("No tubes with HNB colors were found in the image.".into());
+            return;
+        }
+        // Tubes already marked, e.g. by an earlier detection, are not added
+        // again.
+        let new: Vec<Roi> = found
+            .into_iter()
+            .filter(|tube| {
+                let (x, y) = tube.shape.center();
+                !self.regions.rois.iter().any(|roi| roi.shape.contains(x, y))
+            })
+            .collect();
+        if new.is_empty() {
+            self.error_log
+                .push("All tubes found are already marked as regions.".into());
+            return;
+        }
+        for tube in new {
+            // Keep the numbers of the detection, in reading order as in the
+            // CLI, unless another region has the label.
+            if self.regions.rois.iter().any(|roi| roi.label == tube.label) {
+                self.add_roi("Tube", tube.shape);
+            } else {
+                self.regions.rois.push(tube);
+            }
+        }
+        self.regions.draft = None;
+        self.regions_changed();
+    }
+
+    /// Compute the statistics of the regions in the original and in the
+    /// transformed images.
+    ///
+    /// As the transforms act on each pixel alone, only the pixels of the
+    /// regions are transformed here, always directly, so that the statistics
+    /// do not depend on the approximate grids chosen for the views.
+    pub(super) fn update_roi_stats(&mut self) {
+        self.roi_stats_dirty = false;
+        self.regions.rows.clear();
+        self.chromaticity.clear();
+        let image_data = match (&self.orig_data, self.regions.rois.is_empty()) {
+            (Some(image_data), false) => image_data,
+            _ => {
+                self.update_calls();
+                return;
+            }
+        };
+        let (width, height) = (image_data.width(), image_data.height());
+        let data = image_data.data();
+        for roi in self.regions.rois.iter() {
+            let pixels = roi.shape.extract_pixels(&data, width, height);
+            self.chromaticity.add_region(&pixels);
+            self.regions.rows.push(RoiRow {
+                roi: roi.label.clone(),
+                image: ORIGINAL.into(),
+                stats: ColorStats::of_pixels(&pixels),
+            });
+            for view in self.views.iter() {
+                let mut transformed = pixels.clone();
+                view.transform.apply(&mut transformed);
+                self.regions.rows.push(RoiRow {
+                    roi: roi.label.clone(),
+                    image: view.transform.name().to_string(),
+                    stats: ColorStats::of_pixels(&transformed),
+                });
+            }
+        }
+        self.update_calls();
+    }
+
+    pub(super) fn view_roi_tools(&self, ctx: &Context<Self>) -> Html {
+        let regions = &self.regions;
+        html! {
+            <div class="roi-tools">
+                <label>
+                    {"Region of interest: "}
+                    { view_select(&RoiTool::ALL, &regions.tool, |t| t.name().to_string(),
+                        ctx.link().callback(RegionsMsg::SetTool)) }
+                </label>
+                {" "}{regions.tool.hint()}{" "}
+                if self.orig_data.is_some() {
+                    <button
+                        class="btn"
+                        title="Add the tubes not marked yet as regions."
+                        onclick={ctx.link().callback(|_| RegionsMsg::DetectTubes)}
+                    >
+                        {"Detect tubes"}
+                    </button>
+                }
+                if !regions.rois.is_empty() {
+                    <button class="btn" onclick={ctx.link().callback(|_| RegionsMsg::Clear)}>
+                        {"Remove all regions"}
+                    </button>
+                    { self.view_plot_regions(ctx) }
+                }
+            </div>
+        }
+    }
+
+    pub(super) fn view_roi_table(&self, ctx: &Context<Self>) -> Html {
+        view_roi_table(
+            &self.regions.rois,
+            &self.regions.rows,
+            &self.classifier.calls,
+            ctx.link().callback(RegionsMsg::Remove),
+            ctx.link()
+                .callback(|(i, control)| RegionsMsg::SetControl(i, control)),
+        )
+    }
+}
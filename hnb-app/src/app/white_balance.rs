@@ -0,0 +1,224 @@
+//! Adapting the image to daylight, from a neutral area or an estimate of the
+//! light.
+
+use yew::{html, Context, Html};
+
+use hnb_core::{
+    color_transform::ColorTransform,
+    colorspace::{correlated_color_temperature, linear_to_srgb8, linear_to_xyz, xyz_to_xy},
+    image::RgbaImage,
+    roi::Shape,
+    white_balance::{estimate_illuminant, Adaptation, IlluminantEstimator, WhiteBalance},
+};
+
+use super::{App, Msg};
+use crate::select::view_select;
+
+/// Half the side of the square measured around a click on a neutral area,
+/// in image pixels.
+const WHITE_PICK_RADIUS: f32 = 4.0;
+
+#[derive(Default)]
+pub struct WhiteBalancing {
+    adaptation: Adaptation,
+    pub(super) balance: Option<WhiteBalance>,
+    /// How the white of `balance` was found.
+    source: String,
+    /// The colors of the light estimated from the whole image.
+    pub(super) estimates: Vec<(IlluminantEstimator, [f32; 3])>,
+    /// Whether the next click on the original picks the white.
+    pub(super) picking: bool,
+}
+
+pub enum WhiteBalanceMsg {
+    /// Start or cancel picking a neutral area.
+    Pick(bool),
+    SetAdaptation(Adaptation),
+    /// Estimate the light with each of the estimators.
+    Estimate,
+    /// White balance with the light found by the estimator.
+    UseEstimate(IlluminantEstimator, [f32; 3]),
+    Remove,
+}
+
+impl From<WhiteBalanceMsg> for Msg {
+    fn from(msg: WhiteBalanceMsg) -> Self {
+        Msg::WhiteBalance(msg)
+    }
+}
+
+impl App {
+    pub(super) fn handle_white_balance(&mut self, msg: WhiteBalanceMsg) -> bool {
+        let white_balance = &mut self.white_balance;
+        match msg {
+            WhiteBalanceMsg::Pick(picking) => white_balance.picking = picking,
+            WhiteBalanceMsg::SetAdaptation(adaptation) => {
+                white_balance.adaptation = adaptation;
+                if let Some(wb) = white_balance.balance.take() {
+                    white_balance.balance = WhiteBalance::new(wb.illuminant, adaptation);
+                    self.correction_changed();
+                }
+            }
+            WhiteBalanceMsg::Estimate => {
+                let image = match self.corrected_raw_image() {
+                    Some(image) => image,
+                    None => return false,
+                };
+                let estimates: Vec<_> = IlluminantEstimator::ALL
+                    .iter()
+                    .copied()
+                    .filter_map(|e| Some((e, estimate_illuminant(&image, e)?)))
+                    .collect();
+                if estimates.is_empty() {
+                    self.error_log
+                        .push("The light cannot be estimated from this image.".into());
+                }
+                self.white_balance.estimates = estimates;
+            }
+            WhiteBalanceMsg::UseEstimate(estimator, light) => {
+                white_balance.balance = WhiteBalance::new(light, white_balance.adaptation);
+                white_balance.source = estimator.name().to_string();
+                self.correction_changed();
+            }
+            WhiteBalanceMsg::Remove => {
+                white_balance.balance = None;
+                self.correction_changed();
+            }
+        }
+        true
+    }
+
+    /// The original image after the color correction, for estimating the
+    /// light.
+    fn corrected_raw_image(&self) -> Option<RgbaImage> {
+        let mut image = self.raw_image()?;
+        if let Some(correction) = &self.correction.correction {
+            correction.ccm.apply(image.as_raw_mut());
+        }
+        Some(image)
+    }
+
+    /// Set the white balance from the pixels around a click on the original,
+    /// after the color correction.
+    pub(super) fn pick_white(&mut self, x: f32, y: f32) {
+        self.white_balance.picking = false;
+        let image_data = match &self.raw_data {
+            Some(image_data) => image_data,
+            None => return,
+        };
+        let r = WHITE_PICK_RADIUS;
+        let square = Shape::Rect {
+            x0: x - r,
+            y0: y - r,
+            x1: x + r,
+            y1: y + r,
+        };
+        let mut pixels =
+            square.extract_pixels(&image_data.data(), image_data.width(), image_data.height());
+        if let Some(correction) = &self.correction.correction {
+            correction.ccm.apply(&mut pixels);
+        }
+        match WhiteBalance::from_pixels(&pixels, self.white_balance.adaptation) {
+            Some(wb) => {
+                self.white_balance.balance = Some(wb);
+                self.white_balance.source = format!("picked at {x:.0}, {y:.0}");
+                self.correction_changed();
+            }
+            None => self
+                .error_log
+                .push("The clicked area is too dark or colored for a white balance.".into()),
+        }
+    }
+
+    pub(super) fn view_white_balance(&self, ctx: &Context<Self>) -> Html {
+        let white_balance = &self.white_balance;
+        let picking = white_balance.picking;
+        let report = match &white_balance.balance {
+            Some(wb) => {
+                let (x, y) = wb.chromaticity();
+                html! {
+                    <p>
+                        {format!("Light ({}): x {x:.4}, y {y:.4}, about {:.0} K, adapted to D65 \
+                            with {}. ", white_balance.source, correlated_color_temperature((x, y)),
+                            wb.adaptation.name())}
+                        <button class="btn"
+                            onclick={ctx.link().callback(|_| WhiteBalanceMsg::Remove)}>
+                            {"Remove white balance"}
+                        </button>
+                    </p>
+                }
+            }
+            None => html! {},
+        };
+        html! {
+            <div class="white-balance">
+                <h3>{"White balance"}</h3>
+                <p>{"Click a white or gray area of the original image, e.g. the tube rack or a \
+                paper card. Its color is taken as the color of the light, and the image is \
+                adapted to daylight (D65) after the color correction. Without a neutral area, \
+                the light can be estimated from the whole image, assuming that its colors \
+                (grey world, shades of grey), its brightest colors (white patch) or its edges \
+                (grey edge) are gray on average."}</p>
+                <p>
+                    <label>
+                        {"Adaptation: "}
+                        { view_select(&Adaptation::ALL, &white_balance.adaptation,
+                            |a| a.name().to_string(),
+                            ctx.link().callback(WhiteBalanceMsg::SetAdaptation)) }
+                    </label>
+                    {" "}
+                    if self.raw_data.is_some() {
+                        <button class={if picking { "btn selected" } else { "btn" }}
+                            onclick={ctx.link().callback(move |_| WhiteBalanceMsg::Pick(!picking))}>
+                            {if picking { "Cancel picking" } else { "Pick white" }}
+                        </button>
+                        {" "}
+                        <button class="btn"
+                            onclick={ctx.link().callback(|_| WhiteBalanceMsg::Estimate)}>
+                            {"Estimate the light"}
+                        </button>
+                    }
+                </p>
+                { view_estimates(ctx, &white_balance.estimates) }
+                {report}
+            </div>
+        }
+    }
+}
+
+/// The lights estimated from the whole image, each of which can be applied.
+fn view_estimates(ctx: &Context<App>, estimates: &[(IlluminantEstimator, [f32; 3])]) -> Html {
+    if estimates.is_empty() {
+        return html! {};
+    }
+    html! {
+        <table class="illuminants">
+            <tr>
+                <th>{"Estimator"}</th><th>{"Light"}</th><th>{"x"}</th><th>{"y"}</th>
+                <th>{"CCT"}</th><th></th>
+            </tr>
+            { for estimates.iter().map(|&(estimator, light)| {
+                let xy = xyz_to_xy(linear_to_xyz(light));
+                let [r, g, b] = linear_to_srgb8(light);
+                html! {
+                    <tr>
+                        <td>{estimator.name()}</td>
+                        <td><span class="swatch"
+                            style={format!("background: rgb({r}, {g}, {b})")}/></td>
+                        <td>{format!("{:.4}", xy.0)}</td>
+                        <td>{format!("{:.4}", xy.1)}</td>
+                        <td>{format!("{:.0} K", correlated_color_temperature(xy))}</td>
+                        <td>
+                            <button class="btn"
+                                onclick={ctx.link().callback(move |_| {
+                                    WhiteBalanceMsg::UseEstimate(estimator, light)
+                                })}>
+                                {"Apply"}
+                            </button>
+                        </td>
+                    </tr>
+                }
+            })}
+        </table>
+    }
+}
@@ -0,0 +1,81 @@
+//! Web Worker which applies the color transforms, so that the user interface
+//! stays responsive while large images are processed.
+//!
+//! Receives [Job]s from the app and answers with [Reply]s.
+
+use js_sys::Uint8Array;
+use std::cell::RefCell;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
+
+use hnb_app::worker_protocol::{Job, Reply};
+use hnb_core::{color_transform::Registry, lut::LutCache};
+
+/// Approximate number of pixels processed between progress messages.
+const PIXELS_PER_PROGRESS: usize = 1 << 18;
+
+fn process(job: Job, registry: &Registry, lut_cache: &mut LutCache) -> Reply {
+    let (job_id, view_id) = (job.job_id, job.view_id);
+    let transform = match job.transform.create(registry) {
+        Some(transform) => transform,
+        None => {
+            return Reply::Failed {
+                job_id,
+                view_id,
+                message: "unknown transform".into(),
+            }
+        }
+    };
+    let mut pixels = Uint8Array::new(&job.pixels).to_vec();
+    if pixels.len() != 4 * job.width as usize * job.height as usize {
+        return Reply::Failed {
+            job_id,
+            view_id,
+            message: "pixel buffer has wrong size".into(),
+        };
+    }
+
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let row_bytes = 4 * job.width.max(1) as usize;
+    let chunk_rows = (PIXELS_PER_PROGRESS / job.width.max(1) as usize).max(1);
+    let n_chunks = (pixels.len() / row_bytes).div_ceil(chunk_rows).max(1);
+    for (i, chunk) in pixels.chunks_mut(chunk_rows * row_bytes).enumerate() {
+        lut_cache.apply(transform.as_ref(), job.processing, chunk);
+        let progress = Reply::Progress {
+            job_id,
+            view_id,
+            fraction: (i + 1) as f32 / n_chunks as f32,
+        };
+        progress.post(&scope).unwrap();
+    }
+
+    Reply::Done {
+        job_id,
+        view_id,
+        width: job.width,
+        height: job.height,
+        pixels: Uint8Array::from(pixels.as_slice()).buffer(),
+    }
+}
+
+fn main() {
+    console_error_panic_hook::set_once();
+    wasm_logger::init(wasm_logger::Config::new(log::Level::Info));
+
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let registry = Registry::default();
+    let lut_cache = RefCell::new(LutCache::default());
+
+    let reply_scope = scope.clone();
+    let onmessage = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        match Job::from_js(&event.data()) {
+            Some(job) => {
+                let reply = process(job, &registry, &mut lut_cache.borrow_mut());
+                reply.post(&reply_scope).unwrap();
+            }
+            None => log::error!("hnb-worker: invalid message {:?}", event.data()),
+        }
+    });
+    scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+}
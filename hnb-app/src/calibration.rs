@@ -0,0 +1,83 @@
+//! Color correction of the original image with a reference chart, and
+//! drawing the patches found on the image.
+
+use std::convert::TryInto;
+
+use web_sys::CanvasRenderingContext2d;
+
+use hnb_core::{
+    calibration::{delta_e, CcmModel, ChartDetection, ChartLayout, ColorCorrection},
+    roi::{Roi, Shape},
+};
+
+const PATCH_COLOR: &str = "#00e5ff";
+
+/// A color correction fitted to a chart in the image.
+pub struct Correction {
+    /// File name of the image with the chart.
+    pub image: String,
+    pub layout: ChartLayout,
+    pub chart: ChartDetection,
+    pub ccm: ColorCorrection,
+    /// Mean and maximum ΔE*ab of the patches before and after correction.
+    pub before: (f32, f32),
+    pub after: (f32, f32),
+}
+
+impl Correction {
+    pub fn fit(
+        image: &str,
+        layout: &ChartLayout,
+        chart: ChartDetection,
+        model: CcmModel,
+    ) -> Option<Self> {
+        let ccm = ColorCorrection::fit(&chart.measured, &layout.reference, model)?;
+        let corrected: Vec<[f32; 3]> = chart.measured.iter().map(|&m| ccm.correct(m)).collect();
+        Some(Self {
+            image: image.to_string(),
+            layout: layout.clone(),
+            before: delta_e(&chart.measured, &layout.reference),
+            after: delta_e(&corrected, &layout.reference),
+            chart,
+            ccm,
+        })
+    }
+}
+
+/// The index of the last region which is a polygon with four corners, taken
+/// as the centers of the top left, top right, bottom right and bottom left
+/// patches of a chart.
+pub fn chart_polygon(rois: &[Roi]) -> Option<(usize, [(f32, f32); 4])> {
+    rois.iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, roi)| match &roi.shape {
+            Shape::Polygon(vertices) => Some((i, vertices.as_slice().try_into().ok()?)),
+            _ => None,
+        })
+}
+
+/// Outline the measured squares of the patches, and connect the corner
+/// patches, starting at the first, to show the orientation of the chart.
+pub fn draw_chart(ctx: &CanvasRenderingContext2d, chart: &ChartDetection, line_width: f64) {
+    ctx.save();
+    ctx.set_line_width(line_width);
+    ctx.set_stroke_style_str(PATCH_COLOR);
+    ctx.set_fill_style_str(PATCH_COLOR);
+    let half = chart.patch_size as f64 / 2.0;
+    for &(x, y) in chart.centers.iter() {
+        ctx.stroke_rect(x as f64 - half, y as f64 - half, 2.0 * half, 2.0 * half);
+    }
+    ctx.begin_path();
+    let (x, y) = chart.corners[0];
+    ctx.arc(x as f64, y as f64, half / 2.0, 0.0, std::f64::consts::TAU)
+        .unwrap();
+    ctx.fill();
+    ctx.begin_path();
+    for &(x, y) in chart.corners.iter() {
+        ctx.line_to(x as f64, y as f64);
+    }
+    ctx.close_path();
+    ctx.stroke();
+    ctx.restore();
+}
@@ -0,0 +1,361 @@
+//! A chromaticity diagram of the regions: the spectral locus, the sRGB
+//! gamut, the pixels and the mean color of each region, and the separation
+//! of the controls.
+
+use wasm_bindgen::{Clamped, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+use yew::{html, Component, Context, Html, NodeRef, Properties};
+
+use hnb_core::chromaticity::{
+    display_color, is_visible, srgb_gamut, white_point, ControlSeparation, Diagram,
+    RegionChromaticity, SPECTRAL_LOCUS,
+};
+
+use crate::download::download_url;
+
+/// Size of the canvas, in canvas pixels.
+const WIDTH: f64 = 470.0;
+const HEIGHT: f64 = 460.0;
+/// The square showing the diagram.
+const PLOT_LEFT: f64 = 56.0;
+const PLOT_TOP: f64 = 12.0;
+const PLOT_SIZE: f64 = 400.0;
+
+const FONT: &str = "12px sans-serif";
+/// The wavelengths, in nm, written along the spectral locus.
+const WAVELENGTH_LABELS: [u16; 9] = [460, 480, 500, 520, 540, 560, 580, 600, 620];
+/// Steps between the ticks, the largest giving at least 4 ticks is used.
+const TICK_STEPS: [f32; 7] = [0.1, 0.05, 0.02, 0.01, 0.005, 0.002, 0.001];
+/// The margin around the regions when zoomed, as a fraction of their extent.
+const ZOOM_MARGIN: f32 = 0.25;
+/// The smallest span shown when zoomed.
+const MIN_ZOOM_SPAN: f32 = 0.01;
+
+/// A region and the colors it is drawn with.
+#[derive(Clone, PartialEq)]
+pub struct PlottedRegion {
+    pub label: String,
+    pub color: &'static str,
+    pub chromaticity: RegionChromaticity,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub diagram: Diagram,
+    pub regions: Vec<PlottedRegion>,
+    pub controls: Option<ControlSeparation>,
+    /// Whether to show only the neighborhood of the regions.
+    pub zoom: bool,
+}
+
+pub enum Msg {
+    Download,
+}
+
+pub struct ChromaticityPlot {
+    node_ref: NodeRef,
+}
+
+impl Component for ChromaticityPlot {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Download => {
+                if let Some(canvas) = self.node_ref.cast::<HtmlCanvasElement>() {
+                    let file_name = match ctx.props().diagram {
+                        Diagram::Xy => "chromaticity-xy.png",
+                        Diagram::UvPrime => "chromaticity-uv.png",
+                    };
+                    download_url(file_name, &canvas.to_data_url().unwrap());
+                }
+            }
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="chromaticity-plot">
+                <canvas ref={self.node_ref.clone()}
+                    width={WIDTH.to_string()} height={HEIGHT.to_string()}/>
+                <button class="btn" onclick={ctx.link().callback(|_| Msg::Download)}>
+                    {"Download figure (PNG)"}
+                </button>
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        let canvas = match self.node_ref.cast::<HtmlCanvasElement>() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let context = CanvasRenderingContext2d::from(JsValue::from(
+            canvas.get_context("2d").unwrap().unwrap(),
+        ));
+        draw(&context, ctx.props());
+    }
+}
+
+/// The part of a diagram shown: the coordinates of its bottom left corner
+/// and its side.
+#[derive(Clone, Copy)]
+struct Window {
+    u0: f32,
+    v0: f32,
+    span: f32,
+}
+
+impl Window {
+    fn of(props: &Props) -> Self {
+        let diagram = props.diagram;
+        let (width, height) = diagram.extent();
+        let whole = Self {
+            u0: 0.0,
+            v0: 0.0,
+            span: width.max(height),
+        };
+        if !props.zoom {
+            return whole;
+        }
+        let controls = props.controls.iter().flat_map(|c| [c.positive, c.negative]);
+        let points = props
+            .regions
+            .iter()
+            .flat_map(|r| r.chromaticity.points.iter().chain([&r.chromaticity.mean]))
+            .copied()
+            .chain(controls)
+            .map(|xy| diagram.from_xy(xy));
+        let (mut min, mut max) = ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN));
+        for (u, v) in points {
+            min = (min.0.min(u), min.1.min(v));
+            max = (max.0.max(u), max.1.max(v));
+        }
+        if min.0 > max.0 {
+            return whole;
+        }
+        let span = ((max.0 - min.0).max(max.1 - min.1) * (1.0 + 2.0 * ZOOM_MARGIN))
+            .max(MIN_ZOOM_SPAN)
+            .min(whole.span);
+        Self {
+            u0: (min.0 + max.0 - span) / 2.0,
+            v0: (min.1 + max.1 - span) / 2.0,
+            span,
+        }
+    }
+
+    /// The canvas position of diagram coordinates.
+    fn canvas_position(&self, (u, v): (f32, f32)) -> (f64, f64) {
+        let scale = PLOT_SIZE / self.span as f64;
+        (
+            PLOT_LEFT + (u - self.u0) as f64 * scale,
+            PLOT_TOP + PLOT_SIZE - (v - self.v0) as f64 * scale,
+        )
+    }
+
+    /// The diagram coordinates of a canvas position.
+    fn coordinates_at(&self, x: f64, y: f64) -> (f32, f32) {
+        let scale = self.span as f64 / PLOT_SIZE;
+        (
+            self.u0 + ((x - PLOT_LEFT) * scale) as f32,
+            self.v0 + ((PLOT_TOP + PLOT_SIZE - y) * scale) as f32,
+        )
+    }
+}
+
+fn draw(ctx: &CanvasRenderingContext2d, props: &Props) {
+    let diagram = props.diagram;
+    let window = Window::of(props);
+    fill_background(ctx, diagram, &window);
+    let at = |xy: (f32, f32)| window.canvas_position(diagram.from_xy(xy));
+
+    ctx.save();
+    ctx.begin_path();
+    ctx.rect(PLOT_LEFT, PLOT_TOP, PLOT_SIZE, PLOT_SIZE);
+    ctx.clip();
+    ctx.set_font(FONT);
+    ctx.set_line_width(1.0);
+
+    // The spectral locus, closed by the line of purples.
+    ctx.set_stroke_style_str("#000");
+    ctx.begin_path();
+    for &(_, x, y) in SPECTRAL_LOCUS.iter() {
+        let (cx, cy) = at((x, y));
+        ctx.line_to(cx, cy);
+    }
+    ctx.close_path();
+    ctx.stroke();
+    ctx.set_fill_style_str("#000");
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("middle");
+    let (wx, wy) = at(white_point());
+    for &(nm, x, y) in SPECTRAL_LOCUS.iter() {
+        if !WAVELENGTH_LABELS.contains(&nm) {
+            continue;
+        }
+        // Outside the locus, away from the white point.
+        let (cx, cy) = at((x, y));
+        let length = (cx - wx).hypot(cy - wy).max(1.0);
+        let (dx, dy) = ((cx - wx) / length, (cy - wy) / length);
+        ctx.fill_text(&nm.to_string(), cx + 16.0 * dx, cy + 16.0 * dy)
+            .unwrap();
+    }
+
+    // The sRGB gamut and its white.
+    ctx.set_stroke_style_str("#444");
+    ctx.set_line_dash(&js_array(&[4.0, 3.0])).unwrap();
+    ctx.begin_path();
+    for xy in srgb_gamut() {
+        let (cx, cy) = at(xy);
+        ctx.line_to(cx, cy);
+    }
+    ctx.close_path();
+    ctx.stroke();
+    ctx.set_line_dash(&js_array(&[])).unwrap();
+    let (gx, gy) = at(srgb_gamut()[1]);
+    ctx.set_text_align("left");
+    ctx.fill_text("sRGB", gx + 6.0, gy).unwrap();
+    ctx.begin_path();
+    ctx.arc(wx, wy, 3.0, 0.0, std::f64::consts::TAU).unwrap();
+    ctx.stroke();
+    ctx.fill_text("D65", wx + 6.0, wy).unwrap();
+
+    // The pixels of the regions, then the means on top.
+    ctx.set_global_alpha(0.4);
+    for region in props.regions.iter() {
+        ctx.set_fill_style_str(region.color);
+        for &xy in region.chromaticity.points.iter() {
+            let (cx, cy) = at(xy);
+            ctx.fill_rect(cx - 1.0, cy - 1.0, 2.0, 2.0);
+        }
+    }
+    ctx.set_global_alpha(1.0);
+    if let Some(controls) = &props.controls {
+        let ((px, py), (nx, ny)) = (at(controls.positive), at(controls.negative));
+        ctx.set_stroke_style_str("#000");
+        ctx.set_line_width(1.5);
+        ctx.begin_path();
+        ctx.move_to(px, py);
+        ctx.line_to(nx, ny);
+        ctx.stroke();
+        for (x, y) in [(px, py), (nx, ny)] {
+            ctx.stroke_rect(x - 5.0, y - 5.0, 10.0, 10.0);
+        }
+    }
+    for region in props.regions.iter() {
+        let (cx, cy) = at(region.chromaticity.mean);
+        ctx.begin_path();
+        ctx.arc(cx, cy, 4.0, 0.0, std::f64::consts::TAU).unwrap();
+        ctx.set_fill_style_str(region.color);
+        ctx.fill();
+        ctx.set_stroke_style_str("#fff");
+        ctx.set_line_width(1.0);
+        ctx.stroke();
+        ctx.set_fill_style_str("#000");
+        ctx.fill_text(&region.label, cx + 7.0, cy).unwrap();
+    }
+    ctx.restore();
+
+    draw_axes(ctx, diagram, &window);
+    if let Some(controls) = &props.controls {
+        let [u, v] = diagram.axes();
+        let text = format!(
+            "Controls: Δ{u}{v} {:.4}, ΔE*ab {:.1}",
+            controls.distance(diagram),
+            controls.delta_e
+        );
+        ctx.set_text_align("right");
+        ctx.set_text_baseline("top");
+        ctx.set_fill_style_str("#000");
+        ctx.fill_text(&text, PLOT_LEFT + PLOT_SIZE - 6.0, PLOT_TOP + 6.0)
+            .unwrap();
+    }
+}
+
+/// White outside the diagram, and the colors of the chromaticities inside
+/// the spectral locus, lightened to keep the points readable.
+fn fill_background(ctx: &CanvasRenderingContext2d, diagram: Diagram, window: &Window) {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+    let mut pixels = vec![255; width * height * 4];
+    for y in PLOT_TOP as usize..(PLOT_TOP + PLOT_SIZE) as usize {
+        for x in PLOT_LEFT as usize..(PLOT_LEFT + PLOT_SIZE) as usize {
+            let xy = diagram.to_xy(window.coordinates_at(x as f64 + 0.5, y as f64 + 0.5));
+            if !is_visible(xy) {
+                continue;
+            }
+            let i = 4 * (y * width + x);
+            for (pix, v) in pixels[i..i + 3].iter_mut().zip(display_color(xy)) {
+                *pix = ((v as u16 + 255) / 2) as u8;
+            }
+        }
+    }
+    let image_data =
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(&pixels), width as u32, height as u32)
+            .unwrap();
+    ctx.put_image_data(&image_data, 0.0, 0.0).unwrap();
+}
+
+fn draw_axes(ctx: &CanvasRenderingContext2d, diagram: Diagram, window: &Window) {
+    ctx.set_stroke_style_str("#000");
+    ctx.set_line_width(1.0);
+    ctx.stroke_rect(PLOT_LEFT, PLOT_TOP, PLOT_SIZE, PLOT_SIZE);
+    ctx.set_font(FONT);
+    ctx.set_fill_style_str("#000");
+    let step = TICK_STEPS
+        .iter()
+        .copied()
+        .find(|&s| window.span / s >= 4.0)
+        .unwrap_or(TICK_STEPS[TICK_STEPS.len() - 1]);
+    let decimals = (-step.log10()).ceil().max(1.0) as usize;
+    let ticks = |start: f32| {
+        let first = (start / step).ceil() as i32;
+        let last = ((start + window.span) / step).floor() as i32;
+        (first..=last).map(|i| i as f32 * step)
+    };
+    let bottom = PLOT_TOP + PLOT_SIZE;
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("top");
+    for u in ticks(window.u0) {
+        let (x, _) = window.canvas_position((u, window.v0));
+        ctx.begin_path();
+        ctx.move_to(x, bottom);
+        ctx.line_to(x, bottom + 4.0);
+        ctx.stroke();
+        ctx.fill_text(&format!("{u:.decimals$}"), x, bottom + 6.0)
+            .unwrap();
+    }
+    ctx.set_text_align("right");
+    ctx.set_text_baseline("middle");
+    for v in ticks(window.v0) {
+        let (_, y) = window.canvas_position((window.u0, v));
+        ctx.begin_path();
+        ctx.move_to(PLOT_LEFT - 4.0, y);
+        ctx.line_to(PLOT_LEFT, y);
+        ctx.stroke();
+        ctx.fill_text(&format!("{v:.decimals$}"), PLOT_LEFT - 6.0, y)
+            .unwrap();
+    }
+    let [u, v] = diagram.axes();
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("bottom");
+    ctx.fill_text(
+        &format!("{u} ({})", diagram.name()),
+        PLOT_LEFT + PLOT_SIZE / 2.0,
+        HEIGHT - 4.0,
+    )
+    .unwrap();
+    ctx.set_text_baseline("middle");
+    ctx.fill_text(v, 10.0, PLOT_TOP + PLOT_SIZE / 2.0).unwrap();
+}
+
+fn js_array(values: &[f64]) -> js_sys::Array {
+    values.iter().map(|&v| JsValue::from_f64(v)).collect()
+}
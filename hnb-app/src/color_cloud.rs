@@ -0,0 +1,202 @@
+//! Rotatable 3D clouds of the colors of images, side by side and seen by the
+//! same camera: drag to orbit, scroll to zoom and double click to reset.
+
+use std::rc::Rc;
+
+use wasm_bindgen::{closure::Closure, Clamped, JsCast, JsValue};
+use web_sys::{
+    AddEventListenerOptions, CanvasRenderingContext2d, HtmlCanvasElement, ImageData, PointerEvent,
+    WheelEvent,
+};
+use yew::{html, Component, Context, Html, NodeRef, Properties};
+
+use hnb_core::point_cloud::{Camera, PointCloud};
+
+/// Width and height of each canvas, in canvas pixels.
+const SIZE: usize = 340;
+/// Side of the square drawn for each point, in canvas pixels.
+const POINT_SIZE: usize = 2;
+/// The most clouds shown.
+pub const MAX_CLOUDS: usize = 2;
+
+const BACKGROUND: [u8; 3] = [24, 24, 24];
+const FRAME_COLOR: &str = "#777";
+const TEXT_COLOR: &str = "#ddd";
+const FONT: &str = "12px sans-serif";
+
+/// Degrees the camera turns per pixel dragged.
+const ORBIT_SPEED: f32 = 0.5;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 8.0;
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    /// The titles and clouds, at most [MAX_CLOUDS].
+    pub clouds: Rc<Vec<(String, PointCloud)>>,
+}
+
+pub enum Msg {
+    Down(i32, i32),
+    Move(i32, i32),
+    Up,
+    /// The vertical scroll of the wheel.
+    Zoom(f64),
+    Reset,
+}
+
+pub struct ColorCloud {
+    container: NodeRef,
+    canvases: [NodeRef; MAX_CLOUDS],
+    camera: Camera,
+    /// The last pointer position while dragging.
+    drag: Option<(i32, i32)>,
+    /// Listens to the wheel with a non-passive listener, to keep the page
+    /// from scrolling while zooming.
+    _wheel: Option<Closure<dyn FnMut(WheelEvent)>>,
+}
+
+impl Component for ColorCloud {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            container: NodeRef::default(),
+            canvases: Default::default(),
+            camera: Camera::default(),
+            drag: None,
+            _wheel: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Down(x, y) => {
+                self.drag = Some((x, y));
+                false
+            }
+            Msg::Move(x, y) => match self.drag {
+                Some((x0, y0)) => {
+                    self.camera.orbit(
+                        -ORBIT_SPEED * (x - x0) as f32,
+                        ORBIT_SPEED * (y - y0) as f32,
+                    );
+                    self.drag = Some((x, y));
+                    true
+                }
+                None => false,
+            },
+            Msg::Up => {
+                self.drag = None;
+                false
+            }
+            Msg::Zoom(delta) => {
+                let zoom = self.camera.zoom * (-0.001 * delta as f32).exp();
+                self.camera.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+                true
+            }
+            Msg::Reset => {
+                self.camera = Camera::default();
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        html! {
+            <div class="color-cloud" ref={self.container.clone()}
+                onpointerdown={link.callback(|e: PointerEvent| Msg::Down(e.client_x(), e.client_y()))}
+                onpointermove={link.callback(|e: PointerEvent| Msg::Move(e.client_x(), e.client_y()))}
+                onpointerup={link.callback(|_| Msg::Up)}
+                onpointerleave={link.callback(|_| Msg::Up)}
+                ondblclick={link.callback(|_| Msg::Reset)}
+            >
+                { for self.canvases.iter().take(ctx.props().clouds.len()).map(|node_ref| html! {
+                    <canvas ref={node_ref.clone()}
+                        width={SIZE.to_string()} height={SIZE.to_string()}/>
+                })}
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if first_render {
+            self.listen_to_wheel(ctx);
+        }
+        for ((title, cloud), node_ref) in ctx.props().clouds.iter().zip(self.canvases.iter()) {
+            let canvas = match node_ref.cast::<HtmlCanvasElement>() {
+                Some(canvas) => canvas,
+                None => continue,
+            };
+            let context = CanvasRenderingContext2d::from(JsValue::from(
+                canvas.get_context("2d").unwrap().unwrap(),
+            ));
+            draw(&context, &self.camera, title, cloud);
+        }
+    }
+}
+
+impl ColorCloud {
+    fn listen_to_wheel(&mut self, ctx: &Context<Self>) {
+        let container = match self.container.get() {
+            Some(container) => container,
+            None => return,
+        };
+        let link = ctx.link().clone();
+        let wheel = Closure::<dyn FnMut(WheelEvent)>::new(move |e: WheelEvent| {
+            e.prevent_default();
+            link.send_message(Msg::Zoom(e.delta_y()));
+        });
+        let options = AddEventListenerOptions::new();
+        options.set_passive(false);
+        container
+            .add_event_listener_with_callback_and_add_event_listener_options(
+                "wheel",
+                wheel.as_ref().unchecked_ref(),
+                &options,
+            )
+            .unwrap();
+        self._wheel = Some(wheel);
+    }
+}
+
+/// Draw the points, then the frame of the space and the title over them.
+fn draw(ctx: &CanvasRenderingContext2d, camera: &Camera, title: &str, cloud: &PointCloud) {
+    let pixels = cloud.render(camera, SIZE, SIZE, POINT_SIZE, BACKGROUND);
+    let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(pixels.as_slice()),
+        SIZE as u32,
+        SIZE as u32,
+    )
+    .unwrap();
+    ctx.put_image_data(&image_data, 0.0, 0.0).unwrap();
+
+    let project = |p| {
+        let (x, y, _) = camera.project(p, SIZE, SIZE);
+        (x as f64, y as f64)
+    };
+    ctx.set_stroke_style_str(FRAME_COLOR);
+    ctx.set_line_width(1.0);
+    ctx.begin_path();
+    for [start, end] in cloud.space.frame() {
+        let (x, y) = project(start);
+        ctx.move_to(x, y);
+        let (x, y) = project(end);
+        ctx.line_to(x, y);
+    }
+    ctx.stroke();
+
+    ctx.set_font(FONT);
+    ctx.set_fill_style_str(TEXT_COLOR);
+    ctx.set_text_align("center");
+    ctx.set_text_baseline("middle");
+    for (label, p) in cloud.space.labels() {
+        let (x, y) = project(p);
+        ctx.fill_text(label, x, y).unwrap();
+    }
+    ctx.set_text_align("left");
+    ctx.set_text_baseline("top");
+    ctx.fill_text(&format!("{title} ({})", cloud.space.name()), 6.0, 6.0)
+        .unwrap();
+}
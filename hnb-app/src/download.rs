@@ -0,0 +1,35 @@
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, Url};
+
+/// Let the browser save `content` as a file named `file_name`.
+pub fn download_bytes(file_name: &str, content: &[u8]) {
+    let buffer = Uint8Array::from(content);
+    let buffer_val: &JsValue = buffer.as_ref();
+    let parts = Array::new_with_length(1);
+    parts.set(0, buffer_val.clone());
+    let blob = Blob::new_with_u8_array_sequence(parts.as_ref()).unwrap();
+    let url = Url::create_object_url_with_blob(&blob).unwrap();
+    download_url(file_name, &url);
+    Url::revoke_object_url(&url).unwrap();
+}
+
+/// Let the browser save the content at `url`, e.g. a data URL, as a file
+/// named `file_name`.
+pub fn download_url(file_name: &str, url: &str) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor = document
+        .create_element("a")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .unwrap();
+    anchor.set_href(url);
+    anchor.set_download(file_name);
+    anchor.style().set_property("display", "none").unwrap();
+    let body = document.body().unwrap();
+    body.append_child(&anchor).unwrap();
+
+    anchor.click();
+
+    body.remove_child(&anchor).unwrap();
+}
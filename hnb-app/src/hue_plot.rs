@@ -0,0 +1,155 @@
+//! Plots of the hues and chromas of an image: a histogram of the hues
+//! weighted by saturation, and the density of the colors in a polar plot of
+//! hue and chroma.
+
+use std::f64::consts::TAU;
+
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use yew::{html, Component, Context, Html, NodeRef, Properties};
+
+use hnb_core::hue_chroma::{HueChroma, DENSITY_CHROMA_BINS, DENSITY_HUE_BINS, HUE_BINS};
+
+/// Size of the canvas, in canvas pixels.
+const WIDTH: f64 = 420.0;
+const HEIGHT: f64 = 180.0;
+/// The area of the histogram.
+const HISTOGRAM_LEFT: f64 = 4.0;
+const HISTOGRAM_WIDTH: f64 = 216.0;
+const HISTOGRAM_TOP: f64 = 18.0;
+const HISTOGRAM_HEIGHT: f64 = 136.0;
+/// The center and radius of the polar plot.
+const POLAR_X: f64 = 326.0;
+const POLAR_Y: f64 = 96.0;
+const POLAR_RADIUS: f64 = 78.0;
+
+const FONT: &str = "11px sans-serif";
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub plot: HueChroma,
+}
+
+pub struct HuePlot {
+    node_ref: NodeRef,
+}
+
+impl Component for HuePlot {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+        }
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html! {
+            <canvas class="hue-plot" ref={self.node_ref.clone()}
+                width={WIDTH.to_string()} height={HEIGHT.to_string()}/>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        let canvas = match self.node_ref.cast::<HtmlCanvasElement>() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let context = CanvasRenderingContext2d::from(JsValue::from(
+            canvas.get_context("2d").unwrap().unwrap(),
+        ));
+        context.clear_rect(0.0, 0.0, WIDTH, HEIGHT);
+        context.set_font(FONT);
+        context.set_text_baseline("top");
+        context.set_fill_style_str("#000");
+        let plot = &ctx.props().plot;
+        if plot.n_pixels == 0 {
+            context.fill_text("No pixels", 4.0, 4.0).unwrap();
+            return;
+        }
+        draw_histogram(&context, plot);
+        draw_polar(&context, plot);
+    }
+}
+
+fn draw_histogram(ctx: &CanvasRenderingContext2d, plot: &HueChroma) {
+    ctx.fill_text("Hue, weighted by saturation", HISTOGRAM_LEFT, 2.0)
+        .unwrap();
+    let bottom = HISTOGRAM_TOP + HISTOGRAM_HEIGHT;
+    let max = plot.hue_histogram.iter().cloned().fold(0.0, f32::max);
+    let bar = HISTOGRAM_WIDTH / HUE_BINS as f64;
+    if max > 0.0 {
+        for (i, &w) in plot.hue_histogram.iter().enumerate() {
+            let height = HISTOGRAM_HEIGHT * (w / max) as f64;
+            let hue = (i as f64 + 0.5) * 360.0 / HUE_BINS as f64;
+            ctx.set_fill_style_str(&format!("hsl({hue}, 100%, 45%)"));
+            // A little wider than the bin, to avoid gaps.
+            ctx.fill_rect(
+                HISTOGRAM_LEFT + i as f64 * bar,
+                bottom - height,
+                bar + 0.5,
+                height,
+            );
+        }
+    }
+    ctx.set_stroke_style_str("#999");
+    ctx.set_line_width(1.0);
+    ctx.stroke_rect(
+        HISTOGRAM_LEFT,
+        HISTOGRAM_TOP,
+        HISTOGRAM_WIDTH,
+        HISTOGRAM_HEIGHT,
+    );
+    ctx.set_fill_style_str("#000");
+    for degrees in [0, 90, 180, 270, 360] {
+        let x = HISTOGRAM_LEFT + HISTOGRAM_WIDTH * degrees as f64 / 360.0;
+        ctx.set_text_align(match degrees {
+            0 => "left",
+            360 => "right",
+            _ => "center",
+        });
+        ctx.fill_text(&format!("{degrees}°"), x, bottom + 3.0)
+            .unwrap();
+    }
+    ctx.set_text_align("left");
+}
+
+/// Hues increase counterclockwise from red at the right, as in the hue
+/// wheel, and the chroma from the center. The opacity of a bin grows with
+/// the logarithm of its number of pixels, so small clusters stay visible.
+fn draw_polar(ctx: &CanvasRenderingContext2d, plot: &HueChroma) {
+    ctx.fill_text("Hue and chroma", POLAR_X - POLAR_RADIUS, 2.0)
+        .unwrap();
+    let max = plot.density.iter().cloned().fold(0.0, f32::max);
+    let ring = POLAR_RADIUS / DENSITY_CHROMA_BINS as f64;
+    let sector = TAU / DENSITY_HUE_BINS as f64;
+    for c in 0..DENSITY_CHROMA_BINS {
+        for h in 0..DENSITY_HUE_BINS {
+            let n = plot.density_at(h, c);
+            if n <= 0.0 {
+                continue;
+            }
+            let alpha = ((1.0 + n).ln() / (1.0 + max).ln()) as f64;
+            let hue = (h as f64 + 0.5) * 360.0 / DENSITY_HUE_BINS as f64;
+            // Grays in the center, full colors at the rim.
+            let saturation = 100.0 * (c as f64 + 0.5) / DENSITY_CHROMA_BINS as f64;
+            ctx.set_fill_style_str(&format!("hsla({hue}, {saturation}%, 45%, {alpha})"));
+            let (start, end) = (-(h as f64 + 1.0) * sector, -(h as f64) * sector);
+            ctx.begin_path();
+            ctx.arc(POLAR_X, POLAR_Y, (c + 1) as f64 * ring, start, end)
+                .unwrap();
+            ctx.arc_with_anticlockwise(POLAR_X, POLAR_Y, c as f64 * ring, end, start, true)
+                .unwrap();
+            ctx.close_path();
+            ctx.fill();
+        }
+    }
+    ctx.set_stroke_style_str("#999");
+    ctx.set_line_width(1.0);
+    for r in [POLAR_RADIUS / 2.0, POLAR_RADIUS] {
+        ctx.begin_path();
+        ctx.arc(POLAR_X, POLAR_Y, r, 0.0, TAU).unwrap();
+        ctx.stroke();
+    }
+}
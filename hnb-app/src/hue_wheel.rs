@@ -0,0 +1,127 @@
+//! A diagram of the hue circle with the hue clusters of an image and the
+//! Color Stretch center.
+
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use yew::{html, Component, Context, Html, NodeRef, Properties};
+
+use hnb_core::autotune::HueCluster;
+
+/// Width and height of the diagram, in canvas pixels.
+const SIZE: f64 = 220.0;
+/// Width of the colored ring, in canvas pixels.
+const RING_WIDTH: f64 = 16.0;
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub clusters: Vec<HueCluster>,
+    /// The stretch center: hue as a fraction of a full turn, and radius.
+    pub center_hue: f32,
+    pub radius: f32,
+}
+
+pub struct HueWheel {
+    node_ref: NodeRef,
+}
+
+impl Component for HueWheel {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+        }
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html! {
+            <canvas class="hue-wheel" ref={self.node_ref.clone()}
+                width={SIZE.to_string()} height={SIZE.to_string()}/>
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        let canvas = match self.node_ref.cast::<HtmlCanvasElement>() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let context = CanvasRenderingContext2d::from(JsValue::from(
+            canvas.get_context("2d").unwrap().unwrap(),
+        ));
+        draw(&context, ctx.props());
+    }
+}
+
+/// The point at `radius` (1 at the middle of the ring) and `hue` (fraction of
+/// a full turn), with hues increasing counterclockwise from red at the right.
+fn point(hue: f32, radius: f64) -> (f64, f64) {
+    let ring = SIZE / 2.0 - RING_WIDTH;
+    let angle = hue as f64 * std::f64::consts::TAU;
+    (
+        SIZE / 2.0 + radius * ring * angle.cos(),
+        SIZE / 2.0 - radius * ring * angle.sin(),
+    )
+}
+
+fn draw(ctx: &CanvasRenderingContext2d, props: &Props) {
+    ctx.clear_rect(0.0, 0.0, SIZE, SIZE);
+    let (cx, cy) = (SIZE / 2.0, SIZE / 2.0);
+    let ring = SIZE / 2.0 - RING_WIDTH;
+
+    // The hue ring, in steps of 2 degrees.
+    ctx.set_line_width(RING_WIDTH);
+    for i in 0..180 {
+        let start = i as f64 * 2.0;
+        ctx.begin_path();
+        ctx.arc_with_anticlockwise(
+            cx,
+            cy,
+            ring,
+            -(start + 2.5).to_radians(),
+            -start.to_radians(),
+            false,
+        )
+        .unwrap();
+        ctx.set_stroke_style_str(&format!("hsl({start}, 100%, 50%)"));
+        ctx.stroke();
+    }
+    ctx.set_line_width(1.0);
+    ctx.set_stroke_style_str("#999");
+    ctx.begin_path();
+    ctx.arc(cx, cy, ring - RING_WIDTH / 2.0, 0.0, std::f64::consts::TAU)
+        .unwrap();
+    ctx.stroke();
+
+    // The clusters, as dots on the ring with an area by weight, and lines to
+    // the stretch center.
+    let (sx, sy) = point(props.center_hue, props.radius as f64);
+    ctx.set_stroke_style_str("#000");
+    for (i, c) in props.clusters.iter().enumerate() {
+        let (x, y) = point(c.hue, 1.0);
+        if i < 2 {
+            ctx.set_line_dash(&js_sys::Array::of2(&4.0.into(), &3.0.into()))
+                .unwrap();
+            ctx.begin_path();
+            ctx.move_to(sx, sy);
+            ctx.line_to(x, y);
+            ctx.stroke();
+            ctx.set_line_dash(&js_sys::Array::new()).unwrap();
+        }
+        ctx.begin_path();
+        let r = 3.0 + 10.0 * (c.weight as f64).sqrt();
+        ctx.arc(x, y, r, 0.0, std::f64::consts::TAU).unwrap();
+        ctx.set_fill_style_str(&format!("hsl({}, 100%, 50%)", c.hue * 360.0));
+        ctx.fill();
+        ctx.stroke();
+    }
+
+    // The stretch center, as a cross.
+    ctx.set_line_width(2.0);
+    ctx.begin_path();
+    ctx.move_to(sx - 6.0, sy - 6.0);
+    ctx.line_to(sx + 6.0, sy + 6.0);
+    ctx.move_to(sx - 6.0, sy + 6.0);
+    ctx.line_to(sx + 6.0, sy - 6.0);
+    ctx.stroke();
+}
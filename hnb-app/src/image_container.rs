@@ -1,34 +1,26 @@
 use std::{cell::RefCell, rc::Rc};
-use wasm_bindgen::{Clamped, JsCast, JsValue};
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
-use yew::{classes, html, Component, Context, Html, NodeRef, Properties};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, PointerEvent};
+use yew::{classes, html, Callback, Component, Context, Html, NodeRef, Properties, TargetCast};
+
+use hnb_core::{
+    calibration::ChartDetection,
+    classify::Classification,
+    image::output_basename,
+    roi::{Roi, Shape},
+};
 
 use crate::PositionInfo;
 
 const TEXT_PAD_PX: i32 = 2;
 const FONT: &str = "16px sans-serif";
 
-#[derive(Clone, Debug, PartialEq, Default)]
-pub enum ImType {
-    #[default]
-    Original,
-    Rotated,
-    Stretch,
-}
-
-impl std::fmt::Display for ImType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ImType::Original => write!(f, "Original"),
-            ImType::Rotated => write!(f, "Color Rotated"),
-            ImType::Stretch => write!(f, "Color Stretched"),
-        }
-    }
-}
-
 #[derive(PartialEq)]
 pub struct ImCanvasWrapper {
-    im_type: ImType,
+    /// Short identifier of the view, used in file names.
+    slug: String,
+    /// Human readable name of the view.
+    name: String,
     fname: String,
     context_2d: Option<CanvasRenderingContext2d>,
     canvas: Option<HtmlCanvasElement>,
@@ -37,15 +29,16 @@ pub struct ImCanvasWrapper {
 
 impl Drop for ImCanvasWrapper {
     fn drop(&mut self) {
-        log::debug!("Dropping ImCanvasWrapper for {:?}", self.im_type);
+        log::debug!("Dropping ImCanvasWrapper for {}", self.name);
     }
 }
 
 impl ImCanvasWrapper {
-    pub fn new(im_type: ImType, position_info: Rc<RefCell<PositionInfo>>) -> Self {
-        log::debug!("Creating ImCanvasWrapper for {:?}", im_type);
+    pub fn new(slug: &str, name: &str, position_info: Rc<RefCell<PositionInfo>>) -> Self {
+        log::debug!("Creating ImCanvasWrapper for {}", name);
         Self {
-            im_type,
+            slug: slug.to_string(),
+            name: name.to_string(),
             fname: "".to_string(),
             context_2d: None,
             canvas: None,
@@ -54,6 +47,16 @@ impl ImCanvasWrapper {
     }
 
     pub fn draw_image(&mut self, img: &web_sys::HtmlImageElement, fname: &str) {
+        self.draw_image_captioned(img, fname, fname);
+    }
+
+    /// Draw an image with `caption` below.
+    pub fn draw_image_captioned(
+        &mut self,
+        img: &web_sys::HtmlImageElement,
+        fname: &str,
+        caption: &str,
+    ) {
         log::debug!("ImCanvasWrapper::draw_image {}", fname);
         if let Some(ctx) = &self.context_2d {
             log::debug!("  got context_2d");
@@ -74,30 +77,16 @@ impl ImCanvasWrapper {
             )
             .unwrap();
 
-            let text = fname;
             self.fname = fname.to_string();
-            self.draw_text(ctx, text);
+            self.draw_text(ctx, caption);
         } else {
             log::error!("  no context_2d");
         }
     }
 
-    pub fn draw_data(&mut self, image_data: &web_sys::ImageData, fname: &str) {
-        log::debug!("ImCanvasWrapper::draw_data {}", self.im_type);
-        let mut data = image_data.data();
-        match self.im_type {
-            ImType::Original => {}
-            ImType::Rotated => {
-                crate::transform_colors::saturate_and_rotate(data.as_mut_slice());
-            }
-            ImType::Stretch => {
-                crate::transform_colors::color_stretch(data.as_mut_slice());
-            }
-        }
-
-        let w = image_data.width();
-        let h = image_data.height();
-
+    /// Draw transformed pixels with `caption` below.
+    pub fn draw_data(&mut self, image_data: &web_sys::ImageData, fname: &str, caption: &str) {
+        log::debug!("ImCanvasWrapper::draw_data {}", self.name);
         if let Some(ctx) = &self.context_2d {
             ctx.clear_rect(
                 0.0,
@@ -105,22 +94,38 @@ impl ImCanvasWrapper {
                 self.position_info.borrow().canv_width() as f64,
                 self.position_info.borrow().canv_height() as f64,
             );
+            ctx.put_image_data(image_data, 0.0, 0.0).unwrap();
 
-            let new_data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
-                Clamped(data.as_mut_slice()),
-                w,
-                h,
-            )
-            .unwrap();
-            ctx.put_image_data(&new_data, 0.0, 0.0).unwrap();
-
-            let text = match self.im_type {
-                ImType::Original => fname.to_string(),
-                ImType::Rotated => format!("{fname}: Color Rotated"),
-                ImType::Stretch => format!("{fname}: Color Stretched"),
-            };
             self.fname = fname.to_string();
-            self.draw_text(ctx, &text);
+            self.draw_text(ctx, caption);
+        }
+    }
+
+    /// Line width for drawing on the image, so that lines are visible when
+    /// the canvas is scaled down.
+    fn line_width(&self) -> f64 {
+        let width = self.position_info.borrow().canv_width();
+        (width as f64 / 400.0).ceil().max(2.0)
+    }
+
+    /// Outline regions of interest on top of the image.
+    pub fn draw_rois(&self, rois: &[Roi], draft: Option<&Shape>) {
+        if let Some(ctx) = &self.context_2d {
+            crate::roi::draw_rois(ctx, rois, draft, self.line_width());
+        }
+    }
+
+    /// Draw the calls of the regions on top of the image.
+    pub fn draw_badges(&self, rois: &[Roi], calls: &[Option<Classification>]) {
+        if let Some(ctx) = &self.context_2d {
+            crate::roi::draw_badges(ctx, rois, calls, self.line_width());
+        }
+    }
+
+    /// Outline the patches of a color chart on top of the image.
+    pub fn draw_chart(&self, chart: &ChartDetection) {
+        if let Some(ctx) = &self.context_2d {
+            crate::calibration::draw_chart(ctx, chart, self.line_width());
         }
     }
 
@@ -154,27 +159,11 @@ impl ImCanvasWrapper {
     }
 
     fn basename(&self) -> String {
-        let fname_os = std::ffi::OsString::from(&self.fname);
-
-        let stem = std::path::Path::new(&self.fname)
-            .file_stem()
-            .unwrap_or(&fname_os);
-
-        let what = match self.im_type {
-            ImType::Original => "original",
-            ImType::Rotated => "rotated",
-            ImType::Stretch => "stretch",
-        };
-
-        format!("{}-{}", stem.to_str().unwrap(), what)
+        output_basename(&self.fname, &self.slug)
     }
 
-    fn button_text(&self) -> &str {
-        match self.im_type {
-            ImType::Original => "Download original",
-            ImType::Rotated => "Download color-rotated",
-            ImType::Stretch => "Download color-stretched",
-        }
+    fn button_text(&self) -> String {
+        format!("Download {}", self.name.to_lowercase())
     }
 }
 
@@ -182,25 +171,47 @@ pub struct ImageContainer {
     node_ref: NodeRef,
 }
 
+/// A pointer event on the image, in image pixel coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CanvasPointer {
+    Down(f32, f32),
+    Move(f32, f32),
+    Up(f32, f32),
+    DoubleClick,
+}
+
+/// The position of a pointer event in canvas coordinates, which differ from
+/// the position on the page if the canvas is scaled to fit.
+fn canvas_coords(e: &PointerEvent) -> (f32, f32) {
+    let canvas: HtmlCanvasElement = e.target_unchecked_into();
+    let scale = canvas.width() as f32 / canvas.client_width().max(1) as f32;
+    (e.offset_x() as f32 * scale, e.offset_y() as f32 * scale)
+}
+
 pub enum Msg {
     Clicked,
 }
 
 #[derive(PartialEq, Properties)]
 pub struct Props {
-    pub im_type: ImType,
     pub canvas_wrapper: Rc<RefCell<ImCanvasWrapper>>,
     /// A count that changes when the image is updated, to force calling the
     /// ImageContainer::view() method to display the potentially new width and
     /// height of the HTML canvas element.
     pub count: u8,
+    /// Receives pointer events on the canvas, if given.
+    #[prop_or_default]
+    pub on_pointer: Option<Callback<CanvasPointer>>,
 }
 
 impl Component for ImageContainer {
     type Message = Msg;
     type Properties = Props;
     fn create(ctx: &Context<Self>) -> Self {
-        log::debug!("Creating ImageContainer for {:?}", ctx.props().im_type);
+        log::debug!(
+            "Creating ImageContainer for {}",
+            ctx.props().canvas_wrapper.borrow().name
+        );
         Self {
             node_ref: NodeRef::default(),
         }
@@ -209,14 +220,14 @@ impl Component for ImageContainer {
     fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
         log::debug!(
             "ImageContainer::rendered {}",
-            ctx.props().canvas_wrapper.borrow().im_type
+            ctx.props().canvas_wrapper.borrow().name
         );
         // Once rendered, store references for the canvas and 2D context. These can be used for
         // resizing the rendering area when the window or canvas element are resized.
         if ctx.props().canvas_wrapper.borrow().canvas.is_none() {
             log::debug!(
-                "  setting up canvas and context_2d for {:?}",
-                ctx.props().canvas_wrapper.borrow().im_type
+                "  setting up canvas and context_2d for {}",
+                ctx.props().canvas_wrapper.borrow().name
             );
             assert!(ctx.props().canvas_wrapper.borrow().context_2d.is_none());
             let canvas = self.node_ref.cast::<HtmlCanvasElement>().unwrap();
@@ -234,7 +245,7 @@ impl Component for ImageContainer {
     fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
         log::debug!(
             "ImageContainer::changed {}",
-            ctx.props().canvas_wrapper.borrow().im_type
+            ctx.props().canvas_wrapper.borrow().name
         );
         true
     }
@@ -242,7 +253,7 @@ impl Component for ImageContainer {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         log::debug!(
             "ImageContainer::update {}",
-            ctx.props().canvas_wrapper.borrow().im_type
+            ctx.props().canvas_wrapper.borrow().name
         );
         match msg {
             Msg::Clicked => {
@@ -299,19 +310,40 @@ impl Component for ImageContainer {
         };
         let width = pi.borrow().canv_width_str();
         let height = pi.borrow().canv_height_str();
-        log::debug!(
-            "ImageContainer::view {} {}x{}",
-            ctx.props().im_type,
-            width,
-            height
-        );
+        log::debug!("ImageContainer::view {} {}x{}", cw.name, width, height);
+        let canvas = match &ctx.props().on_pointer {
+            Some(cb) => {
+                let (down, moved, up, dbl) = (cb.clone(), cb.clone(), cb.clone(), cb.clone());
+                html! {
+                    <canvas class={classes!("im-canvas", "roi-canvas")} ref={&self.node_ref}
+                        width={width} height={height}
+                        onpointerdown={move |e: PointerEvent| {
+                            let (x, y) = canvas_coords(&e);
+                            down.emit(CanvasPointer::Down(x, y));
+                        }}
+                        onpointermove={move |e: PointerEvent| {
+                            let (x, y) = canvas_coords(&e);
+                            moved.emit(CanvasPointer::Move(x, y));
+                        }}
+                        onpointerup={move |e: PointerEvent| {
+                            let (x, y) = canvas_coords(&e);
+                            up.emit(CanvasPointer::Up(x, y));
+                        }}
+                        ondblclick={move |_| dbl.emit(CanvasPointer::DoubleClick)}
+                    />
+                }
+            }
+            None => html! {
+                <canvas class="im-canvas" ref={&self.node_ref} width={width} height={height} />
+            },
+        };
         html! {
             <span class="im-span">
                 <div>
                     {button}
                 </div>
                 <div>
-                    <canvas class="im-canvas" ref={&self.node_ref} width={width} height={height} />
+                    {canvas}
                 </div>
             </span>
         }
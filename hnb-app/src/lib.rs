@@ -0,0 +1,4 @@
+//! Code shared by the `hnb-app` binary, which runs the user interface, and
+//! the `hnb-worker` binary, which does the pixel processing in a Web Worker.
+
+pub mod worker_protocol;
@@ -1,9 +1,18 @@
 #![recursion_limit = "512"]
 
 mod app;
+mod calibration;
+mod chromaticity_plot;
+mod color_cloud;
+mod download;
 mod file_input;
+mod hue_plot;
+mod hue_wheel;
 mod image_container;
-mod transform_colors;
+mod param_input;
+mod presets;
+mod roi;
+mod select;
 
 use console_error_panic_hook::set_once as set_panic_hook;
 use std::{cell::RefCell, rc::Rc};
@@ -0,0 +1,88 @@
+use web_sys::{Event, HtmlInputElement, InputEvent};
+use yew::{classes, html, Callback, Component, Context, Html, Properties, TargetCast};
+
+/// A labelled slider with a linked number entry for a single parameter.
+pub struct ParamInput {}
+
+pub enum Msg {
+    Changed(f64),
+    /// The slider was released or a number entered.
+    Released,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub label: String,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    /// Called with every value, also while the slider is dragged.
+    pub on_changed: Callback<f32>,
+    /// Called when the value is final, e.g. to do work skipped while the
+    /// slider is dragged.
+    #[prop_or_default]
+    pub on_released: Callback<()>,
+}
+
+impl Component for ParamInput {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {}
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Changed(value) => {
+                // The number entry allows typing anything, so ignore values
+                // which are not (yet) numbers.
+                if value.is_finite() {
+                    ctx.props().on_changed.emit(value as f32);
+                }
+            }
+            Msg::Released => ctx.props().on_released.emit(()),
+        }
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let value = format!("{}", props.value);
+        let min = format!("{}", props.min);
+        let max = format!("{}", props.max);
+        let step = format!("{}", props.step);
+        html! {
+            <div class={classes!("param-input")}>
+                <label>
+                    <span class="param-label">{props.label.as_str()}</span>
+                    <input
+                        type="range"
+                        min={min.clone()}
+                        max={max.clone()}
+                        step={step.clone()}
+                        value={value.clone()}
+                        oninput={ctx.link().callback(|e: InputEvent| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            Msg::Changed(input.value_as_number())
+                        })}
+                        onchange={ctx.link().callback(|_| Msg::Released)}
+                    />
+                    <input
+                        type="number"
+                        class="param-number"
+                        min={min}
+                        max={max}
+                        step={step}
+                        value={value}
+                        onchange={ctx.link().batch_callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            vec![Msg::Changed(input.value_as_number()), Msg::Released]
+                        })}
+                    />
+                </label>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Presets of transform parameters saved in the browser, and the regions with
+//! known results used to tune the transforms.
+
+use hnb_core::{
+    color_transform::Preset,
+    optimize::{subsample, LabelledPixels},
+    roi::Roi,
+};
+use web_sys::Storage;
+
+/// Key of the presets in the local storage of the browser, one per line.
+const STORAGE_KEY: &str = "hnb-presets";
+
+/// Pixels kept per region for tuning, to keep the optimization interactive.
+const MAX_TRAINING_PIXELS: usize = 1000;
+
+fn local_storage() -> Result<Storage, String> {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .ok_or_else(|| "The browser does not allow saving presets.".to_string())
+}
+
+/// The saved presets, and errors for those which cannot be read.
+pub fn load_presets() -> (Vec<Preset>, Vec<String>) {
+    let text = match local_storage().map(|s| s.get_item(STORAGE_KEY)) {
+        Ok(Ok(Some(text))) => text,
+        _ => return (Vec::new(), Vec::new()),
+    };
+    let mut presets = Vec::new();
+    let mut errors = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match Preset::parse_line(line) {
+            Ok(preset) => presets.push(preset),
+            Err(e) => errors.push(format!("Saved presets: {e}")),
+        }
+    }
+    (presets, errors)
+}
+
+pub fn save_presets(presets: &[Preset]) -> Result<(), String> {
+    let lines: Vec<String> = presets.iter().map(Preset::to_line).collect();
+    local_storage()?
+        .set_item(STORAGE_KEY, &lines.join("\n"))
+        .map_err(|e| format!("Cannot save presets: {e:?}"))
+}
+
+/// A control region of an image, for tuning transforms.
+pub struct TrainingRegion {
+    /// File name of the image.
+    pub image: String,
+    pub sample: LabelledPixels,
+}
+
+/// The control regions of the RGBA image `data`, named `image`.
+pub fn control_regions(
+    image: &str,
+    rois: &[Roi],
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Vec<TrainingRegion> {
+    rois.iter()
+        .filter_map(|roi| {
+            let pixels = roi.shape.extract_pixels(data, width, height);
+            if pixels.is_empty() {
+                return None;
+            }
+            Some(TrainingRegion {
+                image: image.to_string(),
+                sample: LabelledPixels {
+                    control: roi.control?,
+                    pixels: subsample(&pixels, MAX_TRAINING_PIXELS),
+                },
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,443 @@
+//! Drawing regions of interest on the original image and showing their color
+//! statistics.
+
+use js_sys::Array;
+use web_sys::CanvasRenderingContext2d;
+use yew::{html, Callback, Html};
+
+use hnb_core::{
+    classify::{Call, Classification, Control},
+    roi::{Roi, Shape},
+    stats::{ColorStats, Summary},
+};
+
+use crate::image_container::CanvasPointer;
+
+/// Clicking closer than this (in image pixels) to the first vertex of a
+/// polygon closes it.
+const CLOSE_POLYGON_PX: f32 = 8.0;
+
+/// Rectangles and circles smaller than this (in image pixels) are ignored, as
+/// they are probably accidental clicks.
+const MIN_SIZE_PX: f32 = 2.0;
+
+const ROI_COLOR: &str = "#ffd700";
+const DRAFT_COLOR: &str = "#ffffff";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoiTool {
+    Rect,
+    Circle,
+    Polygon,
+}
+
+impl RoiTool {
+    pub const ALL: [RoiTool; 3] = [RoiTool::Rect, RoiTool::Circle, RoiTool::Polygon];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoiTool::Rect => "Rectangle",
+            RoiTool::Circle => "Circle",
+            RoiTool::Polygon => "Polygon",
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            RoiTool::Rect => "Drag on the original image to draw a rectangle.",
+            RoiTool::Circle => "Drag from the center on the original image to draw a circle.",
+            RoiTool::Polygon => {
+                "Click the corners on the original image, then double click or click the \
+                first corner to close the polygon."
+            }
+        }
+    }
+}
+
+/// A region of interest while it is being drawn.
+pub struct RoiDraft {
+    tool: RoiTool,
+    points: Vec<(f32, f32)>,
+    cursor: (f32, f32),
+}
+
+impl RoiDraft {
+    /// The shape as drawn so far, up to the pointer position.
+    pub fn shape(&self) -> Shape {
+        let (x0, y0) = self.points[0];
+        let (x1, y1) = self.cursor;
+        match self.tool {
+            RoiTool::Rect => Shape::Rect { x0, y0, x1, y1 },
+            RoiTool::Circle => Shape::Circle {
+                cx: x0,
+                cy: y0,
+                r: (x1 - x0).hypot(y1 - y0),
+            },
+            RoiTool::Polygon => {
+                let mut points = self.points.clone();
+                points.push(self.cursor);
+                Shape::Polygon(points)
+            }
+        }
+    }
+
+    fn finish(mut self) -> Option<Shape> {
+        match self.tool {
+            RoiTool::Rect | RoiTool::Circle => {
+                let (x0, y0, x1, y1) = self.shape().bounds();
+                if x1 - x0 < MIN_SIZE_PX || y1 - y0 < MIN_SIZE_PX {
+                    None
+                } else {
+                    Some(self.shape())
+                }
+            }
+            RoiTool::Polygon => {
+                // A double click also adds two vertices at the same place.
+                self.points
+                    .dedup_by(|a, b| (a.0 - b.0).hypot(a.1 - b.1) < 1.0);
+                if self.points.len() < 3 {
+                    None
+                } else {
+                    Some(Shape::Polygon(self.points))
+                }
+            }
+        }
+    }
+}
+
+/// Update the region being drawn with `tool` for a pointer event. Returns the
+/// finished shape, if any.
+pub fn handle_pointer(
+    draft: &mut Option<RoiDraft>,
+    tool: RoiTool,
+    event: CanvasPointer,
+) -> Option<Shape> {
+    match event {
+        CanvasPointer::Down(x, y) => match draft {
+            None => {
+                *draft = Some(RoiDraft {
+                    tool,
+                    points: vec![(x, y)],
+                    cursor: (x, y),
+                });
+                None
+            }
+            Some(d) if d.tool == RoiTool::Polygon => {
+                let (x0, y0) = d.points[0];
+                if d.points.len() >= 3 && (x - x0).hypot(y - y0) < CLOSE_POLYGON_PX {
+                    draft.take().and_then(RoiDraft::finish)
+                } else {
+                    d.points.push((x, y));
+                    None
+                }
+            }
+            Some(_) => None,
+        },
+        CanvasPointer::Move(x, y) => {
+            if let Some(d) = draft {
+                d.cursor = (x, y);
+            }
+            None
+        }
+        CanvasPointer::Up(x, y) => match draft {
+            Some(d) if d.tool != RoiTool::Polygon => {
+                d.cursor = (x, y);
+                draft.take().and_then(RoiDraft::finish)
+            }
+            _ => None,
+        },
+        CanvasPointer::DoubleClick => match draft {
+            Some(d) if d.tool == RoiTool::Polygon => draft.take().and_then(RoiDraft::finish),
+            _ => None,
+        },
+    }
+}
+
+fn trace_shape(ctx: &CanvasRenderingContext2d, shape: &Shape) {
+    ctx.begin_path();
+    match shape {
+        Shape::Rect { x0, y0, x1, y1 } => {
+            ctx.rect(
+                x0.min(*x1) as f64,
+                y0.min(*y1) as f64,
+                (x1 - x0).abs() as f64,
+                (y1 - y0).abs() as f64,
+            );
+        }
+        Shape::Circle { cx, cy, r } => {
+            ctx.arc(
+                *cx as f64,
+                *cy as f64,
+                *r as f64,
+                0.0,
+                std::f64::consts::TAU,
+            )
+            .unwrap();
+        }
+        Shape::Polygon(points) => {
+            for (i, (x, y)) in points.iter().enumerate() {
+                if i == 0 {
+                    ctx.move_to(*x as f64, *y as f64);
+                } else {
+                    ctx.line_to(*x as f64, *y as f64);
+                }
+            }
+            ctx.close_path();
+        }
+    }
+}
+
+/// Outline the regions and the region being drawn, with their labels.
+///
+/// `line_width` is in canvas pixels, which should be chosen depending on the
+/// image size to be visible when the canvas is scaled down.
+pub fn draw_rois(
+    ctx: &CanvasRenderingContext2d,
+    rois: &[Roi],
+    draft: Option<&Shape>,
+    line_width: f64,
+) {
+    ctx.save();
+    ctx.set_line_width(line_width);
+    ctx.set_font(&format!("bold {}px sans-serif", (8.0 * line_width).round()));
+    ctx.set_text_baseline("bottom");
+    ctx.set_stroke_style_str(ROI_COLOR);
+    ctx.set_fill_style_str(ROI_COLOR);
+    for roi in rois.iter() {
+        trace_shape(ctx, &roi.shape);
+        ctx.stroke();
+        let (x0, y0, _, _) = roi.shape.bounds();
+        ctx.fill_text(&roi_caption(roi), x0 as f64, y0 as f64 - line_width)
+            .unwrap();
+    }
+    if let Some(shape) = draft {
+        ctx.set_stroke_style_str(DRAFT_COLOR);
+        let dash = Array::of2(&(3.0 * line_width).into(), &(3.0 * line_width).into());
+        ctx.set_line_dash(&dash).unwrap();
+        trace_shape(ctx, shape);
+        ctx.stroke();
+    }
+    ctx.restore();
+}
+
+/// The label of a region, with the kind of control if it is one.
+pub fn roi_caption(roi: &Roi) -> String {
+    match roi.control {
+        Some(control) => format!("{} ({})", roi.label, control.short_name()),
+        None => roi.label.clone(),
+    }
+}
+
+/// The color showing a call, or the controls of its kind.
+pub fn call_color(call: Call) -> &'static str {
+    match call {
+        Call::Positive => "#2e9cdb",
+        Call::Negative => "#7b3fa0",
+        Call::Inconclusive => "#8c8c8c",
+    }
+}
+
+/// Draw a badge with the call and the label at the center of each region, or
+/// with the kind of control for the controls.
+///
+/// `calls` are in the order of `rois`; other regions without a call get no
+/// badge.
+pub fn draw_badges(
+    ctx: &CanvasRenderingContext2d,
+    rois: &[Roi],
+    calls: &[Option<Classification>],
+    line_width: f64,
+) {
+    let radius = 6.0 * line_width;
+    ctx.save();
+    ctx.set_line_width(line_width);
+    ctx.set_text_align("center");
+    ctx.set_stroke_style_str("#ffffff");
+    for (roi, c) in rois.iter().zip(calls.iter()) {
+        let (color, symbol, text) = match (roi.control, c) {
+            (Some(control), _) => {
+                let call = match control {
+                    Control::Positive => Call::Positive,
+                    Control::Negative => Call::Negative,
+                };
+                (call_color(call), control.short_name(), roi.label.clone())
+            }
+            (None, Some(c)) => (
+                call_color(c.call),
+                c.call.symbol(),
+                format!("{} {:.0}%", roi.label, 100.0 * c.confidence),
+            ),
+            (None, None) => continue,
+        };
+        let (x, y) = roi.shape.center();
+        let (x, y) = (x as f64, y as f64);
+        ctx.begin_path();
+        ctx.arc(x, y, radius, 0.0, std::f64::consts::TAU).unwrap();
+        ctx.set_fill_style_str(color);
+        ctx.fill();
+        ctx.stroke();
+
+        ctx.set_fill_style_str("#ffffff");
+        ctx.set_text_baseline("middle");
+        ctx.set_font(&format!("bold {}px sans-serif", (1.4 * radius).round()));
+        ctx.fill_text(symbol, x, y).unwrap();
+
+        // The label below the badge, outlined to be readable on any color.
+        ctx.set_text_baseline("top");
+        ctx.set_font(&format!("bold {}px sans-serif", (0.8 * radius).round()));
+        let y = y + radius + line_width;
+        ctx.set_stroke_style_str("#000000");
+        ctx.stroke_text(&text, x, y).unwrap();
+        ctx.fill_text(&text, x, y).unwrap();
+        ctx.set_stroke_style_str("#ffffff");
+    }
+    ctx.restore();
+}
+
+/// The statistics of one region in one image.
+pub struct RoiRow {
+    pub roi: String,
+    /// Name of the original image or of the view.
+    pub image: String,
+    pub stats: Option<ColorStats>,
+}
+
+fn format_summary(s: &Summary, precision: usize) -> String {
+    format!(
+        "{:.p$} ± {:.p$} ({:.p$})",
+        s.mean,
+        s.std,
+        s.median,
+        p = precision
+    )
+}
+
+fn stats_cells(stats: &Option<ColorStats>) -> Html {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => {
+            return html! {
+                <>
+                    <td>{"0"}</td>
+                    <td colspan="10">{"No pixels in this region."}</td>
+                </>
+            }
+        }
+    };
+    let hue = match &stats.hue {
+        Some(h) => format!("{:.1}° ± {:.1}° ({:.1}°)", h.mean, h.std, h.median),
+        None => "–".to_string(),
+    };
+    html! {
+        <>
+            <td>{stats.pixel_count}</td>
+            { for stats.rgb.iter().map(|s| html! { <td>{format_summary(s, 1)}</td> }) }
+            <td>{hue}</td>
+            <td>{format_summary(&stats.saturation, 3)}</td>
+            { for stats.lab.iter().map(|s| html! { <td>{format_summary(s, 1)}</td> }) }
+            <td>{format_summary(&stats.chroma, 1)}</td>
+        </>
+    }
+}
+
+/// A table of the statistics and calls, with buttons to mark each region as
+/// a control and to remove it.
+///
+/// `rows` are grouped by region, in the order of `rois`, and so are `calls`.
+pub fn view_roi_table(
+    rois: &[Roi],
+    rows: &[RoiRow],
+    calls: &[Option<Classification>],
+    on_remove: Callback<usize>,
+    on_control: Callback<(usize, Option<Control>)>,
+) -> Html {
+    if rois.is_empty() {
+        return html! {};
+    }
+    html! {
+        <div class="roi-table">
+            <p>{"Values are mean ± standard deviation (median). Hue and saturation are those of \
+            HSL, computed from the sRGB values. L*, a*, b* and chroma C* are CIELAB (D65)."}</p>
+            <table>
+                <thead>
+                    <tr>
+                        <th>{"ROI"}</th>
+                        <th>{"Image"}</th>
+                        <th>{"Pixels"}</th>
+                        <th>{"R"}</th>
+                        <th>{"G"}</th>
+                        <th>{"B"}</th>
+                        <th>{"Hue"}</th>
+                        <th>{"Saturation"}</th>
+                        <th>{"L*"}</th>
+                        <th>{"a*"}</th>
+                        <th>{"b*"}</th>
+                        <th>{"C*"}</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for rois.iter().enumerate().map(|(i, roi)| {
+                        let roi_rows: Vec<&RoiRow> =
+                            rows.iter().filter(|r| r.roi == roi.label).collect();
+                        let n = roi_rows.len().max(1).to_string();
+                        let on_remove = on_remove.clone();
+                        let call = match calls.get(i) {
+                            Some(Some(c)) => {
+                                let score = match c.score {
+                                    Some(score) => format!(", score {score:.2}"),
+                                    None => String::new(),
+                                };
+                                html! {
+                                    <>
+                                        <br/>
+                                        {format!("{} ({:.0}%{score})", c.call.name(),
+                                            100.0 * c.confidence)}
+                                    </>
+                                }
+                            }
+                            _ => html! {},
+                        };
+                        let control_buttons = Control::ALL.iter().map(|&control| {
+                            let on_control = on_control.clone();
+                            let selected = roi.control == Some(control);
+                            let class = if selected { "btn selected" } else { "btn" };
+                            // Clicking the selected kind unmarks the region.
+                            let value = if selected { None } else { Some(control) };
+                            html! {
+                                <button class={class} title={control.name()}
+                                    onclick={move |_| on_control.emit((i, value))}>
+                                    {control.short_name()}
+                                </button>
+                            }
+                        });
+                        let head = html! {
+                            <td rowspan={n}>
+                                {roi.label.as_str()}
+                                {call}
+                                <br/>
+                                { for control_buttons }
+                                <button class="btn" onclick={move |_| on_remove.emit(i)}>
+                                    {"Remove"}
+                                </button>
+                            </td>
+                        };
+                        if roi_rows.is_empty() {
+                            return html! { <tr>{head}<td colspan="11"></td></tr> };
+                        }
+                        html! {
+                            <>
+                            { for roi_rows.iter().enumerate().map(|(j, row)| html! {
+                                <tr>
+                                    { if j == 0 { head.clone() } else { html! {} } }
+                                    <td>{row.image.as_str()}</td>
+                                    { stats_cells(&row.stats) }
+                                </tr>
+                            })}
+                            </>
+                        }
+                    })}
+                </tbody>
+            </table>
+        </div>
+    }
+}
@@ -0,0 +1,38 @@
+//! A drop-down list of choices, e.g. the `ALL` values of an enum.
+
+use web_sys::{Event, HtmlSelectElement};
+use yew::{html, Callback, Html, TargetCast};
+
+/// A `<select>` showing each of `choices` by its `name`, with `selected`
+/// chosen. Picking another choice emits it to `on_select`.
+pub fn view_select<T>(
+    choices: &[T],
+    selected: &T,
+    name: impl Fn(&T) -> String,
+    on_select: Callback<T>,
+) -> Html
+where
+    T: Clone + PartialEq + 'static,
+{
+    let options = choices.to_vec();
+    let onchange = move |e: Event| {
+        let select: HtmlSelectElement = e.target_unchecked_into();
+        let choice = select
+            .value()
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| options.get(i));
+        if let Some(choice) = choice {
+            on_select.emit(choice.clone());
+        }
+    };
+    html! {
+        <select {onchange}>
+            { for choices.iter().enumerate().map(|(i, choice)| html! {
+                <option value={i.to_string()} selected={choice == selected}>
+                    {name(choice)}
+                </option>
+            })}
+        </select>
+    }
+}
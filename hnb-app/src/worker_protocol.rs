@@ -0,0 +1,316 @@
+//! Messages between the app and the pixel processing worker.
+//!
+//! The messages are plain JavaScript objects. Pixel buffers are sent as
+//! `ArrayBuffer`s in the transfer list of `postMessage()`, so they are moved
+//! between the threads instead of copied.
+
+use js_sys::{Array, ArrayBuffer, Float32Array, Object, Reflect};
+use std::convert::TryInto;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{DedicatedWorkerGlobalScope, Worker};
+
+use hnb_core::{
+    color_transform::{ColorTransform, Registry},
+    cube::CubeLut,
+    lut::{Interpolation, Lut3d, Processing},
+};
+
+/// URL of the worker script, as built by trunk, relative to the app page.
+pub const WORKER_URL: &str = "./hnb-worker_loader.js";
+
+fn set(obj: &Object, key: &str, value: impl Into<JsValue>) {
+    Reflect::set(obj, &JsValue::from_str(key), &value.into()).unwrap();
+}
+
+fn get(obj: &JsValue, key: &str) -> Option<JsValue> {
+    Reflect::get(obj, &JsValue::from_str(key))
+        .ok()
+        .filter(|v| !v.is_undefined())
+}
+
+fn get_f64(obj: &JsValue, key: &str) -> Option<f64> {
+    get(obj, key)?.as_f64()
+}
+
+fn get_u32(obj: &JsValue, key: &str) -> Option<u32> {
+    get_f64(obj, key).map(|v| v as u32)
+}
+
+fn get_string(obj: &JsValue, key: &str) -> Option<String> {
+    get(obj, key)?.as_string()
+}
+
+fn get_rgb(obj: &JsValue, key: &str) -> Option<[f32; 3]> {
+    let values = Float32Array::new(&get(obj, key)?).to_vec();
+    values.try_into().ok()
+}
+
+fn processing_to_string(processing: Processing) -> String {
+    match processing {
+        Processing::Direct => "direct".into(),
+        Processing::Full => "full".into(),
+        Processing::Grid {
+            size,
+            interpolation: Interpolation::Tetrahedral,
+        } => format!("{size}"),
+        Processing::Grid {
+            size,
+            interpolation: Interpolation::Trilinear,
+        } => format!("{size}-trilinear"),
+    }
+}
+
+fn processing_from_str(value: &str) -> Option<Processing> {
+    match value {
+        "direct" => Some(Processing::Direct),
+        "full" => Some(Processing::Full),
+        _ => {
+            let (size, interpolation) = match value.strip_suffix("-trilinear") {
+                Some(size) => (size, Interpolation::Trilinear),
+                None => (value, Interpolation::Tetrahedral),
+            };
+            Some(Processing::Grid {
+                size: size.parse().ok().filter(|s| (2..=256).contains(s))?,
+                interpolation,
+            })
+        }
+    }
+}
+
+/// What the worker needs to recreate a transform of the app.
+pub enum TransformSpec {
+    /// A transform from the [Registry] with its parameter values.
+    Registered {
+        slug: String,
+        parameters: Vec<(String, f32)>,
+    },
+    /// A lookup table, e.g. from a `.cube` file.
+    Table { name: String, lut: Lut3d },
+}
+
+impl TransformSpec {
+    pub fn of(transform: &dyn ColorTransform) -> Self {
+        match transform.lookup_table() {
+            Some(lut) => TransformSpec::Table {
+                name: transform.name().to_string(),
+                lut: lut.clone(),
+            },
+            None => TransformSpec::Registered {
+                slug: transform.slug().to_string(),
+                parameters: transform
+                    .parameters()
+                    .into_iter()
+                    .map(|p| (p.slug.to_string(), p.value))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Recreate the transform. Returns `None` for unknown slugs.
+    pub fn create(&self, registry: &Registry) -> Option<Box<dyn ColorTransform>> {
+        match self {
+            TransformSpec::Registered { slug, parameters } => {
+                let mut transform = registry.create(slug)?;
+                for (name, value) in parameters.iter() {
+                    transform.set_parameter(name, *value);
+                }
+                Some(transform)
+            }
+            TransformSpec::Table { name, lut } => {
+                Some(Box::new(CubeLut::new(name.clone(), lut.clone())))
+            }
+        }
+    }
+
+    fn to_js(&self) -> JsValue {
+        let obj = Object::new();
+        match self {
+            TransformSpec::Registered { slug, parameters } => {
+                set(&obj, "slug", slug.as_str());
+                let params = Object::new();
+                for (name, value) in parameters.iter() {
+                    set(&params, name, *value);
+                }
+                set(&obj, "parameters", params);
+            }
+            TransformSpec::Table { name, lut } => {
+                set(&obj, "name", name.as_str());
+                set(&obj, "size", lut.size() as u32);
+                set(&obj, "values", Float32Array::from(lut.values()));
+                set(
+                    &obj,
+                    "domain_min",
+                    Float32Array::from(&lut.domain_min()[..]),
+                );
+                set(
+                    &obj,
+                    "domain_max",
+                    Float32Array::from(&lut.domain_max()[..]),
+                );
+            }
+        }
+        obj.into()
+    }
+
+    fn from_js(obj: &JsValue) -> Option<Self> {
+        if let Some(slug) = get_string(obj, "slug") {
+            let params: Object = get(obj, "parameters")?.dyn_into().ok()?;
+            let parameters = Object::entries(&params)
+                .iter()
+                .filter_map(|entry| {
+                    let entry: Array = entry.dyn_into().ok()?;
+                    Some((entry.get(0).as_string()?, entry.get(1).as_f64()? as f32))
+                })
+                .collect();
+            return Some(TransformSpec::Registered { slug, parameters });
+        }
+        let size = get_u32(obj, "size")? as usize;
+        let values = Float32Array::new(&get(obj, "values")?).to_vec();
+        if size < 2 || values.len() != 3 * size * size * size {
+            return None;
+        }
+        let domain_min = get_rgb(obj, "domain_min")?;
+        let domain_max = get_rgb(obj, "domain_max")?;
+        if (0..3).any(|c| domain_min[c] >= domain_max[c]) {
+            return None;
+        }
+        Some(TransformSpec::Table {
+            name: get_string(obj, "name")?,
+            lut: Lut3d::from_values(size, values).with_domain(domain_min, domain_max),
+        })
+    }
+}
+
+/// A request to the worker to transform the pixels of one view.
+pub struct Job {
+    /// Identifies one round of processing of all views.
+    pub job_id: u32,
+    pub view_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub processing: Processing,
+    pub transform: TransformSpec,
+    /// RGBA pixels, `4 * width * height` bytes.
+    pub pixels: ArrayBuffer,
+}
+
+impl Job {
+    /// Send the job, moving the pixel buffer to the worker.
+    pub fn post(self, worker: &Worker) -> Result<(), JsValue> {
+        let obj = Object::new();
+        set(&obj, "job_id", self.job_id);
+        set(&obj, "view_id", self.view_id);
+        set(&obj, "width", self.width);
+        set(&obj, "height", self.height);
+        set(&obj, "processing", processing_to_string(self.processing));
+        set(&obj, "transform", self.transform.to_js());
+        set(&obj, "pixels", self.pixels.clone());
+        worker.post_message_with_transfer(&obj, &Array::of1(&self.pixels))
+    }
+
+    pub fn from_js(obj: &JsValue) -> Option<Self> {
+        Some(Self {
+            job_id: get_u32(obj, "job_id")?,
+            view_id: get_u32(obj, "view_id")?,
+            width: get_u32(obj, "width")?,
+            height: get_u32(obj, "height")?,
+            processing: processing_from_str(&get_string(obj, "processing")?)?,
+            transform: TransformSpec::from_js(&get(obj, "transform")?)?,
+            pixels: get(obj, "pixels")?.dyn_into().ok()?,
+        })
+    }
+}
+
+/// A message from the worker about a [Job].
+pub enum Reply {
+    /// The fraction of the pixels of the job done so far.
+    Progress {
+        job_id: u32,
+        view_id: u32,
+        fraction: f32,
+    },
+    /// The transformed pixels.
+    Done {
+        job_id: u32,
+        view_id: u32,
+        width: u32,
+        height: u32,
+        pixels: ArrayBuffer,
+    },
+    Failed {
+        job_id: u32,
+        view_id: u32,
+        message: String,
+    },
+}
+
+impl Reply {
+    /// Send the reply to the app, moving the pixel buffer if there is one.
+    pub fn post(self, scope: &DedicatedWorkerGlobalScope) -> Result<(), JsValue> {
+        let obj = Object::new();
+        let transfer = Array::new();
+        match self {
+            Reply::Progress {
+                job_id,
+                view_id,
+                fraction,
+            } => {
+                set(&obj, "type", "progress");
+                set(&obj, "job_id", job_id);
+                set(&obj, "view_id", view_id);
+                set(&obj, "fraction", fraction);
+            }
+            Reply::Done {
+                job_id,
+                view_id,
+                width,
+                height,
+                pixels,
+            } => {
+                set(&obj, "type", "done");
+                set(&obj, "job_id", job_id);
+                set(&obj, "view_id", view_id);
+                set(&obj, "width", width);
+                set(&obj, "height", height);
+                set(&obj, "pixels", pixels.clone());
+                transfer.push(&pixels);
+            }
+            Reply::Failed {
+                job_id,
+                view_id,
+                message,
+            } => {
+                set(&obj, "type", "failed");
+                set(&obj, "job_id", job_id);
+                set(&obj, "view_id", view_id);
+                set(&obj, "message", message);
+            }
+        }
+        scope.post_message_with_transfer(&obj, &transfer)
+    }
+
+    pub fn from_js(obj: &JsValue) -> Option<Self> {
+        let job_id = get_u32(obj, "job_id")?;
+        let view_id = get_u32(obj, "view_id")?;
+        match get_string(obj, "type")?.as_str() {
+            "progress" => Some(Reply::Progress {
+                job_id,
+                view_id,
+                fraction: get_f64(obj, "fraction")? as f32,
+            }),
+            "done" => Some(Reply::Done {
+                job_id,
+                view_id,
+                width: get_u32(obj, "width")?,
+                height: get_u32(obj, "height")?,
+                pixels: get(obj, "pixels")?.dyn_into().ok()?,
+            }),
+            "failed" => Some(Reply::Failed {
+                job_id,
+                view_id,
+                message: get_string(obj, "message")?,
+            }),
+            _ => None,
+        }
+    }
+}
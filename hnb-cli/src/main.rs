@@ -0,0 +1,343 @@
+//! `hnb` - batch process images with the transforms of the HNB web app.
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use hnb_core::{
+    color_transform::{ColorTransform, Registry},
+    colorspace::{correlated_color_temperature, linear_to_xyz, xyz_to_xy},
+    cube::{transform_to_cube, DEFAULT_CUBE_SIZE, MAX_CUBE_SIZE},
+    image::{output_basename, RgbaImage},
+    lut::{Interpolation, LutCache, Processing},
+    white_balance::{estimate_illuminant, Adaptation, IlluminantEstimator, WhiteBalance},
+};
+
+/// File extensions of images which are processed when scanning directories.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff"];
+
+/// Apply the color transforms of the HNB web app to image files.
+///
+/// For every input image and transform, a PNG named `<stem>-<transform>.png`
+/// is written, e.g. `plate-rotated.png` and `plate-stretch.png`.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    /// Image files or directories containing image files.
+    #[arg(required_unless_present_any = ["list", "cube"])]
+    inputs: Vec<PathBuf>,
+
+    /// Scan directories recursively.
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Directory for the output files. Defaults to the directory of each input.
+    #[arg(short, long)]
+    output_dir: Option<PathBuf>,
+
+    /// Transform to apply (may be given multiple times). Defaults to all.
+    #[arg(short, long = "transform", value_name = "SLUG")]
+    transforms: Vec<String>,
+
+    /// Set a transform parameter, e.g. `stretch.center_hue=0.55` (may be
+    /// given multiple times).
+    #[arg(short, long = "set", value_name = "SLUG.PARAM=VALUE")]
+    set: Vec<String>,
+
+    /// Apply the transforms through a 3D lookup table: `full` for an exact
+    /// 256³ table, a node count like `33` or `65` for an interpolated
+    /// (tetrahedral) table, `65-trilinear` for trilinear interpolation, or
+    /// `direct` to compute every pixel. A full table is built once per
+    /// transform and pays off when processing many images.
+    #[arg(long, value_name = "SIZE", default_value = "direct", value_parser = parse_processing)]
+    lut: Processing,
+
+    /// White balance each image before the transforms, with the light
+    /// estimated by `grey-world`, `white-patch`, `shades-of-grey` or
+    /// `grey-edge` and the Bradford adaptation to D65.
+    #[arg(long, value_name = "METHOD", value_parser = parse_estimator)]
+    white_balance: Option<IlluminantEstimator>,
+
+    /// Overwrite existing output files.
+    #[arg(short, long)]
+    force: bool,
+
+    /// List the available transforms and their parameters, then exit.
+    #[arg(long)]
+    list: bool,
+
+    /// Instead of processing images, write each selected transform as a
+    /// `.cube` 3D LUT named `<transform>.cube`, for use in other software.
+    #[arg(long)]
+    cube: bool,
+
+    /// Number of nodes per axis of the `.cube` LUTs, from 2 to 256. Other
+    /// software interpolates between the nodes, so larger LUTs are closer to
+    /// the transforms, but not all software reads more than 33.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CUBE_SIZE,
+        value_parser = parse_cube_size)]
+    cube_size: usize,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let registry = Registry::default();
+
+    if cli.list {
+        print_transforms(&registry);
+        return Ok(());
+    }
+
+    let mut transforms = create_transforms(&registry, &cli.transforms)?;
+    for setting in cli.set.iter() {
+        apply_setting(&mut transforms, setting)?;
+    }
+
+    if cli.cube {
+        return write_cubes(&cli, &transforms);
+    }
+
+    let mut files = Vec::new();
+    for input in cli.inputs.iter() {
+        if input.is_dir() {
+            let mut found = Vec::new();
+            collect_images(input, cli.recursive, &mut found)?;
+            // Do not process our own output from a previous run.
+            let (inputs, outputs) = split_outputs(found, &registry);
+            for output in outputs.iter() {
+                println!("skipping {}, an output of a previous run", output.display());
+            }
+            files.extend(inputs);
+        } else {
+            files.push(input.clone());
+        }
+    }
+    if files.is_empty() {
+        bail!("no input images found");
+    }
+
+    if let Some(output_dir) = &cli.output_dir {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("creating {}", output_dir.display()))?;
+    }
+
+    let mut lut_cache = LutCache::default();
+    let mut n_errors = 0;
+    for (i, file) in files.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, files.len(), file.display());
+        if let Err(e) = process_file(file, &cli, &transforms, &mut lut_cache) {
+            eprintln!("  error: {e:#}");
+            n_errors += 1;
+        }
+    }
+    if n_errors > 0 {
+        bail!("{} of {} images failed", n_errors, files.len());
+    }
+    Ok(())
+}
+
+fn write_cubes(cli: &Cli, transforms: &[Box<dyn ColorTransform>]) -> Result<()> {
+    let output_dir = cli.output_dir.clone().unwrap_or_default();
+    if !output_dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(&output_dir)
+            .with_context(|| format!("creating {}", output_dir.display()))?;
+    }
+    for transform in transforms.iter() {
+        let out_path = output_dir.join(format!("{}.cube", transform.slug()));
+        if out_path.exists() && !cli.force {
+            bail!("{} exists (use --force to overwrite)", out_path.display());
+        }
+        std::fs::write(
+            &out_path,
+            transform_to_cube(transform.as_ref(), cli.cube_size),
+        )
+        .with_context(|| format!("writing {}", out_path.display()))?;
+        println!("{} -> {}", transform.name(), out_path.display());
+    }
+    Ok(())
+}
+
+fn print_transforms(registry: &Registry) {
+    for kind in registry.kinds() {
+        println!("{}: {}", kind.slug, kind.name);
+        for p in (kind.create)().parameters() {
+            println!(
+                "    {}.{} = {} ({}, range {} to {})",
+                kind.slug, p.slug, p.value, p.name, p.min, p.max
+            );
+        }
+    }
+}
+
+fn create_transforms(
+    registry: &Registry,
+    slugs: &[String],
+) -> Result<Vec<Box<dyn ColorTransform>>> {
+    if slugs.is_empty() {
+        return Ok(registry.kinds().iter().map(|k| (k.create)()).collect());
+    }
+    slugs
+        .iter()
+        .map(|slug| {
+            registry
+                .create(slug)
+                .with_context(|| format!("unknown transform \"{slug}\" (see --list)"))
+        })
+        .collect()
+}
+
+/// Parse a `slug.param=value` setting and apply it.
+fn apply_setting(transforms: &mut [Box<dyn ColorTransform>], setting: &str) -> Result<()> {
+    let (key, value) = setting
+        .split_once('=')
+        .with_context(|| format!("expected SLUG.PARAM=VALUE, got \"{setting}\""))?;
+    let (slug, param) = key
+        .split_once('.')
+        .with_context(|| format!("expected SLUG.PARAM=VALUE, got \"{setting}\""))?;
+    let value: f32 = value
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid value in \"{setting}\""))?;
+
+    let mut found = false;
+    for transform in transforms.iter_mut().filter(|t| t.slug() == slug) {
+        if !transform.parameters().iter().any(|p| p.slug == param) {
+            bail!("transform \"{slug}\" has no parameter \"{param}\" (see --list)");
+        }
+        transform.set_parameter(param, value);
+        found = true;
+    }
+    if !found {
+        bail!("transform \"{slug}\" is not selected");
+    }
+    Ok(())
+}
+
+fn parse_processing(value: &str) -> Result<Processing> {
+    let (size, interpolation) = match value {
+        "direct" => return Ok(Processing::Direct),
+        "full" => return Ok(Processing::Full),
+        _ => match value.strip_suffix("-trilinear") {
+            Some(size) => (size, Interpolation::Trilinear),
+            None => (value, Interpolation::Tetrahedral),
+        },
+    };
+    let size: u16 = size
+        .parse()
+        .ok()
+        .filter(|s| (2..=256).contains(s))
+        .with_context(|| {
+            format!("expected direct, full or a size from 2 to 256, got \"{value}\"")
+        })?;
+    Ok(Processing::Grid {
+        size,
+        interpolation,
+    })
+}
+
+fn parse_cube_size(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .ok()
+        .filter(|s| (2..=MAX_CUBE_SIZE).contains(s))
+        .with_context(|| format!("expected a size from 2 to {MAX_CUBE_SIZE}, got \"{value}\""))
+}
+
+fn parse_estimator(value: &str) -> Result<IlluminantEstimator> {
+    IlluminantEstimator::from_slug(value).with_context(|| {
+        let slugs: Vec<&str> = IlluminantEstimator::ALL.iter().map(|e| e.slug()).collect();
+        format!("expected one of {}, got \"{value}\"", slugs.join(", "))
+    })
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Split the images found in a directory into inputs and the outputs of our
+/// transforms from a previous run on the other images, e.g.
+/// `plate-rotated.png` next to `plate.jpg`, returned second.
+fn split_outputs(found: Vec<PathBuf>, registry: &Registry) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let outputs: HashSet<PathBuf> = found
+        .iter()
+        .flat_map(|input| {
+            let fname = input.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            registry.kinds().iter().map(move |k| {
+                input.with_file_name(format!("{}.png", output_basename(fname, k.slug)))
+            })
+        })
+        .collect();
+    found.into_iter().partition(|f| !outputs.contains(f))
+}
+
+fn collect_images(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("reading directory {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                collect_images(&path, recursive, files)?;
+            }
+        } else if is_image(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn process_file(
+    file: &Path,
+    cli: &Cli,
+    transforms: &[Box<dyn ColorTransform>],
+    lut_cache: &mut LutCache,
+) -> Result<()> {
+    let decoded = image::open(file)
+        .with_context(|| format!("decoding {}", file.display()))?
+        .to_rgba8();
+    let (width, height) = decoded.dimensions();
+    let mut orig = RgbaImage::from_raw(width, height, decoded.into_raw())?;
+    if let Some(estimator) = cli.white_balance {
+        let light = estimate_illuminant(&orig, estimator)
+            .with_context(|| format!("cannot estimate the light with {}", estimator.slug()))?;
+        let wb = WhiteBalance::new(light, Adaptation::Bradford)
+            .context("cannot white balance with the estimated light")?;
+        wb.apply(orig.as_raw_mut());
+        let (x, y) = xyz_to_xy(linear_to_xyz(light));
+        println!(
+            "  light ({}): x {x:.4}, y {y:.4}, about {:.0} K",
+            estimator.name(),
+            correlated_color_temperature((x, y))
+        );
+    }
+
+    let output_dir = match &cli.output_dir {
+        Some(dir) => dir.clone(),
+        None => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let fname = file
+        .file_name()
+        .and_then(|f| f.to_str())
+        .with_context(|| format!("invalid file name {}", file.display()))?;
+
+    for transform in transforms.iter() {
+        let out_path = output_dir.join(format!("{}.png", output_basename(fname, transform.slug())));
+        if out_path.exists() && !cli.force {
+            bail!("{} exists (use --force to overwrite)", out_path.display());
+        }
+        let mut result = orig.as_raw().to_vec();
+        lut_cache.apply(transform.as_ref(), cli.lut, &mut result);
+        image::RgbaImage::from_raw(width, height, result)
+            .expect("buffer size matches")
+            .save(&out_path)
+            .with_context(|| format!("writing {}", out_path.display()))?;
+        println!("  {} -> {}", transform.name(), out_path.display());
+    }
+    Ok(())
+}
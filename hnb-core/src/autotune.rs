@@ -0,0 +1,180 @@
+//! Tuning the Color Stretch center from the colors of an image alone.
+//!
+//! Without regions of known result (see [crate::optimize]), the hues of the
+//! image are grouped by k-means on the hue circle, with each pixel weighted
+//! by its chroma so that gray background hardly counts. The stretch center
+//! is then placed to push the two largest groups as far apart as possible.
+
+use crate::color_transform::{ColorStretch, HslPipeline};
+use crate::image::BYTES_PER_PIXEL;
+use crate::transform_colors::{rgb_to_hsl, srgb_decode_table, stretch_center, stretch_hue};
+
+/// Number of bins of the hue histogram clustered by [hue_clusters].
+const HUE_BINS: usize = 360;
+
+/// Number of clusters searched by [auto_stretch]. One more than the two used
+/// leaves room for a colored background or label.
+const AUTO_CLUSTERS: usize = 3;
+
+/// Limit of the number of k-means iterations.
+const MAX_KMEANS_ITERATIONS: usize = 100;
+
+/// A group of similar hues.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HueCluster {
+    /// The mean hue, as a fraction of a full turn like
+    /// [ColorStretch::center_hue].
+    pub hue: f32,
+    /// The share of the total chroma of the pixels, from 0 to 1.
+    pub weight: f32,
+}
+
+/// Distance of two angles in degrees, from 0 to 180.
+fn angle_distance(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+/// The histogram of the HSL hues of the RGBA `pixels`, in 1 degree bins,
+/// weighted by chroma. The hues are computed like those of Color Stretch
+/// with `pipeline`.
+fn hue_histogram(pixels: &[u8], pipeline: HslPipeline) -> Vec<f32> {
+    let decode = match pipeline {
+        HslPipeline::Legacy | HslPipeline::SrgbEncoded => {
+            let mut table = [0.0; 256];
+            for (i, v) in table.iter_mut().enumerate() {
+                *v = i as f32 / 255.0;
+            }
+            table
+        }
+        HslPipeline::SrgbLinearized => srgb_decode_table(),
+    };
+    let mut histogram = vec![0.0; HUE_BINS];
+    for pix in pixels.chunks_exact(BYTES_PER_PIXEL) {
+        let [r, g, b] = [0, 1, 2].map(|i| decode[pix[i] as usize]);
+        let chroma = r.max(g).max(b) - r.min(g).min(b);
+        if chroma > 0.0 {
+            let (h, _, _) = rgb_to_hsl(r, g, b);
+            histogram[(h as usize).min(HUE_BINS - 1)] += chroma;
+        }
+    }
+    histogram
+}
+
+/// Group the hues of the RGBA `pixels` into at most `k` clusters, largest
+/// first. Empty for images without any color.
+pub fn hue_clusters(pixels: &[u8], k: usize, pipeline: HslPipeline) -> Vec<HueCluster> {
+    let histogram = hue_histogram(pixels, pipeline);
+    let total: f32 = histogram.iter().sum();
+    if total <= 0.0 || k == 0 {
+        return Vec::new();
+    }
+    let bin_hue = |i: usize| i as f32 + 0.5;
+
+    // Deterministic k-means++ start: the fullest bin, then repeatedly the bin
+    // with the most weight far from the centers so far.
+    let mut centers: Vec<f32> = Vec::new();
+    while centers.len() < k {
+        let (best, score) = (0..HUE_BINS)
+            .map(|i| {
+                let d = centers
+                    .iter()
+                    .map(|&c| angle_distance(c, bin_hue(i)))
+                    .fold(180.0, f32::min);
+                (i, histogram[i] * d * d)
+            })
+            .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a });
+        if score <= 0.0 {
+            break;
+        }
+        centers.push(bin_hue(best));
+    }
+
+    let mut weights = vec![0.0; centers.len()];
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        let mut sums = vec![(0.0f32, 0.0f32); centers.len()];
+        weights.iter_mut().for_each(|w| *w = 0.0);
+        for (i, &w) in histogram.iter().enumerate() {
+            if w <= 0.0 {
+                continue;
+            }
+            let h = bin_hue(i);
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| {
+                    angle_distance(centers[a], h).total_cmp(&angle_distance(centers[b], h))
+                })
+                .unwrap();
+            let (sin, cos) = h.to_radians().sin_cos();
+            sums[nearest].0 += w * sin;
+            sums[nearest].1 += w * cos;
+            weights[nearest] += w;
+        }
+        let new_centers: Vec<f32> = sums
+            .iter()
+            .zip(centers.iter())
+            .map(|(&(sin, cos), &old)| {
+                if sin == 0.0 && cos == 0.0 {
+                    old
+                } else {
+                    sin.atan2(cos).to_degrees().rem_euclid(360.0)
+                }
+            })
+            .collect();
+        let converged = new_centers
+            .iter()
+            .zip(centers.iter())
+            .all(|(&a, &b)| angle_distance(a, b) < 0.01);
+        centers = new_centers;
+        if converged {
+            break;
+        }
+    }
+
+    let mut clusters: Vec<HueCluster> = centers
+        .iter()
+        .zip(weights.iter())
+        .filter(|(_, &w)| w > 0.0)
+        .map(|(&hue, &w)| HueCluster {
+            hue: hue / 360.0,
+            weight: w / total,
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    clusters
+}
+
+/// The result of [auto_stretch].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutoStretch {
+    pub transform: ColorStretch,
+    /// The hue clusters of the image, largest first. The center is placed for
+    /// the first two.
+    pub clusters: Vec<HueCluster>,
+}
+
+/// Place the Color Stretch center so that the two largest hue clusters of the
+/// RGBA `pixels` are pushed furthest apart, keeping the radius, saturation
+/// and pipeline of `start`. `None` if there are not two clusters.
+pub fn auto_stretch(pixels: &[u8], start: &ColorStretch) -> Option<AutoStretch> {
+    let clusters = hue_clusters(pixels, AUTO_CLUSTERS, start.pipeline);
+    if clusters.len() < 2 {
+        return None;
+    }
+    let (h1, h2) = (clusters[0].hue * 360.0, clusters[1].hue * 360.0);
+    let spread = |center_hue: f32| {
+        let (cx, cy) = stretch_center(center_hue, start.radius);
+        angle_distance(stretch_hue(h1, cx, cy), stretch_hue(h2, cx, cy))
+    };
+    // In steps of 0.001 turn, the precision of the parameter input.
+    let center_hue = (0..1000)
+        .map(|i| i as f32 / 1000.0)
+        .max_by(|&a, &b| spread(a).total_cmp(&spread(b)))
+        .unwrap();
+    Some(AutoStretch {
+        transform: ColorStretch {
+            center_hue,
+            ..start.clone()
+        },
+        clusters,
+    })
+}
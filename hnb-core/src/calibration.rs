@@ -0,0 +1,753 @@
+//! Color correction with a reference chart photographed next to the tubes.
+//!
+//! Phone cameras render the same dye in very different colors. With a chart
+//! of known colors in the photo, e.g. an X-Rite ColorChecker Classic or a
+//! printed card, [detect_chart] finds and measures its patches and
+//! [ColorCorrection::fit] finds the color correction matrix (CCM) mapping
+//! the measured to the known colors. Applied to the image before the color
+//! transforms, this makes photos of different devices comparable.
+//!
+//! Patches are measured and the correction is applied in linear light. The
+//! 3x3 matrix corrects the white balance and the primaries of the camera;
+//! the root-polynomial model (Finlayson et al., 2015) can follow non-linear
+//! camera responses while, like the matrix, being independent of exposure.
+
+use std::collections::HashSet;
+
+use crate::color_transform::{ColorTransform, Parameter};
+use crate::colorspace::{linear_to_lab, map_linear, srgb8_to_linear};
+use crate::image::{RgbaImage, BYTES_PER_PIXEL};
+use crate::transform_colors::srgb_decode_table;
+
+/// The sRGB (D65) colors of the X-Rite ColorChecker Classic, as published by
+/// X-Rite, row by row with the brown "dark skin" patch at the top left and
+/// the gray scale in the bottom row.
+pub const COLOR_CHECKER_CLASSIC: [[u8; 3]; 24] = [
+    [115, 82, 68],
+    [194, 150, 130],
+    [98, 122, 157],
+    [87, 108, 67],
+    [133, 128, 177],
+    [103, 189, 170],
+    [214, 126, 44],
+    [80, 91, 166],
+    [193, 90, 99],
+    [94, 60, 108],
+    [157, 188, 64],
+    [224, 163, 46],
+    [56, 61, 150],
+    [70, 148, 73],
+    [175, 54, 60],
+    [231, 199, 31],
+    [187, 86, 149],
+    [8, 133, 161],
+    [243, 243, 242],
+    [200, 200, 200],
+    [160, 160, 160],
+    [122, 122, 121],
+    [85, 85, 85],
+    [52, 52, 52],
+];
+
+/// An error in the description of a reference chart. Line numbers start at
+/// 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChartError {
+    InvalidLine {
+        line: usize,
+        message: String,
+    },
+    /// There is no line with the number of rows and columns.
+    MissingSize,
+    WrongPatchCount {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for ChartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChartError::InvalidLine { line, message } => write!(f, "line {line}: {message}"),
+            ChartError::MissingSize => write!(f, "missing line with the rows and columns"),
+            ChartError::WrongPatchCount { expected, actual } => {
+                write!(f, "expected {expected} patch colors but found {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChartError {}
+
+/// A chart of colored patches in a regular grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartLayout {
+    pub name: String,
+    pub rows: usize,
+    pub cols: usize,
+    /// The sRGB colors of the patches, row by row.
+    pub reference: Vec<[u8; 3]>,
+}
+
+impl ChartLayout {
+    pub fn color_checker_classic() -> Self {
+        Self {
+            name: "ColorChecker Classic".into(),
+            rows: 4,
+            cols: 6,
+            reference: COLOR_CHECKER_CLASSIC.to_vec(),
+        }
+    }
+
+    /// Read a chart description: a line with the number of rows and
+    /// columns, then one line per patch, row by row, with its sRGB color as
+    /// three numbers from 0 to 255. Numbers are separated by spaces, tabs or
+    /// commas. Empty lines and lines starting with `#` are ignored.
+    pub fn parse(name: &str, text: &str) -> Result<Self, ChartError> {
+        let mut size = None;
+        let mut reference = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| ChartError::InvalidLine {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let numbers: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if size.is_none() {
+                match numbers[..] {
+                    [rows, cols] => {
+                        let parse = |s: &str| s.parse::<usize>().ok().filter(|&n| n > 0);
+                        match (parse(rows), parse(cols)) {
+                            (Some(rows), Some(cols)) => size = Some((rows, cols)),
+                            _ => return Err(invalid("expected the numbers of rows and columns")),
+                        }
+                    }
+                    _ => return Err(invalid("expected the numbers of rows and columns")),
+                }
+                continue;
+            }
+            let rgb: Vec<u8> = numbers.iter().filter_map(|s| s.parse().ok()).collect();
+            match rgb[..] {
+                [r, g, b] if numbers.len() == 3 => reference.push([r, g, b]),
+                _ => return Err(invalid("expected three values from 0 to 255")),
+            }
+        }
+        let (rows, cols) = size.ok_or(ChartError::MissingSize)?;
+        if reference.len() != rows * cols {
+            return Err(ChartError::WrongPatchCount {
+                expected: rows * cols,
+                actual: reference.len(),
+            });
+        }
+        Ok(Self {
+            name: name.to_string(),
+            rows,
+            cols,
+            reference,
+        })
+    }
+}
+
+/// The patches of a chart found in an image, in image pixel coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartDetection {
+    /// The centers of the top left, top right, bottom right and bottom left
+    /// patches, in the orientation of the layout.
+    pub corners: [(f32, f32); 4],
+    /// The centers of the patches, in the order of the layout.
+    pub centers: Vec<(f32, f32)>,
+    /// Side of the squares measured around the centers.
+    pub patch_size: f32,
+    /// The mean linear sRGB color of each patch.
+    pub measured: Vec<[f32; 3]>,
+}
+
+impl ChartDetection {
+    /// Measure the patches of a chart whose corner patches the user marked,
+    /// see [ChartDetection::corners].
+    pub fn from_corners(image: &RgbaImage, layout: &ChartLayout, corners: [(f32, f32); 4]) -> Self {
+        let [tl, tr, br, bl] = corners;
+        let lerp =
+            |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        let fraction = |i: usize, n: usize| {
+            if n > 1 {
+                i as f32 / (n - 1) as f32
+            } else {
+                0.5
+            }
+        };
+        let mut centers = Vec::with_capacity(layout.rows * layout.cols);
+        for r in 0..layout.rows {
+            let v = fraction(r, layout.rows);
+            let (left, right) = (lerp(tl, bl, v), lerp(tr, br, v));
+            for c in 0..layout.cols {
+                centers.push(lerp(left, right, fraction(c, layout.cols)));
+            }
+        }
+        let distance = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).hypot(a.1 - b.1);
+        let spacing = |a, b, n: usize| distance(a, b) / (n.max(2) - 1) as f32;
+        let mut pitch = f32::INFINITY;
+        if layout.cols > 1 {
+            pitch = pitch.min(spacing(tl, tr, layout.cols).min(spacing(bl, br, layout.cols)));
+        }
+        if layout.rows > 1 {
+            pitch = pitch.min(spacing(tl, bl, layout.rows).min(spacing(tr, br, layout.rows)));
+        }
+        let patch_size = if pitch.is_finite() {
+            PATCH_FRACTION * pitch
+        } else {
+            8.0
+        };
+        let measured = centers
+            .iter()
+            .map(|&c| measure_square(image, c, patch_size))
+            .collect();
+        Self {
+            corners,
+            centers,
+            patch_size,
+            measured,
+        }
+    }
+}
+
+/// The part of the distance between patch centers which is measured, leaving
+/// out the edges of the patches.
+const PATCH_FRACTION: f32 = 0.5;
+
+/// The larger side of the reduced image in which charts are searched.
+const WORKING_SIZE: u32 = 640;
+
+/// Neighboring pixels of a patch differ by at most this in each channel.
+const MAX_STEP: i32 = 8;
+
+/// Pixels of a patch differ from its first pixel by at most this.
+const MAX_SPREAD: i32 = 28;
+
+/// Smallest area of a patch in the reduced image, in pixels.
+const MIN_PATCH_AREA: usize = 9;
+
+/// The mean linear sRGB color of the pixels in a square of side `size`
+/// around `center`.
+fn measure_square(image: &RgbaImage, center: (f32, f32), size: f32) -> [f32; 3] {
+    let decode = srgb_decode_table();
+    let half = (size / 2.0).max(0.5);
+    let (w, h) = (image.width() as f32, image.height() as f32);
+    let x0 = (center.0 - half).floor().clamp(0.0, w - 1.0) as u32;
+    let x1 = (center.0 + half).ceil().clamp(1.0, w) as u32;
+    let y0 = (center.1 - half).floor().clamp(0.0, h - 1.0) as u32;
+    let y1 = (center.1 + half).ceil().clamp(1.0, h) as u32;
+    let mut sum = [0.0f64; 3];
+    let mut n = 0;
+    for y in y0..y1.max(y0 + 1) {
+        for x in x0..x1.max(x0 + 1) {
+            let p = image.pixel(x, y);
+            for c in 0..3 {
+                sum[c] += decode[p[c] as usize] as f64;
+            }
+            n += 1;
+        }
+    }
+    sum.map(|s| (s / n as f64) as f32)
+}
+
+/// A region of nearly uniform color which may be a patch, in reduced image
+/// coordinates.
+struct Candidate {
+    center: (f32, f32),
+    area: usize,
+}
+
+/// Regions of nearly uniform color which are about square.
+fn find_candidates(reduced: &RgbaImage) -> Vec<Candidate> {
+    let (width, height) = (reduced.width() as usize, reduced.height() as usize);
+    let data = reduced.as_raw();
+    let color = |i: usize| {
+        let p = &data[i * BYTES_PER_PIXEL..i * BYTES_PER_PIXEL + 3];
+        [p[0] as i32, p[1] as i32, p[2] as i32]
+    };
+    let differ = |a: [i32; 3], b: [i32; 3], limit: i32| (0..3).any(|c| (a[c] - b[c]).abs() > limit);
+    let max_area = width * height / 20;
+
+    let mut seen = vec![false; width * height];
+    let mut result = Vec::new();
+    for start in 0..width * height {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let seed = color(start);
+        let mut stack = vec![start];
+        let mut pixels = Vec::new();
+        while let Some(i) = stack.pop() {
+            pixels.push(i);
+            let (x, y) = (i % width, i / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for j in neighbors.into_iter().flatten() {
+                if !seen[j]
+                    && !differ(color(i), color(j), MAX_STEP)
+                    && !differ(seed, color(j), MAX_SPREAD)
+                {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        if pixels.len() < MIN_PATCH_AREA || pixels.len() > max_area {
+            continue;
+        }
+        // A filled square has the variance side² / 12 along any axis.
+        let n = pixels.len() as f32;
+        let coords = || {
+            pixels
+                .iter()
+                .map(|&i| ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5))
+        };
+        let (mx, my) = coords().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+        let (mut sxx, mut syy, mut sxy) = (1.0 / 12.0, 1.0 / 12.0, 0.0);
+        for (x, y) in coords() {
+            sxx += (x - mx).powi(2) / n;
+            syy += (y - my).powi(2) / n;
+            sxy += (x - mx) * (y - my) / n;
+        }
+        let mean = (sxx + syy) / 2.0;
+        let d = ((sxx - syy).powi(2) / 4.0 + sxy * sxy).sqrt();
+        let (l1, l2) = (mean + d, mean - d);
+        let squareness = n / (12.0 * (l1 * l2).max(f32::EPSILON).sqrt());
+        if l1 / l2.max(f32::EPSILON) < 2.0 && (0.75..1.3).contains(&squareness) {
+            result.push(Candidate {
+                center: (mx, my),
+                area: pixels.len(),
+            });
+        }
+    }
+    result
+}
+
+/// A lattice `origin + i * a + j * b` in reduced image coordinates.
+struct Lattice {
+    origin: (f32, f32),
+    a: (f32, f32),
+    b: (f32, f32),
+}
+
+impl Lattice {
+    fn at(&self, i: f32, j: f32) -> (f32, f32) {
+        (
+            self.origin.0 + i * self.a.0 + j * self.b.0,
+            self.origin.1 + i * self.a.1 + j * self.b.1,
+        )
+    }
+}
+
+/// Find a `w` x `h` window of a lattice of patches among the candidates.
+/// Returns the lattice, with the origin at the first cell of the window, and
+/// the number of patches found in the window.
+fn find_grid(
+    candidates: &[Candidate],
+    dims: &[(usize, usize)],
+) -> Option<(Lattice, (usize, usize), usize)> {
+    // Patches have about the same size.
+    let similar = |a: usize, b: usize| {
+        let ratio = a as f32 / b as f32;
+        (0.6..1.67).contains(&ratio)
+    };
+    let seed = candidates.iter().max_by_key(|c| {
+        candidates
+            .iter()
+            .filter(|d| similar(c.area, d.area))
+            .count()
+    })?;
+    let patches: Vec<(f32, f32)> = candidates
+        .iter()
+        .filter(|c| similar(seed.area, c.area))
+        .map(|c| c.center)
+        .collect();
+    if patches.len() < 4 {
+        return None;
+    }
+
+    // The direction and spacing of the grid from the nearest neighbors.
+    let mut distances = Vec::new();
+    let (mut sin4, mut cos4) = (0.0, 0.0);
+    for (i, p) in patches.iter().enumerate() {
+        let nearest = patches
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, q)| (q.0 - p.0, q.1 - p.1))
+            .min_by(|a, b| a.0.hypot(a.1).total_cmp(&b.0.hypot(b.1)))?;
+        distances.push(nearest.0.hypot(nearest.1));
+        let angle = 4.0 * nearest.1.atan2(nearest.0);
+        sin4 += angle.sin();
+        cos4 += angle.cos();
+    }
+    distances.sort_by(f32::total_cmp);
+    let spacing = distances[distances.len() / 2];
+    let theta = f32::atan2(sin4, cos4) / 4.0;
+    let (u, v) = ((theta.cos(), theta.sin()), (-theta.sin(), theta.cos()));
+
+    // Grid cells of the patches on the lattice of the anchor with the most.
+    let cells_of = |anchor: (f32, f32)| {
+        let mut cells = Vec::new();
+        for &p in patches.iter() {
+            let (dx, dy) = (p.0 - anchor.0, p.1 - anchor.1);
+            let (i, j) = (
+                (dx * u.0 + dy * u.1) / spacing,
+                (dx * v.0 + dy * v.1) / spacing,
+            );
+            let (ri, rj) = (i.round(), j.round());
+            if (i - ri).abs() < 0.3 && (j - rj).abs() < 0.3 {
+                cells.push(((ri as i32, rj as i32), p));
+            }
+        }
+        cells
+    };
+    let cells = patches
+        .iter()
+        .map(|&a| cells_of(a))
+        .max_by_key(|c| c.len())?;
+    let occupied: HashSet<(i32, i32)> = cells.iter().map(|c| c.0).collect();
+
+    // The window of the chart's size with the most patches.
+    let (min_i, max_i) = occupied.iter().fold((i32::MAX, i32::MIN), |(lo, hi), c| {
+        (lo.min(c.0), hi.max(c.0))
+    });
+    let (min_j, max_j) = occupied.iter().fold((i32::MAX, i32::MIN), |(lo, hi), c| {
+        (lo.min(c.1), hi.max(c.1))
+    });
+    let mut best = None;
+    let mut count = 0;
+    for &(w, h) in dims {
+        for i0 in min_i - w as i32 + 1..=max_i {
+            for j0 in min_j - h as i32 + 1..=max_j {
+                let n = occupied
+                    .iter()
+                    .filter(|c| {
+                        (i0..i0 + w as i32).contains(&c.0) && (j0..j0 + h as i32).contains(&c.1)
+                    })
+                    .count();
+                if n > count {
+                    best = Some(((i0, j0), (w, h)));
+                    count = n;
+                }
+            }
+        }
+    }
+    let ((i0, j0), (w, h)) = best?;
+
+    // Fit the lattice to the patches in the window by least squares.
+    let inside: Vec<((f64, f64), (f32, f32))> = cells
+        .iter()
+        .filter(|c| (i0..i0 + w as i32).contains(&c.0 .0) && (j0..j0 + h as i32).contains(&c.0 .1))
+        .map(|c| (((c.0 .0 - i0) as f64, (c.0 .1 - j0) as f64), c.1))
+        .collect();
+    let mut ata = vec![vec![0.0; 3]; 3];
+    let (mut atx, mut aty) = (vec![0.0; 3], vec![0.0; 3]);
+    for &((i, j), p) in inside.iter() {
+        let row = [1.0, i, j];
+        for r in 0..3 {
+            for c in 0..3 {
+                ata[r][c] += row[r] * row[c];
+            }
+            atx[r] += row[r] * p.0 as f64;
+            aty[r] += row[r] * p.1 as f64;
+        }
+    }
+    // Patches on one line do not determine the other direction.
+    let fitted = solve(ata.clone(), atx).zip(solve(ata, aty));
+    let lattice = match fitted {
+        Some((x, y)) => Lattice {
+            origin: (x[0] as f32, y[0] as f32),
+            a: (x[1] as f32, y[1] as f32),
+            b: (x[2] as f32, y[2] as f32),
+        },
+        None => {
+            let anchor = cells[0].1;
+            let (ci, cj) = ((cells[0].0 .0 - i0) as f32, (cells[0].0 .1 - j0) as f32);
+            let (a, b) = (
+                (u.0 * spacing, u.1 * spacing),
+                (v.0 * spacing, v.1 * spacing),
+            );
+            Lattice {
+                origin: (
+                    anchor.0 - ci * a.0 - cj * b.0,
+                    anchor.1 - ci * a.1 - cj * b.1,
+                ),
+                a,
+                b,
+            }
+        }
+    };
+    Some((lattice, (w, h), count))
+}
+
+/// Find the chart with `layout` in the image and measure its patches.
+///
+/// The patches are found as regions of uniform color of about the same
+/// size on a regular grid, which may be rotated. Patches which are not found,
+/// e.g. because they have the color of the chart's border, are measured at
+/// their place in the grid. The orientation of the chart is the one for
+/// which the patches best fit the reference colors. Returns `None` if less
+/// than half the patches are found.
+pub fn detect_chart(image: &RgbaImage, layout: &ChartLayout) -> Option<ChartDetection> {
+    let n_patches = layout.rows * layout.cols;
+    if n_patches < 4 || image.width() == 0 || image.height() == 0 {
+        return None;
+    }
+    let (reduced, factor) = image.block_average(WORKING_SIZE);
+    let candidates = find_candidates(&reduced);
+    let mut dims = vec![(layout.cols, layout.rows)];
+    if layout.rows != layout.cols {
+        dims.push((layout.rows, layout.cols));
+    }
+    let (lattice, (w, h), count) = find_grid(&candidates, &dims)?;
+    if count < (n_patches / 2).max(4) {
+        return None;
+    }
+
+    // Full resolution centers of the cells of the window.
+    let scale = factor as f32;
+    let cell_center = |p: usize, q: usize| {
+        let (x, y) = lattice.at(p as f32, q as f32);
+        (x * scale, y * scale)
+    };
+    let pitch = (lattice.a.0.hypot(lattice.a.1)).min(lattice.b.0.hypot(lattice.b.1)) * scale;
+    let patch_size = PATCH_FRACTION * pitch;
+    let mut measured_cells = vec![[0.0; 3]; w * h];
+    for q in 0..h {
+        for p in 0..w {
+            measured_cells[q * w + p] = measure_square(image, cell_center(p, q), patch_size);
+        }
+    }
+
+    // Try the orientations: a transposed grid if the window has the rows and
+    // columns swapped, and flips along either axis.
+    let transposed = (w, h) != (layout.cols, layout.rows);
+    let mut best: Option<(f32, Vec<(usize, usize)>)> = None;
+    for flip_rows in [false, true] {
+        for flip_cols in [false, true] {
+            let cells: Vec<(usize, usize)> = (0..layout.rows)
+                .flat_map(|r| (0..layout.cols).map(move |c| (r, c)))
+                .map(|(r, c)| {
+                    let r = if flip_rows { layout.rows - 1 - r } else { r };
+                    let c = if flip_cols { layout.cols - 1 - c } else { c };
+                    if transposed {
+                        (r, c)
+                    } else {
+                        (c, r)
+                    }
+                })
+                .collect();
+            let measured: Vec<[f32; 3]> = cells
+                .iter()
+                .map(|&(p, q)| measured_cells[q * w + p])
+                .collect();
+            let error = match ColorCorrection::fit(&measured, &layout.reference, CcmModel::Linear) {
+                Some(ccm) => {
+                    let corrected: Vec<[f32; 3]> =
+                        measured.iter().map(|&m| ccm.correct(m)).collect();
+                    delta_e(&corrected, &layout.reference).0
+                }
+                None => f32::INFINITY,
+            };
+            if best.as_ref().is_none_or(|b| error < b.0) {
+                best = Some((error, cells));
+            }
+        }
+    }
+    let (_, cells) = best?;
+    let centers: Vec<(f32, f32)> = cells.iter().map(|&(p, q)| cell_center(p, q)).collect();
+    let measured = cells
+        .iter()
+        .map(|&(p, q)| measured_cells[q * w + p])
+        .collect();
+    let corner = |r: usize, c: usize| centers[r * layout.cols + c];
+    Some(ChartDetection {
+        corners: [
+            corner(0, 0),
+            corner(0, layout.cols - 1),
+            corner(layout.rows - 1, layout.cols - 1),
+            corner(layout.rows - 1, 0),
+        ],
+        centers,
+        patch_size,
+        measured,
+    })
+}
+
+/// Mean and maximum CIELAB color difference ΔE*ab between linear sRGB
+/// colors and the corresponding 8 bit sRGB reference colors.
+pub fn delta_e(linear: &[[f32; 3]], reference: &[[u8; 3]]) -> (f32, f32) {
+    let (mut sum, mut max) = (0.0, 0.0f32);
+    for (&rgb, &r) in linear.iter().zip(reference.iter()) {
+        let (a, b) = (linear_to_lab(rgb), linear_to_lab(srgb8_to_linear(r)));
+        let d = ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt();
+        sum += d;
+        max = max.max(d);
+    }
+    (sum / linear.len().max(1) as f32, max)
+}
+
+/// The model of a [ColorCorrection].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CcmModel {
+    /// A 3x3 matrix.
+    #[default]
+    Linear,
+    /// A 3x6 matrix of the colors and the square roots of their pairwise
+    /// products.
+    RootPolynomial,
+}
+
+impl CcmModel {
+    pub const ALL: [CcmModel; 2] = [CcmModel::Linear, CcmModel::RootPolynomial];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CcmModel::Linear => "3x3 matrix",
+            CcmModel::RootPolynomial => "Root-polynomial",
+        }
+    }
+
+    fn n_terms(&self) -> usize {
+        match self {
+            CcmModel::Linear => 3,
+            CcmModel::RootPolynomial => 6,
+        }
+    }
+
+    /// The terms of the model for a linear sRGB color.
+    #[inline]
+    fn terms(&self, [r, g, b]: [f32; 3]) -> [f32; 6] {
+        match self {
+            CcmModel::Linear => [r, g, b, 0.0, 0.0, 0.0],
+            CcmModel::RootPolynomial => {
+                let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+                [r, g, b, (r * g).sqrt(), (g * b).sqrt(), (r * b).sqrt()]
+            }
+        }
+    }
+}
+
+/// A color correction matrix, mapping the colors of a camera to sRGB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorCorrection {
+    pub model: CcmModel,
+    /// One row per output channel (linear R, G and B), with one column per
+    /// term of the model.
+    pub coefficients: [[f32; 6]; 3],
+}
+
+impl ColorCorrection {
+    /// The least squares fit, in linear light, of the `measured` linear sRGB
+    /// colors of the patches to their sRGB `reference` colors. `None` if
+    /// there are too few patches for the model or they do not determine it,
+    /// e.g. if all are gray.
+    pub fn fit(measured: &[[f32; 3]], reference: &[[u8; 3]], model: CcmModel) -> Option<Self> {
+        let n = model.n_terms();
+        if measured.len() != reference.len() || measured.len() < n {
+            return None;
+        }
+        let mut ata = vec![vec![0.0f64; n]; n];
+        let mut atb = vec![vec![0.0f64; n]; 3];
+        for (&m, &r) in measured.iter().zip(reference.iter()) {
+            let terms = model.terms(m);
+            let target = srgb8_to_linear(r);
+            for i in 0..n {
+                for j in 0..n {
+                    ata[i][j] += terms[i] as f64 * terms[j] as f64;
+                }
+                for c in 0..3 {
+                    atb[c][i] += terms[i] as f64 * target[c] as f64;
+                }
+            }
+        }
+        // A little regularization for nearly dependent terms.
+        let ridge = 1e-6 * (0..n).map(|i| ata[i][i]).sum::<f64>() / n as f64;
+        for (i, row) in ata.iter_mut().enumerate() {
+            row[i] += ridge;
+        }
+        let mut coefficients = [[0.0; 6]; 3];
+        for c in 0..3 {
+            let x = solve(ata.clone(), atb[c].clone())?;
+            for i in 0..n {
+                coefficients[c][i] = x[i] as f32;
+            }
+        }
+        Some(Self {
+            model,
+            coefficients,
+        })
+    }
+
+    /// Correct a linear sRGB color. The result may be outside the gamut.
+    #[inline]
+    pub fn correct(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let terms = self.model.terms(rgb);
+        self.coefficients
+            .map(|row| row.iter().zip(terms.iter()).map(|(a, t)| a * t).sum())
+    }
+}
+
+impl ColorTransform for ColorCorrection {
+    fn name(&self) -> &str {
+        "Color corrected"
+    }
+    fn slug(&self) -> &str {
+        "ccm"
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        Vec::new()
+    }
+    fn set_parameter(&mut self, _slug: &str, _value: f32) {}
+    fn apply(&self, data: &mut [u8]) {
+        map_linear(data, |rgb| self.correct(rgb));
+    }
+    fn summary(&self) -> String {
+        self.model.name().to_string()
+    }
+    fn cache_key(&self) -> String {
+        format!("{}:{:?}", self.slug(), self.coefficients)
+    }
+}
+
+/// Solve the linear equations `a x = b` by Gaussian elimination with partial
+/// pivoting. `None` if `a` is singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let scale = a.iter().flatten().fold(0.0f64, |m, v| m.max(v.abs()));
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() <= 1e-12 * scale.max(f64::MIN_POSITIVE) {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let f = a[row][col] / a[col][col];
+            let (upper, lower) = a.split_at_mut(row);
+            for (x, pivot_x) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *x -= f * pivot_x;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
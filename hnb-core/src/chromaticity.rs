@@ -0,0 +1,223 @@
+//! Chromaticity diagrams of the colors of the regions, CIE 1931 xy and
+//! CIE 1976 u'v', with the spectral locus and the sRGB gamut.
+//!
+//! Chromaticities are kept as CIE 1931 xy and converted for the diagram
+//! shown. Distances in u'v' are closer to perceived differences than in xy.
+
+use crate::classify::ControlReference;
+use crate::colorspace::{
+    lab_to_xyz, linear_to_srgb8, linear_to_xyz, srgb8_to_linear, xyz_to_linear, xyz_to_xy, D65_XYZ,
+};
+use crate::image::BYTES_PER_PIXEL;
+use crate::white_balance::mean_linear;
+
+/// The CIE 1931 2° chromaticities of the monochromatic lights, by wavelength
+/// in nm.
+pub const SPECTRAL_LOCUS: [(u16, f32, f32); 45] = [
+    (380, 0.1741, 0.0050),
+    (390, 0.1738, 0.0049),
+    (400, 0.1733, 0.0048),
+    (410, 0.1726, 0.0048),
+    (420, 0.1714, 0.0051),
+    (430, 0.1689, 0.0069),
+    (440, 0.1644, 0.0109),
+    (450, 0.1566, 0.0177),
+    (460, 0.1440, 0.0297),
+    (470, 0.1241, 0.0578),
+    (475, 0.1096, 0.0868),
+    (480, 0.0913, 0.1327),
+    (485, 0.0687, 0.2007),
+    (490, 0.0454, 0.2950),
+    (495, 0.0235, 0.4127),
+    (500, 0.0082, 0.5384),
+    (505, 0.0039, 0.6548),
+    (510, 0.0139, 0.7502),
+    (515, 0.0389, 0.8120),
+    (520, 0.0743, 0.8338),
+    (525, 0.1142, 0.8262),
+    (530, 0.1547, 0.8059),
+    (535, 0.1929, 0.7816),
+    (540, 0.2296, 0.7543),
+    (545, 0.2658, 0.7243),
+    (550, 0.3016, 0.6923),
+    (555, 0.3373, 0.6589),
+    (560, 0.3731, 0.6245),
+    (565, 0.4087, 0.5896),
+    (570, 0.4441, 0.5547),
+    (575, 0.4788, 0.5202),
+    (580, 0.5125, 0.4866),
+    (585, 0.5448, 0.4544),
+    (590, 0.5752, 0.4242),
+    (595, 0.6029, 0.3965),
+    (600, 0.6270, 0.3725),
+    (605, 0.6482, 0.3514),
+    (610, 0.6658, 0.3340),
+    (620, 0.6915, 0.3083),
+    (630, 0.7079, 0.2920),
+    (640, 0.7190, 0.2809),
+    (650, 0.7260, 0.2740),
+    (660, 0.7300, 0.2700),
+    (680, 0.7334, 0.2666),
+    (700, 0.7347, 0.2653),
+];
+
+/// Pixels with all channels below this value are left out of the clouds, as
+/// the chromaticity of very dark colors is mostly noise.
+const MIN_VALUE: u8 = 8;
+
+/// A chromaticity diagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Diagram {
+    /// CIE 1931 xy.
+    #[default]
+    Xy,
+    /// CIE 1976 UCS u'v'.
+    UvPrime,
+}
+
+impl Diagram {
+    pub const ALL: [Diagram; 2] = [Diagram::Xy, Diagram::UvPrime];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Diagram::Xy => "CIE 1931 xy",
+            Diagram::UvPrime => "CIE 1976 u'v'",
+        }
+    }
+
+    /// The names of the horizontal and vertical axes.
+    pub fn axes(&self) -> [&'static str; 2] {
+        match self {
+            Diagram::Xy => ["x", "y"],
+            Diagram::UvPrime => ["u'", "v'"],
+        }
+    }
+
+    /// The largest horizontal and vertical coordinates shown, from 0.
+    pub fn extent(&self) -> (f32, f32) {
+        match self {
+            Diagram::Xy => (0.8, 0.9),
+            Diagram::UvPrime => (0.65, 0.65),
+        }
+    }
+
+    /// The coordinates in the diagram of the xy chromaticity.
+    pub fn from_xy(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        match self {
+            Diagram::Xy => (x, y),
+            Diagram::UvPrime => {
+                let d = -2.0 * x + 12.0 * y + 3.0;
+                (4.0 * x / d, 9.0 * y / d)
+            }
+        }
+    }
+
+    /// The xy chromaticity at coordinates of the diagram.
+    pub fn to_xy(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        match self {
+            Diagram::Xy => (u, v),
+            Diagram::UvPrime => {
+                let d = 6.0 * u - 16.0 * v + 12.0;
+                (9.0 * u / d, 4.0 * v / d)
+            }
+        }
+    }
+
+    /// The Euclidean distance in the diagram between two xy chromaticities.
+    pub fn distance(&self, a: (f32, f32), b: (f32, f32)) -> f32 {
+        let (a, b) = (self.from_xy(a), self.from_xy(b));
+        (a.0 - b.0).hypot(a.1 - b.1)
+    }
+}
+
+/// The xy chromaticities of the sRGB red, green and blue primaries.
+pub fn srgb_gamut() -> [(f32, f32); 3] {
+    [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].map(|rgb| xyz_to_xy(linear_to_xyz(rgb)))
+}
+
+/// The xy chromaticity of the D65 white.
+pub fn white_point() -> (f32, f32) {
+    xyz_to_xy(D65_XYZ)
+}
+
+/// Whether an xy chromaticity is inside the spectral locus and the line of
+/// purples, that is the chromaticity of a real color.
+pub fn is_visible((x, y): (f32, f32)) -> bool {
+    let mut inside = false;
+    let n = SPECTRAL_LOCUS.len();
+    for i in 0..n {
+        let (_, x0, y0) = SPECTRAL_LOCUS[i];
+        let (_, x1, y1) = SPECTRAL_LOCUS[(i + 1) % n];
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// The color filling the diagram at an xy chromaticity: its linear sRGB
+/// with the negative channels clipped and the largest channel 1, so that
+/// chromaticities outside the gamut are shown less saturated.
+pub fn display_color((x, y): (f32, f32)) -> [u8; 3] {
+    if y <= 0.0 {
+        return [0, 0, 0];
+    }
+    let rgb = xyz_to_linear([x / y, 1.0, (1.0 - x - y) / y]).map(|v| v.max(0.0));
+    let max = rgb.iter().cloned().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return [0, 0, 0];
+    }
+    linear_to_srgb8(rgb.map(|v| v / max))
+}
+
+/// The chromaticities of the pixels of a region.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegionChromaticity {
+    /// The chromaticity of the mean linear color.
+    pub mean: (f32, f32),
+    /// The chromaticities of evenly spaced pixels which are not too dark.
+    pub points: Vec<(f32, f32)>,
+}
+
+impl RegionChromaticity {
+    /// The chromaticities of the RGBA `pixels`, with at most `max_points`
+    /// points. `None` without pixels.
+    pub fn of_pixels(pixels: &[u8], max_points: usize) -> Option<Self> {
+        let mean = xyz_to_xy(linear_to_xyz(mean_linear(pixels)?));
+        let n = pixels.len() / BYTES_PER_PIXEL;
+        let step = n.div_ceil(max_points.max(1)).max(1);
+        let points = pixels
+            .chunks_exact(BYTES_PER_PIXEL)
+            .step_by(step)
+            .filter(|pix| pix[..3].iter().any(|&v| v >= MIN_VALUE))
+            .map(|pix| xyz_to_xy(linear_to_xyz(srgb8_to_linear([pix[0], pix[1], pix[2]]))))
+            .collect();
+        Some(Self { mean, points })
+    }
+}
+
+/// The chromaticities of the positive and negative controls and how far
+/// apart they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControlSeparation {
+    pub positive: (f32, f32),
+    pub negative: (f32, f32),
+    /// The CIELAB distance ΔE*ab between the controls.
+    pub delta_e: f32,
+}
+
+impl ControlSeparation {
+    pub fn new(reference: &ControlReference) -> Self {
+        let xy = |lab| xyz_to_xy(lab_to_xyz(lab));
+        Self {
+            positive: xy(reference.positive),
+            negative: xy(reference.negative),
+            delta_e: reference.separation(),
+        }
+    }
+
+    /// The Euclidean distance between the controls in `diagram`.
+    pub fn distance(&self, diagram: Diagram) -> f32 {
+        diagram.distance(self.positive, self.negative)
+    }
+}
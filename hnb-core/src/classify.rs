@@ -0,0 +1,403 @@
+//! Calling reactions with HNB dye positive or negative from their color.
+//!
+//! HNB turns from violet (negative) to sky blue (positive) when the
+//! amplification consumes the magnesium ions. The call uses the CIELAB hue
+//! and chroma of the median color of a region: hues below the boundary hue
+//! are positive, above it negative. Regions too close to the boundary, too
+//! gray or with a hue outside the range of HNB are inconclusive.
+//!
+//! With positive and negative controls in the image, [classify_by_controls]
+//! scores each region by its position between the two control colors, so
+//! the calls adapt to the lighting of each photo.
+
+use crate::color_transform::Parameter;
+use crate::stats::ColorStats;
+
+/// The result of a reaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Call {
+    /// Sky blue.
+    Positive,
+    /// Violet.
+    Negative,
+    Inconclusive,
+}
+
+impl Call {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Call::Positive => "positive",
+            Call::Negative => "negative",
+            Call::Inconclusive => "inconclusive",
+        }
+    }
+
+    /// A one character symbol, e.g. for labels on the image.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Call::Positive => "+",
+            Call::Negative => "−",
+            Call::Inconclusive => "?",
+        }
+    }
+}
+
+/// A region with a known result, to compare the other regions with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    Positive,
+    Negative,
+}
+
+impl Control {
+    pub const ALL: [Control; 2] = [Control::Positive, Control::Negative];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Control::Positive => "positive control",
+            Control::Negative => "negative control",
+        }
+    }
+
+    /// A short label, e.g. for buttons and labels on the image.
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Control::Positive => "C+",
+            Control::Negative => "C−",
+        }
+    }
+}
+
+/// The decision boundaries of [classify] and [classify_by_controls].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassifierParams {
+    /// CIELAB hue angle, in degrees, between sky blue and violet.
+    pub boundary_hue: f32,
+    /// Hues closer than this to the boundary, in degrees, are inconclusive.
+    pub margin: f32,
+    /// Regions with a lower CIELAB chroma C* are inconclusive.
+    pub min_chroma: f32,
+    /// The range of CIELAB hues of HNB, in degrees. Other hues are
+    /// inconclusive.
+    pub hue_range: (f32, f32),
+    /// Scores closer than this to 0.5 are inconclusive with controls.
+    pub control_margin: f32,
+    /// Regions further than this from the line through the controls, relative
+    /// to the distance between the controls, are inconclusive.
+    pub max_off_axis: f32,
+}
+
+impl Default for ClassifierParams {
+    fn default() -> Self {
+        Self {
+            boundary_hue: 285.0,
+            margin: 8.0,
+            min_chroma: 8.0,
+            hue_range: (200.0, 350.0),
+            control_margin: 0.15,
+            max_off_axis: 1.0,
+        }
+    }
+}
+
+impl ClassifierParams {
+    /// The adjustable parameters of [classify] with their current values.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Boundary hue (degrees)",
+                slug: "boundary_hue",
+                value: self.boundary_hue,
+                min: 200.0,
+                max: 350.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Inconclusive margin (degrees)",
+                slug: "margin",
+                value: self.margin,
+                min: 0.0,
+                max: 45.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Minimum chroma",
+                slug: "min_chroma",
+                value: self.min_chroma,
+                min: 0.0,
+                max: 50.0,
+                step: 1.0,
+            },
+        ]
+    }
+
+    /// The adjustable parameters of [classify_by_controls] with their
+    /// current values.
+    pub fn control_parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Inconclusive margin (score)",
+                slug: "control_margin",
+                value: self.control_margin,
+                min: 0.0,
+                max: 0.5,
+                step: 0.01,
+            },
+            Parameter {
+                name: "Maximum distance from the controls' axis",
+                slug: "max_off_axis",
+                value: self.max_off_axis,
+                min: 0.1,
+                max: 5.0,
+                step: 0.1,
+            },
+        ]
+    }
+
+    /// Set the parameter `slug` to `value`. Unknown slugs are ignored.
+    pub fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "boundary_hue" => self.boundary_hue = value,
+            "margin" => self.margin = value,
+            "min_chroma" => self.min_chroma = value,
+            "control_margin" => self.control_margin = value,
+            "max_off_axis" => self.max_off_axis = value,
+            _ => {}
+        }
+    }
+}
+
+/// The call for one region with the color it is based on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Classification {
+    pub call: Call,
+    /// From 0 (at the edge of the inconclusive margin) to 1 (20 degrees or
+    /// more beyond it, with at least twice the minimum chroma). 0 for
+    /// inconclusive calls.
+    pub confidence: f32,
+    /// CIELAB hue of the median a* and b*, in degrees.
+    pub hue: f32,
+    /// CIELAB chroma of the median a* and b*.
+    pub chroma: f32,
+    /// With controls, the position between the negative (0) and the positive
+    /// (1) controls.
+    pub score: Option<f32>,
+}
+
+/// Hue difference beyond the margin at which the confidence reaches 1.
+const FULL_CONFIDENCE_DEGREES: f32 = 20.0;
+
+/// Call the reaction of a region from its color statistics.
+pub fn classify(stats: &ColorStats, params: &ClassifierParams) -> Classification {
+    let (a, b) = (stats.lab[1].median, stats.lab[2].median);
+    let hue = b.atan2(a).to_degrees().rem_euclid(360.0);
+    let chroma = a.hypot(b);
+    let (h0, h1) = params.hue_range;
+    // Signed distance from the boundary, positive towards violet.
+    let distance = (hue - params.boundary_hue + 180.0).rem_euclid(360.0) - 180.0;
+    let call = if chroma < params.min_chroma
+        || !(h0..=h1).contains(&hue)
+        || distance.abs() < params.margin
+    {
+        Call::Inconclusive
+    } else if distance < 0.0 {
+        Call::Positive
+    } else {
+        Call::Negative
+    };
+    let confidence = match call {
+        Call::Inconclusive => 0.0,
+        _ => {
+            let by_hue = (distance.abs() - params.margin) / FULL_CONFIDENCE_DEGREES;
+            let by_chroma = chroma / (2.0 * params.min_chroma).max(f32::EPSILON);
+            by_hue.min(by_chroma).clamp(0.0, 1.0)
+        }
+    };
+    Classification {
+        call,
+        confidence,
+        hue,
+        chroma,
+        score: None,
+    }
+}
+
+/// The median CIELAB color of a region.
+fn median_lab(stats: &ColorStats) -> [f32; 3] {
+    [
+        stats.lab[0].median,
+        stats.lab[1].median,
+        stats.lab[2].median,
+    ]
+}
+
+fn mean_lab<'a>(stats: impl IntoIterator<Item = &'a ColorStats>) -> Option<[f32; 3]> {
+    let mut sum = [0.0; 3];
+    let mut n = 0;
+    for s in stats {
+        for (sum, v) in sum.iter_mut().zip(median_lab(s)) {
+            *sum += v;
+        }
+        n += 1;
+    }
+    if n == 0 {
+        return None;
+    }
+    Some(sum.map(|v| v / n as f32))
+}
+
+/// The centers of the positive and negative controls in CIELAB.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControlReference {
+    pub positive: [f32; 3],
+    pub negative: [f32; 3],
+}
+
+impl ControlReference {
+    /// The mean of the median colors of each group of controls. `None` without
+    /// controls of both kinds or if both have the same color.
+    pub fn new<'a>(
+        positives: impl IntoIterator<Item = &'a ColorStats>,
+        negatives: impl IntoIterator<Item = &'a ColorStats>,
+    ) -> Option<Self> {
+        let reference = Self {
+            positive: mean_lab(positives)?,
+            negative: mean_lab(negatives)?,
+        };
+        if reference.separation() > f32::EPSILON {
+            Some(reference)
+        } else {
+            None
+        }
+    }
+
+    /// The CIELAB distance ΔE*ab between the controls.
+    pub fn separation(&self) -> f32 {
+        distance(self.positive, self.negative)
+    }
+
+    /// The projection of `lab` on the line from the negative (0) to the
+    /// positive (1) control, and the distance from that line relative to the
+    /// separation of the controls.
+    pub fn project(&self, lab: [f32; 3]) -> (f32, f32) {
+        let axis = sub(self.positive, self.negative);
+        let v = sub(lab, self.negative);
+        let len2 = dot(axis, axis);
+        let t = dot(v, axis) / len2;
+        let off_axis = (dot(v, v) - t * t * len2).max(0.0).sqrt() / len2.sqrt();
+        (t, off_axis)
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = sub(a, b);
+    dot(d, d).sqrt()
+}
+
+/// Call the reaction of a region by the position of its median color between
+/// the controls.
+///
+/// Scores above 0.5 are positive and below negative, except within
+/// `control_margin` of 0.5 or for colors far from the line through the
+/// controls, which are inconclusive. The confidence reaches 1 at the color of
+/// the controls.
+pub fn classify_by_controls(
+    stats: &ColorStats,
+    reference: &ControlReference,
+    params: &ClassifierParams,
+) -> Classification {
+    let lab = median_lab(stats);
+    let (score, off_axis) = reference.project(lab);
+    let from_middle = score - 0.5;
+    let call = if off_axis > params.max_off_axis || from_middle.abs() < params.control_margin {
+        Call::Inconclusive
+    } else if from_middle > 0.0 {
+        Call::Positive
+    } else {
+        Call::Negative
+    };
+    let confidence = match call {
+        Call::Inconclusive => 0.0,
+        _ => ((from_middle.abs() - params.control_margin)
+            / (0.5 - params.control_margin).max(f32::EPSILON))
+        .clamp(0.0, 1.0),
+    };
+    Classification {
+        call,
+        confidence,
+        hue: lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0),
+        chroma: lab[1].hypot(lab[2]),
+        score: Some(score),
+    }
+}
+
+/// Call the regions of a strip or plate, given as their kind of control, if
+/// any, and their color statistics.
+///
+/// The controls of both kinds, if present, are the reference by which every
+/// other region is called with [classify_by_controls], otherwise [classify]
+/// is used. The calls are in the order of `regions`: `None` for the controls
+/// themselves and for regions without statistics.
+pub fn classify_regions(
+    regions: &[(Option<Control>, Option<&ColorStats>)],
+    params: &ClassifierParams,
+) -> (Vec<Option<Classification>>, Option<ControlReference>) {
+    let controls = |control| {
+        regions
+            .iter()
+            .filter(move |(c, _)| *c == Some(control))
+            .filter_map(|(_, s)| *s)
+    };
+    let reference = ControlReference::new(controls(Control::Positive), controls(Control::Negative));
+    let calls = regions
+        .iter()
+        .map(|(control, stats)| {
+            let stats = stats.filter(|_| control.is_none())?;
+            Some(match &reference {
+                Some(reference) => classify_by_controls(stats, reference, params),
+                None => classify(stats, params),
+            })
+        })
+        .collect();
+    (calls, reference)
+}
+
+/// Number of regions per call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallCounts {
+    pub positive: usize,
+    pub negative: usize,
+    pub inconclusive: usize,
+}
+
+impl CallCounts {
+    pub fn of<'a>(calls: impl IntoIterator<Item = &'a Call>) -> Self {
+        let mut counts = Self::default();
+        for call in calls {
+            match call {
+                Call::Positive => counts.positive += 1,
+                Call::Negative => counts.negative += 1,
+                Call::Inconclusive => counts.inconclusive += 1,
+            }
+        }
+        counts
+    }
+}
+
+impl std::fmt::Display for CallCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} positive / {} negative / {} inconclusive",
+            self.positive, self.negative, self.inconclusive
+        )
+    }
+}
@@ -0,0 +1,686 @@
+use crate::fiji::color_inspector_rotate;
+use crate::lut::Lut3d;
+use crate::perceptual::{lch_rotate, lch_stretch, LchSpace};
+use crate::transform_colors::{
+    color_stretch, color_stretch_encoded, saturate_and_rotate, saturate_and_rotate_linear,
+};
+
+/// Description and current value of one adjustable parameter of a transform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    /// Human readable name, e.g. for a slider label.
+    pub name: &'static str,
+    /// Short identifier, unique within one transform.
+    pub slug: &'static str,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+}
+
+/// A color transform operating on a raw RGBA pixel buffer.
+pub trait ColorTransform {
+    /// Human readable name, e.g. "Color Rotated".
+    fn name(&self) -> &str;
+    /// Short identifier used e.g. in file names, e.g. "rotated".
+    fn slug(&self) -> &str;
+    /// The adjustable parameters with their current values.
+    fn parameters(&self) -> Vec<Parameter>;
+    /// Set the parameter `slug` to `value`. Unknown slugs are ignored.
+    fn set_parameter(&mut self, slug: &str, value: f32);
+    /// Transform the pixels of an RGBA buffer in place.
+    fn apply(&self, data: &mut [u8]);
+
+    /// Short description of the current parameters, e.g. for captions.
+    fn summary(&self) -> String {
+        self.parameters()
+            .iter()
+            .map(|p| format!("{} {}", p.slug, p.value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Identifies the transform and its current parameters, e.g. to cache a
+    /// [Lut3d] computed from it. Two transforms with the same key
+    /// must give the same results.
+    fn cache_key(&self) -> String {
+        let mut key = self.slug().to_string();
+        for p in self.parameters() {
+            key.push_str(&format!(":{}={}", p.slug, p.value));
+        }
+        key
+    }
+
+    /// The table, if [ColorTransform::apply] is itself a lookup in a
+    /// [Lut3d]. Precomputing another table then does not make it faster.
+    fn lookup_table(&self) -> Option<&Lut3d> {
+        None
+    }
+
+    /// Whether every pixel must be computed by [ColorTransform::apply], as
+    /// an interpolated [Lut3d] would defeat the purpose of the transform.
+    fn requires_direct(&self) -> bool {
+        false
+    }
+}
+
+/// How the HSL values of Color Rotate and Color Stretch are computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HslPipeline {
+    /// The original implementation, chosen to visually match FIJI.
+    #[default]
+    Legacy,
+    /// Compute HSL from linear light and limit the saturation to 1.
+    ///
+    /// Legacy Color Stretch already computes HSL from linear light, so this
+    /// only applies to Color Rotate.
+    SrgbLinearized,
+    /// Compute HSL from the sRGB encoded values.
+    ///
+    /// Legacy Color Rotate already computes HSL from the encoded values, so
+    /// this only applies to Color Stretch.
+    SrgbEncoded,
+}
+
+/// Color Rotate: rotate hue and increase saturation, see [saturate_and_rotate]
+/// and [saturate_and_rotate_linear].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorRotate {
+    /// Hue rotation, in degrees.
+    pub degrees: f32,
+    /// Saturation gain.
+    pub saturation: f32,
+    pub pipeline: HslPipeline,
+}
+
+impl Default for ColorRotate {
+    fn default() -> Self {
+        Self {
+            degrees: 180.0,
+            saturation: 4.0,
+            pipeline: HslPipeline::Legacy,
+        }
+    }
+}
+
+impl ColorRotate {
+    /// Color Rotate with the sRGB linearized pipeline.
+    pub fn linearized() -> Self {
+        Self {
+            pipeline: HslPipeline::SrgbLinearized,
+            ..Default::default()
+        }
+    }
+}
+
+impl ColorTransform for ColorRotate {
+    fn name(&self) -> &str {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbEncoded => "Color Rotated",
+            HslPipeline::SrgbLinearized => "Color Rotated (sRGB linearized)",
+        }
+    }
+    fn slug(&self) -> &str {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbEncoded => "rotated",
+            HslPipeline::SrgbLinearized => "rotated-linear",
+        }
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Hue rotation (degrees)",
+                slug: "degrees",
+                value: self.degrees,
+                min: 0.0,
+                max: 360.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Saturation gain",
+                slug: "saturation",
+                value: self.saturation,
+                min: 0.0,
+                max: 10.0,
+                step: 0.1,
+            },
+        ]
+    }
+    fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "degrees" => self.degrees = value,
+            "saturation" => self.saturation = value,
+            _ => {}
+        }
+    }
+    fn apply(&self, data: &mut [u8]) {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbEncoded => {
+                saturate_and_rotate(data, self.degrees, self.saturation)
+            }
+            HslPipeline::SrgbLinearized => {
+                saturate_and_rotate_linear(data, self.degrees, self.saturation)
+            }
+        }
+    }
+    fn summary(&self) -> String {
+        format!("{}°, {}x saturation", self.degrees, self.saturation)
+    }
+}
+
+/// Color Rotate in YUV, transcribed from the code of FIJI's Color Inspector
+/// 3D, see [color_inspector_rotate]. It is not verified against images saved
+/// from FIJI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorInspectorRotate {
+    /// Hue rotation, in degrees.
+    pub degrees: f32,
+    /// Saturation gain.
+    pub saturation: f32,
+}
+
+impl Default for ColorInspectorRotate {
+    fn default() -> Self {
+        Self {
+            degrees: 180.0,
+            saturation: 4.0,
+        }
+    }
+}
+
+impl ColorTransform for ColorInspectorRotate {
+    fn name(&self) -> &str {
+        "Color Rotated (YUV)"
+    }
+    fn slug(&self) -> &str {
+        "fiji-rotated"
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Color rotation (degrees)",
+                slug: "degrees",
+                value: self.degrees,
+                min: 0.0,
+                max: 360.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Saturation",
+                slug: "saturation",
+                value: self.saturation,
+                min: 0.0,
+                max: 10.0,
+                step: 0.1,
+            },
+        ]
+    }
+    fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "degrees" => self.degrees = value,
+            "saturation" => self.saturation = value,
+            _ => {}
+        }
+    }
+    fn apply(&self, data: &mut [u8]) {
+        color_inspector_rotate(data, self.degrees, self.saturation);
+    }
+    fn summary(&self) -> String {
+        format!("{}°, {}x saturation", self.degrees, self.saturation)
+    }
+    /// It is meant to follow FIJI's 8 bit values, which a grid would blur.
+    fn requires_direct(&self) -> bool {
+        true
+    }
+}
+
+/// Color Stretch: stretch hues away from a center, see [color_stretch] and
+/// [color_stretch_encoded].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorStretch {
+    /// Center hue, as a fraction of a full turn.
+    pub center_hue: f32,
+    /// Distance of the center from the origin of the hue circle.
+    pub radius: f32,
+    /// Saturation gain.
+    pub saturation: f32,
+    pub pipeline: HslPipeline,
+}
+
+impl Default for ColorStretch {
+    fn default() -> Self {
+        Self {
+            center_hue: 0.6,
+            radius: 0.8,
+            saturation: 4.0,
+            pipeline: HslPipeline::Legacy,
+        }
+    }
+}
+
+impl ColorStretch {
+    /// Color Stretch computing HSL from the sRGB encoded values.
+    pub fn encoded() -> Self {
+        Self {
+            pipeline: HslPipeline::SrgbEncoded,
+            ..Default::default()
+        }
+    }
+}
+
+impl ColorTransform for ColorStretch {
+    fn name(&self) -> &str {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbLinearized => "Color Stretched",
+            HslPipeline::SrgbEncoded => "Color Stretched (sRGB encoded)",
+        }
+    }
+    fn slug(&self) -> &str {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbLinearized => "stretch",
+            HslPipeline::SrgbEncoded => "stretch-encoded",
+        }
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Center hue (fraction of full turn)",
+                slug: "center_hue",
+                value: self.center_hue,
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+            },
+            Parameter {
+                name: "Radius",
+                slug: "radius",
+                value: self.radius,
+                min: 0.0,
+                max: 0.99,
+                step: 0.01,
+            },
+            Parameter {
+                name: "Saturation gain",
+                slug: "saturation",
+                value: self.saturation,
+                min: 0.0,
+                max: 10.0,
+                step: 0.1,
+            },
+        ]
+    }
+    fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "center_hue" => self.center_hue = value,
+            "radius" => self.radius = value,
+            "saturation" => self.saturation = value,
+            _ => {}
+        }
+    }
+    fn apply(&self, data: &mut [u8]) {
+        match self.pipeline {
+            HslPipeline::Legacy | HslPipeline::SrgbLinearized => {
+                color_stretch(data, self.center_hue, self.radius, self.saturation)
+            }
+            HslPipeline::SrgbEncoded => {
+                color_stretch_encoded(data, self.center_hue, self.radius, self.saturation)
+            }
+        }
+    }
+    fn summary(&self) -> String {
+        format!(
+            "center {}, radius {}, {}x saturation",
+            self.center_hue, self.radius, self.saturation
+        )
+    }
+}
+
+/// Hue rotation and chroma gain in a perceptual space, see [lch_rotate].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LchRotate {
+    pub space: LchSpace,
+    /// Hue rotation, in degrees.
+    pub degrees: f32,
+    /// Chroma gain.
+    pub chroma: f32,
+}
+
+impl LchRotate {
+    pub fn new(space: LchSpace) -> Self {
+        Self {
+            space,
+            degrees: 180.0,
+            chroma: 4.0,
+        }
+    }
+}
+
+impl ColorTransform for LchRotate {
+    fn name(&self) -> &str {
+        match self.space {
+            LchSpace::Oklch => "Color Rotated (OKLCh)",
+            LchSpace::CieLch => "Color Rotated (CIE LCh)",
+        }
+    }
+    fn slug(&self) -> &str {
+        match self.space {
+            LchSpace::Oklch => "oklch-rotated",
+            LchSpace::CieLch => "lch-rotated",
+        }
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Hue rotation (degrees)",
+                slug: "degrees",
+                value: self.degrees,
+                min: 0.0,
+                max: 360.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Chroma gain (limited to gamut)",
+                slug: "chroma",
+                value: self.chroma,
+                min: 0.0,
+                max: 10.0,
+                step: 0.1,
+            },
+        ]
+    }
+    fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "degrees" => self.degrees = value,
+            "chroma" => self.chroma = value,
+            _ => {}
+        }
+    }
+    fn apply(&self, data: &mut [u8]) {
+        lch_rotate(data, self.space, self.degrees, self.chroma);
+    }
+    fn summary(&self) -> String {
+        format!("{}°, {}x chroma", self.degrees, self.chroma)
+    }
+}
+
+/// Color Stretch with hue and chroma in a perceptual space, see [lch_stretch].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LchStretch {
+    pub space: LchSpace,
+    /// Center hue angle, in degrees.
+    pub center_hue: f32,
+    /// Distance of the center from the origin of the hue circle.
+    pub radius: f32,
+    /// Chroma gain.
+    pub chroma: f32,
+}
+
+impl LchStretch {
+    pub fn new(space: LchSpace) -> Self {
+        Self {
+            space,
+            center_hue: space.default_center_hue(),
+            radius: 0.8,
+            chroma: 4.0,
+        }
+    }
+}
+
+impl ColorTransform for LchStretch {
+    fn name(&self) -> &str {
+        match self.space {
+            LchSpace::Oklch => "Color Stretched (OKLCh)",
+            LchSpace::CieLch => "Color Stretched (CIE LCh)",
+        }
+    }
+    fn slug(&self) -> &str {
+        match self.space {
+            LchSpace::Oklch => "oklch-stretch",
+            LchSpace::CieLch => "lch-stretch",
+        }
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter {
+                name: "Center hue angle (degrees)",
+                slug: "center_hue",
+                value: self.center_hue,
+                min: 0.0,
+                max: 360.0,
+                step: 1.0,
+            },
+            Parameter {
+                name: "Radius",
+                slug: "radius",
+                value: self.radius,
+                min: 0.0,
+                max: 0.99,
+                step: 0.01,
+            },
+            Parameter {
+                name: "Chroma gain (limited to gamut)",
+                slug: "chroma",
+                value: self.chroma,
+                min: 0.0,
+                max: 10.0,
+                step: 0.1,
+            },
+        ]
+    }
+    fn set_parameter(&mut self, slug: &str, value: f32) {
+        match slug {
+            "center_hue" => self.center_hue = value,
+            "radius" => self.radius = value,
+            "chroma" => self.chroma = value,
+            _ => {}
+        }
+    }
+    fn apply(&self, data: &mut [u8]) {
+        lch_stretch(data, self.space, self.center_hue, self.radius, self.chroma);
+    }
+    fn summary(&self) -> String {
+        format!(
+            "center {}°, radius {}, {}x chroma",
+            self.center_hue, self.radius, self.chroma
+        )
+    }
+}
+
+/// A kind of color transform which can be instantiated by a [Registry].
+pub struct TransformKind {
+    pub slug: &'static str,
+    pub name: &'static str,
+    /// Create a new instance with default parameters.
+    pub create: fn() -> Box<dyn ColorTransform>,
+}
+
+/// The set of color transforms available to the user.
+pub struct Registry {
+    kinds: Vec<TransformKind>,
+}
+
+impl Default for Registry {
+    /// A registry with all built-in transforms.
+    fn default() -> Self {
+        let mut result = Self::empty();
+        result.register(TransformKind {
+            slug: "rotated",
+            name: "Color Rotate",
+            create: || Box::new(ColorRotate::default()),
+        });
+        result.register(TransformKind {
+            slug: "stretch",
+            name: "Color Stretch",
+            create: || Box::new(ColorStretch::default()),
+        });
+        result.register(TransformKind {
+            slug: "rotated-linear",
+            name: "Color Rotate (sRGB linearized)",
+            create: || Box::new(ColorRotate::linearized()),
+        });
+        result.register(TransformKind {
+            slug: "stretch-encoded",
+            name: "Color Stretch (sRGB encoded)",
+            create: || Box::new(ColorStretch::encoded()),
+        });
+        result.register(TransformKind {
+            slug: "fiji-rotated",
+            name: "Color Rotate (YUV)",
+            create: || Box::new(ColorInspectorRotate::default()),
+        });
+        result.register(TransformKind {
+            slug: "oklch-rotated",
+            name: "Color Rotate (OKLCh)",
+            create: || Box::new(LchRotate::new(LchSpace::Oklch)),
+        });
+        result.register(TransformKind {
+            slug: "oklch-stretch",
+            name: "Color Stretch (OKLCh)",
+            create: || Box::new(LchStretch::new(LchSpace::Oklch)),
+        });
+        result.register(TransformKind {
+            slug: "lch-rotated",
+            name: "Color Rotate (CIE LCh)",
+            create: || Box::new(LchRotate::new(LchSpace::CieLch)),
+        });
+        result.register(TransformKind {
+            slug: "lch-stretch",
+            name: "Color Stretch (CIE LCh)",
+            create: || Box::new(LchStretch::new(LchSpace::CieLch)),
+        });
+        result
+    }
+}
+
+impl Registry {
+    /// A registry without any transforms.
+    pub fn empty() -> Self {
+        Self { kinds: Vec::new() }
+    }
+
+    /// Add a transform kind. A kind with the same slug is replaced.
+    pub fn register(&mut self, kind: TransformKind) {
+        self.kinds.retain(|k| k.slug != kind.slug);
+        self.kinds.push(kind);
+    }
+
+    pub fn kinds(&self) -> &[TransformKind] {
+        &self.kinds
+    }
+
+    /// Create a new instance of the transform kind `slug`.
+    pub fn create(&self, slug: &str) -> Option<Box<dyn ColorTransform>> {
+        self.kinds
+            .iter()
+            .find(|k| k.slug == slug)
+            .map(|k| (k.create)())
+    }
+}
+
+/// An error in a saved [Preset].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetError {
+    /// The line does not have a name, a transform kind and parameter values
+    /// separated by tabs.
+    InvalidLine(String),
+    /// A parameter value is not `slug=number`.
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetError::InvalidLine(line) => write!(f, "invalid preset \"{line}\""),
+            PresetError::InvalidValue(value) => {
+                write!(f, "invalid preset parameter \"{value}\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+/// Named parameter values for a transform kind of the [Registry], e.g. found
+/// by an optimization, to create the same transform again later.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    /// Slug of the transform kind.
+    pub kind: String,
+    /// Parameter slugs and values.
+    pub values: Vec<(String, f32)>,
+}
+
+impl Preset {
+    /// The current parameters of `transform`, whose slug must be that of its
+    /// kind in the registry.
+    pub fn new(name: &str, transform: &dyn ColorTransform) -> Self {
+        // Tabs and line breaks separate the parts of saved presets.
+        let name = name.replace(['\t', '\n', '\r'], " ");
+        Self {
+            name: name.trim().to_string(),
+            kind: transform.slug().to_string(),
+            values: transform
+                .parameters()
+                .into_iter()
+                .map(|p| (p.slug.to_string(), p.value))
+                .collect(),
+        }
+    }
+
+    /// Create the transform with the parameters of the preset. `None` if the
+    /// registry has no such transform kind.
+    pub fn create(&self, registry: &Registry) -> Option<Box<dyn ColorTransform>> {
+        let mut transform = registry.create(&self.kind)?;
+        for (slug, value) in self.values.iter() {
+            transform.set_parameter(slug, *value);
+        }
+        Some(transform)
+    }
+
+    /// The options of the `hnb` command line tool applying the preset, e.g.
+    /// `-t stretch -s stretch.radius=0.8`.
+    pub fn cli_args(&self) -> String {
+        let mut args = format!("-t {}", self.kind);
+        for (slug, value) in self.values.iter() {
+            args.push_str(&format!(" -s {}.{}={}", self.kind, slug, value));
+        }
+        args
+    }
+
+    /// One line of text, e.g. for saving the preset, which is read by
+    /// [Preset::parse_line].
+    pub fn to_line(&self) -> String {
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|(slug, value)| format!("{slug}={value}"))
+            .collect();
+        format!("{}\t{}\t{}", self.name, self.kind, values.join(","))
+    }
+
+    pub fn parse_line(line: &str) -> Result<Self, PresetError> {
+        let invalid = || PresetError::InvalidLine(line.to_string());
+        let mut parts = line.split('\t');
+        let (name, kind, values) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(kind), Some(values), None) if !kind.is_empty() => {
+                (name, kind, values)
+            }
+            _ => return Err(invalid()),
+        };
+        let values = values
+            .split(',')
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                v.split_once('=')
+                    .and_then(|(slug, value)| Some((slug.to_string(), value.parse().ok()?)))
+                    .ok_or_else(|| PresetError::InvalidValue(v.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            values,
+        })
+    }
+}
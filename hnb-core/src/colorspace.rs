@@ -0,0 +1,235 @@
+//! Conversions between linear sRGB and device independent color spaces.
+//!
+//! Linear sRGB values are linear light in 0..=1, see
+//! [crate::transform_colors::srgb_to_linear]. The white point is D65
+//! throughout.
+
+use crate::image::BYTES_PER_PIXEL;
+use crate::transform_colors::{linear_to_srgb, srgb_decode_table, srgb_to_linear};
+
+/// A 3x3 matrix, row major.
+pub type Matrix3 = [[f32; 3]; 3];
+
+/// D65 white point in XYZ with Y = 1.
+pub const D65_XYZ: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Linear sRGB to XYZ (D65).
+pub const SRGB_TO_XYZ: Matrix3 = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+
+/// XYZ (D65) to linear sRGB.
+pub const XYZ_TO_SRGB: Matrix3 = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+#[inline]
+pub fn mul(m: &Matrix3, v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+/// The product `a b` of two matrices.
+pub fn mul_matrices(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+/// The inverse of a matrix, `None` if it is singular.
+pub fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: f32 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let mut inverse = [[0.0; 3]; 3];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = cofactor(j, i) / det;
+        }
+    }
+    Some(inverse)
+}
+
+/// Decode 8 bit sRGB to linear sRGB.
+#[inline]
+pub fn srgb8_to_linear(rgb: [u8; 3]) -> [f32; 3] {
+    [
+        srgb_to_linear(rgb[0] as f32 / 255.0),
+        srgb_to_linear(rgb[1] as f32 / 255.0),
+        srgb_to_linear(rgb[2] as f32 / 255.0),
+    ]
+}
+
+/// Encode linear sRGB as 8 bit sRGB, clamping to the gamut.
+#[inline]
+pub fn linear_to_srgb8(rgb: [f32; 3]) -> [u8; 3] {
+    let enc = |v: f32| (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    [enc(rgb[0]), enc(rgb[1]), enc(rgb[2])]
+}
+
+/// Whether linear sRGB values are within the gamut, allowing a tiny error.
+#[inline]
+pub fn in_gamut(rgb: [f32; 3]) -> bool {
+    const EPS: f32 = 1e-4;
+    rgb.iter().all(|&v| (-EPS..=1.0 + EPS).contains(&v))
+}
+
+#[inline]
+pub fn linear_to_xyz(rgb: [f32; 3]) -> [f32; 3] {
+    mul(&SRGB_TO_XYZ, rgb)
+}
+
+#[inline]
+pub fn xyz_to_linear(xyz: [f32; 3]) -> [f32; 3] {
+    mul(&XYZ_TO_SRGB, xyz)
+}
+
+/// The CIE 1931 xy chromaticity of XYZ, that of D65 for black.
+pub fn xyz_to_xy(xyz: [f32; 3]) -> (f32, f32) {
+    let sum = xyz[0] + xyz[1] + xyz[2];
+    if sum <= 0.0 {
+        return xyz_to_xy(D65_XYZ);
+    }
+    (xyz[0] / sum, xyz[1] / sum)
+}
+
+/// The correlated color temperature in kelvin of a chromaticity near the
+/// Planckian locus, by the approximation of McCamy (1992).
+pub fn correlated_color_temperature((x, y): (f32, f32)) -> f32 {
+    let n = (x - 0.3320) / (0.1858 - y);
+    449.0 * n.powi(3) + 3525.0 * n.powi(2) + 6823.3 * n + 5520.33
+}
+
+/// Apply `f` to the linear sRGB colors of an RGBA buffer in place.
+pub(crate) fn map_linear(data: &mut [u8], f: impl Fn([f32; 3]) -> [f32; 3]) {
+    // Encoding by table, fine enough to round to the same 8 bit values
+    // almost everywhere.
+    const ENCODE_TABLE_SIZE: usize = 4096;
+    let decode = srgb_decode_table();
+    let encode: Vec<u8> = (0..ENCODE_TABLE_SIZE)
+        .map(|i| {
+            let v = i as f32 / (ENCODE_TABLE_SIZE - 1) as f32;
+            (linear_to_srgb(v) * 255.0).round() as u8
+        })
+        .collect();
+    let max = (ENCODE_TABLE_SIZE - 1) as f32;
+    for pix in data.chunks_exact_mut(BYTES_PER_PIXEL) {
+        let rgb = f([
+            decode[pix[0] as usize],
+            decode[pix[1] as usize],
+            decode[pix[2] as usize],
+        ]);
+        for c in 0..3 {
+            pix[c] = encode[(rgb[c].clamp(0.0, 1.0) * max).round() as usize];
+        }
+    }
+}
+
+const LAB_DELTA: f32 = 6.0 / 29.0;
+
+#[inline]
+fn lab_f(t: f32) -> f32 {
+    if t > LAB_DELTA * LAB_DELTA * LAB_DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * LAB_DELTA * LAB_DELTA) + 4.0 / 29.0
+    }
+}
+
+#[inline]
+fn lab_f_inv(t: f32) -> f32 {
+    if t > LAB_DELTA {
+        t * t * t
+    } else {
+        3.0 * LAB_DELTA * LAB_DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+/// XYZ to CIE L*a*b*, relative to the D65 white.
+pub fn xyz_to_lab(xyz: [f32; 3]) -> [f32; 3] {
+    let fx = lab_f(xyz[0] / D65_XYZ[0]);
+    let fy = lab_f(xyz[1] / D65_XYZ[1]);
+    let fz = lab_f(xyz[2] / D65_XYZ[2]);
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// CIE L*a*b* to XYZ, relative to the D65 white.
+pub fn lab_to_xyz(lab: [f32; 3]) -> [f32; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+    [
+        D65_XYZ[0] * lab_f_inv(fx),
+        D65_XYZ[1] * lab_f_inv(fy),
+        D65_XYZ[2] * lab_f_inv(fz),
+    ]
+}
+
+#[inline]
+pub fn linear_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    xyz_to_lab(linear_to_xyz(rgb))
+}
+
+#[inline]
+pub fn lab_to_linear(lab: [f32; 3]) -> [f32; 3] {
+    xyz_to_linear(lab_to_xyz(lab))
+}
+
+/// Linear sRGB to OKLab, see https://bottosson.github.io/posts/oklab/ .
+pub fn linear_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// OKLab to linear sRGB, the inverse of [linear_to_oklab].
+pub fn oklab_to_linear(lab: [f32; 3]) -> [f32; 3] {
+    let [ll, a, b] = lab;
+    let l = ll + 0.396_337_78 * a + 0.215_803_76 * b;
+    let m = ll - 0.105_561_346 * a - 0.063_854_17 * b;
+    let s = ll - 0.089_484_18 * a - 1.291_485_5 * b;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_4 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// Lab-like (lightness, a, b) to (lightness, chroma, hue in degrees 0..360).
+#[inline]
+pub fn lab_to_lch(lab: [f32; 3]) -> [f32; 3] {
+    let c = lab[1].hypot(lab[2]);
+    let h = lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0);
+    [lab[0], c, h]
+}
+
+/// (lightness, chroma, hue in degrees) to Lab-like (lightness, a, b).
+#[inline]
+pub fn lch_to_lab(lch: [f32; 3]) -> [f32; 3] {
+    let h = lch[2].to_radians();
+    [lch[0], lch[1] * h.cos(), lch[1] * h.sin()]
+}
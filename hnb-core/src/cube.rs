@@ -0,0 +1,302 @@
+//! Reading and writing 3D LUTs in the Adobe/Resolve `.cube` format.
+//!
+//! The format is described in the "Cube LUT Specification" by Adobe. It is
+//! understood e.g. by DaVinci Resolve, darktable (lut 3D module), ImageJ/FIJI
+//! plugins and many camera apps, so a transform tuned here can be applied
+//! elsewhere.
+
+use std::hash::{Hash, Hasher};
+
+use crate::color_transform::{ColorTransform, Parameter};
+use crate::lut::{Interpolation, Lut3d};
+
+/// Number of nodes per axis of exported LUTs. 33 is supported by virtually
+/// all applications reading `.cube` files.
+pub const DEFAULT_CUBE_SIZE: usize = 33;
+
+/// The largest number of nodes per axis allowed by the specification. Not all
+/// applications read more than 33, see [DEFAULT_CUBE_SIZE].
+pub const MAX_CUBE_SIZE: usize = 256;
+
+/// An error in the contents of a `.cube` file. Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CubeError {
+    /// A line which is not a valid keyword or data line.
+    InvalidLine { line: usize, message: String },
+    /// The file has a 1D LUT, which is not supported.
+    Unsupported1d { line: usize },
+    /// There is no `LUT_3D_SIZE` line.
+    MissingSize,
+    /// The number of data lines does not match `LUT_3D_SIZE`.
+    WrongNodeCount { expected: usize, actual: usize },
+    /// `DOMAIN_MIN` is not smaller than `DOMAIN_MAX`.
+    EmptyDomain,
+}
+
+impl std::fmt::Display for CubeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CubeError::InvalidLine { line, message } => write!(f, "line {line}: {message}"),
+            CubeError::Unsupported1d { line } => {
+                write!(f, "line {line}: 1D LUTs are not supported, only 3D LUTs")
+            }
+            CubeError::MissingSize => write!(f, "no LUT_3D_SIZE given"),
+            CubeError::WrongNodeCount { expected, actual } => write!(
+                f,
+                "expected {expected} data lines for LUT_3D_SIZE, found {actual}"
+            ),
+            CubeError::EmptyDomain => write!(f, "DOMAIN_MIN must be smaller than DOMAIN_MAX"),
+        }
+    }
+}
+
+impl std::error::Error for CubeError {}
+
+/// The contents of a `.cube` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeFile {
+    pub title: Option<String>,
+    pub lut: Lut3d,
+}
+
+/// Parse exactly `N` numbers from `tokens`.
+fn parse_numbers<const N: usize>(
+    tokens: &[&str],
+    line: usize,
+    what: &str,
+) -> Result<[f32; N], CubeError> {
+    let invalid = || CubeError::InvalidLine {
+        line,
+        message: format!("expected {N} numbers for {what}"),
+    };
+    if tokens.len() != N {
+        return Err(invalid());
+    }
+    let mut result = [0.0; N];
+    for (r, t) in result.iter_mut().zip(tokens) {
+        *r = t
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(invalid)?;
+    }
+    Ok(result)
+}
+
+/// Parse the text of a `.cube` file with a 3D LUT.
+pub fn parse_cube(text: &str) -> Result<CubeFile, CubeError> {
+    let mut title = None;
+    let mut size: Option<usize> = None;
+    let mut domain_min = [0.0; 3];
+    let mut domain_max = [1.0; 3];
+    let mut values = Vec::new();
+
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let keyword = tokens[0];
+        let is_data = keyword.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c));
+
+        if is_data {
+            if size.is_none() {
+                return Err(CubeError::InvalidLine {
+                    line: line_no,
+                    message: "data before LUT_3D_SIZE".into(),
+                });
+            }
+            values.extend(parse_numbers::<3>(&tokens, line_no, "a data line")?);
+            continue;
+        }
+        if !values.is_empty() {
+            return Err(CubeError::InvalidLine {
+                line: line_no,
+                message: format!("keyword {keyword} after the data"),
+            });
+        }
+        match keyword {
+            "TITLE" => {
+                let rest = line[keyword.len()..].trim();
+                title = Some(rest.trim_matches('"').to_string());
+            }
+            "LUT_3D_SIZE" => {
+                if size.is_some() {
+                    return Err(CubeError::InvalidLine {
+                        line: line_no,
+                        message: "LUT_3D_SIZE given twice".into(),
+                    });
+                }
+                let n = tokens
+                    .get(1)
+                    .filter(|_| tokens.len() == 2)
+                    .and_then(|t| t.parse::<usize>().ok())
+                    .filter(|n| (2..=MAX_CUBE_SIZE).contains(n))
+                    .ok_or_else(|| CubeError::InvalidLine {
+                        line: line_no,
+                        message: format!("LUT_3D_SIZE must be a number from 2 to {MAX_CUBE_SIZE}"),
+                    })?;
+                size = Some(n);
+            }
+            "LUT_1D_SIZE" | "LUT_1D_INPUT_RANGE" => {
+                return Err(CubeError::Unsupported1d { line: line_no });
+            }
+            "DOMAIN_MIN" => domain_min = parse_numbers(&tokens[1..], line_no, keyword)?,
+            "DOMAIN_MAX" => domain_max = parse_numbers(&tokens[1..], line_no, keyword)?,
+            "LUT_3D_INPUT_RANGE" => {
+                let [lo, hi] = parse_numbers(&tokens[1..], line_no, keyword)?;
+                domain_min = [lo; 3];
+                domain_max = [hi; 3];
+            }
+            _ => {
+                return Err(CubeError::InvalidLine {
+                    line: line_no,
+                    message: format!("unknown keyword {keyword}"),
+                })
+            }
+        }
+    }
+
+    let size = size.ok_or(CubeError::MissingSize)?;
+    let expected = size * size * size;
+    if values.len() != 3 * expected {
+        return Err(CubeError::WrongNodeCount {
+            expected,
+            actual: values.len() / 3,
+        });
+    }
+    if (0..3).any(|c| domain_min[c] >= domain_max[c]) {
+        return Err(CubeError::EmptyDomain);
+    }
+    Ok(CubeFile {
+        title,
+        lut: Lut3d::from_values(size, values).with_domain(domain_min, domain_max),
+    })
+}
+
+/// A 3D LUT loaded from a `.cube` file, applied with tetrahedral
+/// interpolation.
+#[derive(Clone, Debug)]
+pub struct CubeLut {
+    name: String,
+    lut: Lut3d,
+    key: String,
+}
+
+impl CubeLut {
+    pub fn new(name: String, lut: Lut3d) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for v in lut.values() {
+            v.to_bits().hash(&mut hasher);
+        }
+        for v in lut.domain_min().iter().chain(lut.domain_max().iter()) {
+            v.to_bits().hash(&mut hasher);
+        }
+        let key = format!("cube:{name}:{:016x}", hasher.finish());
+        Self { name, lut, key }
+    }
+
+    /// Parse the `.cube` file `file_name` with contents `text`.
+    pub fn parse(file_name: &str, text: &str) -> Result<Self, CubeError> {
+        let cube = parse_cube(text)?;
+        let name = match cube.title {
+            Some(title) if !title.is_empty() => format!("LUT {title}"),
+            _ => format!("LUT {file_name}"),
+        };
+        Ok(Self::new(name, cube.lut))
+    }
+
+    pub fn lut(&self) -> &Lut3d {
+        &self.lut
+    }
+}
+
+impl ColorTransform for CubeLut {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn slug(&self) -> &str {
+        "cube"
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![]
+    }
+    fn set_parameter(&mut self, _slug: &str, _value: f32) {}
+    fn apply(&self, data: &mut [u8]) {
+        self.lut.apply(data, Interpolation::Tetrahedral);
+    }
+    fn summary(&self) -> String {
+        format!("{}³ .cube", self.lut.size())
+    }
+    fn cache_key(&self) -> String {
+        self.key.clone()
+    }
+    fn lookup_table(&self) -> Option<&Lut3d> {
+        Some(&self.lut)
+    }
+}
+
+/// Write `lut` in `.cube` format.
+///
+/// `title` is stored in the `TITLE` line, `comments` are written as `#`
+/// comment lines at the top of the file. Both are written in ASCII, which is
+/// all the specification allows.
+pub fn write_cube(lut: &Lut3d, title: &str, comments: &[String]) -> String {
+    let size = lut.size();
+    let mut out = String::with_capacity(30 * size * size * size + 200);
+    for comment in comments {
+        for line in comment.lines() {
+            out.push_str(&format!("# {}\n", to_ascii(line)));
+        }
+    }
+    // The title is a quoted string, so it must not contain quotes itself.
+    out.push_str(&format!(
+        "TITLE \"{}\"\n",
+        to_ascii(title).replace('"', "'")
+    ));
+    out.push_str(&format!("LUT_3D_SIZE {size}\n"));
+    let [r0, g0, b0] = lut.domain_min();
+    let [r1, g1, b1] = lut.domain_max();
+    out.push_str(&format!("DOMAIN_MIN {r0:?} {g0:?} {b0:?}\n"));
+    out.push_str(&format!("DOMAIN_MAX {r1:?} {g1:?} {b1:?}\n"));
+    for rgb in lut.values().chunks_exact(3) {
+        out.push_str(&format!("{:.6} {:.6} {:.6}\n", rgb[0], rgb[1], rgb[2]));
+    }
+    out
+}
+
+/// `text` with degree signs written as "deg" and other characters outside
+/// of ASCII as "?".
+fn to_ascii(text: &str) -> String {
+    text.replace('°', " deg")
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '?' })
+        .collect()
+}
+
+/// Compute a LUT of `transform` with `size` nodes per axis, from 2 to
+/// [MAX_CUBE_SIZE], and write it in `.cube` format, with the transform name
+/// and parameters in the header.
+///
+/// Applications interpolate between the nodes, so the LUT approximates the
+/// transform, which the header says as well.
+pub fn transform_to_cube(transform: &dyn ColorTransform, size: usize) -> String {
+    let size = size.clamp(2, MAX_CUBE_SIZE);
+    let lut = Lut3d::from_transform(transform, size);
+    let mut comments = vec![format!("Created by hnb from {}", transform.name())];
+    comments.extend(
+        transform
+            .parameters()
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.value)),
+    );
+    comments.push(format!(
+        "Sampled at {size} nodes per axis and interpolated in between, this \
+        only approximates the transform as computed by hnb."
+    ));
+    let title = format!("{} ({})", transform.name(), transform.summary());
+    write_cube(&lut, &title, &comments)
+}
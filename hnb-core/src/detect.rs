@@ -0,0 +1,253 @@
+//! Automatic detection of the liquid in reaction tubes, strips or wells.
+//!
+//! The image is reduced in size, pixels with the colors of HNB (blue to
+//! violet with some chroma) are selected, the selection is cleaned up with a
+//! morphological opening and closing, and connected regions of a plausible
+//! size become regions of interest. These are ordered like text: rows from
+//! top to bottom, and left to right within each row.
+
+use crate::colorspace::{lab_to_lch, linear_to_lab, srgb8_to_linear};
+use crate::image::{RgbaImage, BYTES_PER_PIXEL};
+use crate::roi::{Roi, Shape};
+
+/// Settings of [detect_tubes].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectionParams {
+    /// The minimum CIELAB chroma C* of liquid pixels.
+    pub min_chroma: f32,
+    /// The range of CIELAB hue angles, in degrees, of liquid pixels. The range
+    /// may wrap around 360.
+    pub hue_range: (f32, f32),
+    /// The range of CIELAB lightness L* of liquid pixels, to skip shadows and
+    /// highlights.
+    pub lightness_range: (f32, f32),
+    /// Regions smaller than this fraction of the image are ignored.
+    pub min_area: f32,
+    /// Regions larger than this fraction of the image are ignored.
+    pub max_area: f32,
+    /// The size of the region of interest relative to the bounding box of the
+    /// detected region. Less than 1 keeps the edges of the liquid, with
+    /// reflections and the tube wall, out of the measurement.
+    pub inset: f32,
+    /// The image is reduced so that its larger side has at most this many
+    /// pixels before detection.
+    pub working_size: u32,
+}
+
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            min_chroma: 12.0,
+            // Sky blue (positive) to violet (negative) HNB.
+            hue_range: (200.0, 350.0),
+            lightness_range: (10.0, 95.0),
+            min_area: 0.000_2,
+            max_area: 0.1,
+            inset: 0.6,
+            working_size: 512,
+        }
+    }
+}
+
+impl DetectionParams {
+    fn is_liquid(&self, rgb: [u8; 3]) -> bool {
+        let [l, c, h] = lab_to_lch(linear_to_lab(srgb8_to_linear(rgb)));
+        let (h0, h1) = self.hue_range;
+        let in_hue = if h0 <= h1 {
+            (h0..=h1).contains(&h)
+        } else {
+            h >= h0 || h <= h1
+        };
+        c >= self.min_chroma
+            && in_hue
+            && (self.lightness_range.0..=self.lightness_range.1).contains(&l)
+    }
+}
+
+/// A binary image.
+struct Mask {
+    width: usize,
+    height: usize,
+    data: Vec<bool>,
+}
+
+impl Mask {
+    fn get(&self, x: isize, y: isize) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.data[y as usize * self.width + x as usize]
+    }
+
+    /// Keep pixels whose 3x3 neighborhood is all set (`all`) or set pixels
+    /// with any neighbor set (`!all`), i.e. erosion or dilation.
+    fn filter3(&self, all: bool) -> Mask {
+        let mut data = vec![false; self.data.len()];
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let mut neighbors = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)));
+                data[y as usize * self.width + x as usize] = if all {
+                    neighbors.all(|(dx, dy)| self.get(x + dx, y + dy))
+                } else {
+                    neighbors.any(|(dx, dy)| self.get(x + dx, y + dy))
+                };
+            }
+        }
+        Mask {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /// Remove specks (opening), then fill small holes (closing).
+    fn clean(&self) -> Mask {
+        self.filter3(true)
+            .filter3(false)
+            .filter3(false)
+            .filter3(true)
+    }
+
+    /// The 8-connected components, each as the list of its pixel indices.
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.data.len()];
+        let mut result = Vec::new();
+        for start in 0..self.data.len() {
+            if !self.data[start] || seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let mut pixels = Vec::new();
+            while let Some(i) = stack.pop() {
+                pixels.push(i);
+                let (x, y) = ((i % self.width) as isize, (i / self.width) as isize);
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if self.get(nx, ny) {
+                            let j = ny as usize * self.width + nx as usize;
+                            if !seen[j] {
+                                seen[j] = true;
+                                stack.push(j);
+                            }
+                        }
+                    }
+                }
+            }
+            result.push(pixels);
+        }
+        result
+    }
+}
+
+/// A region found in the reduced image, in full image coordinates.
+struct Blob {
+    center: (f32, f32),
+    bounds: (f32, f32, f32, f32),
+}
+
+/// Find the liquid in tubes or wells and return them as regions of interest
+/// labelled "Tube 1", "Tube 2", ... in reading order.
+pub fn detect_tubes(image: &RgbaImage, params: &DetectionParams) -> Vec<Roi> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let (reduced, factor) = image.block_average(params.working_size);
+    let (sw, sh) = (reduced.width() as usize, reduced.height() as usize);
+    let mask = Mask {
+        width: sw,
+        height: sh,
+        data: reduced
+            .as_raw()
+            .chunks_exact(BYTES_PER_PIXEL)
+            .map(|p| params.is_liquid([p[0], p[1], p[2]]))
+            .collect(),
+    }
+    .clean();
+
+    let scale = factor as f32;
+    let total = (sw * sh) as f32;
+    let mut blobs: Vec<Blob> = mask
+        .components()
+        .into_iter()
+        .filter(|pixels| {
+            let area = pixels.len() as f32 / total;
+            params.min_area <= area && area <= params.max_area
+        })
+        .map(|pixels| {
+            let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0, 0);
+            let (mut sx, mut sy) = (0.0, 0.0);
+            for &i in pixels.iter() {
+                let (x, y) = (i % sw, i / sw);
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x + 1);
+                y1 = y1.max(y + 1);
+                sx += x as f32 + 0.5;
+                sy += y as f32 + 0.5;
+            }
+            let n = pixels.len() as f32;
+            Blob {
+                center: (sx / n * scale, sy / n * scale),
+                bounds: (
+                    x0 as f32 * scale,
+                    y0 as f32 * scale,
+                    (x1 as f32 * scale).min(width as f32),
+                    (y1 as f32 * scale).min(height as f32),
+                ),
+            }
+        })
+        .collect();
+
+    sort_reading_order(&mut blobs);
+    blobs
+        .iter()
+        .enumerate()
+        .map(|(i, blob)| {
+            let (cx, cy) = blob.center;
+            let (x0, y0, x1, y1) = blob.bounds;
+            let (hw, hh) = (
+                (x1 - x0) * params.inset / 2.0,
+                (y1 - y0) * params.inset / 2.0,
+            );
+            Roi::new(
+                format!("Tube {}", i + 1),
+                Shape::Rect {
+                    x0: cx - hw,
+                    y0: cy - hh,
+                    x1: cx + hw,
+                    y1: cy + hh,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Sort into rows, from top to bottom, and each row from left to right.
+///
+/// A region starts a new row if its center is lower than the center of the
+/// first region of the current row by more than half the median height.
+fn sort_reading_order(blobs: &mut Vec<Blob>) {
+    if blobs.is_empty() {
+        return;
+    }
+    let mut heights: Vec<f32> = blobs.iter().map(|b| b.bounds.3 - b.bounds.1).collect();
+    heights.sort_by(f32::total_cmp);
+    let row_gap = heights[heights.len() / 2] / 2.0;
+
+    blobs.sort_by(|a, b| a.center.1.total_cmp(&b.center.1));
+    let mut rows: Vec<Vec<Blob>> = Vec::new();
+    for blob in blobs.drain(..) {
+        match rows.last_mut() {
+            Some(row) if blob.center.1 - row[0].center.1 <= row_gap => row.push(blob),
+            _ => rows.push(vec![blob]),
+        }
+    }
+    for mut row in rows {
+        row.sort_by(|a, b| a.center.0.total_cmp(&b.center.0));
+        blobs.extend(row);
+    }
+}
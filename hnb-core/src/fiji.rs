@@ -0,0 +1,68 @@
+//! Transcription of the color transformation of FIJI's Color Inspector 3D.
+//!
+//! The Color Inspector 3D plugin by Kai Uwe Barthel changes "Color Rotation"
+//! and "Saturation" in YUV space: the chroma vector (U, V) is rotated by the
+//! hue angle and scaled by the saturation factor while the luma Y is kept. All
+//! arithmetic is done in Java `double` precision directly on the 8 bit sRGB
+//! values (no linearization) and converted back with an `(int)` cast of the
+//! value plus 0.5, clamped to 0..=255.
+//!
+//! Unlike [crate::transform_colors::saturate_and_rotate], which was tuned to
+//! match the plugin visually, this transcribes the formulas of the Java code.
+//! It is not verified to give the same 8 bit values as FIJI: the YUV
+//! constants, the rounding and the clamp are taken from the source linked
+//! below but have not been checked against images saved from FIJI, as the
+//! regression corpus in `tests/golden` has no such references yet.
+//!
+//! See
+//! https://github.com/erisir/FIJI/blob/a30ce62566b7a441bc315c8fff365b9985779b27/src-plugins/Color_Inspector_3D/src/main/java/Color_Inspector_3D.java#L4391-L4472
+
+/// Java's `(int)` cast of a `double`: truncation toward zero, saturating.
+#[inline]
+fn java_int(v: f64) -> i32 {
+    v as i32
+}
+
+/// Clamp a cast value to 8 bits.
+///
+/// Truncating toward zero only differs from rounding down for values below
+/// 0, which become 0 either way, so the result does not depend on whether
+/// the value is clamped before or after the `(int)` cast.
+#[inline]
+fn clamp_u8(v: i32) -> u8 {
+    v.clamp(0, 255) as u8
+}
+
+/// Rotate the chroma by `degrees` and multiply it by `saturation`, as done by
+/// Color Inspector 3D.
+///
+/// Operates on raw RGBA pixel buffer. Alpha is left unchanged.
+pub fn color_inspector_rotate(data: &mut [u8], degrees: f32, saturation: f32) {
+    let angle = (degrees as f64).to_radians();
+    let cos = angle.cos() * saturation as f64;
+    let sin = angle.sin() * saturation as f64;
+
+    for pix in data.chunks_exact_mut(4) {
+        let r = pix[0] as f64;
+        let g = pix[1] as f64;
+        let b = pix[2] as f64;
+
+        // RGB -> YUV
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let u = (b - y) * 0.492;
+        let v = (r - y) * 0.877;
+
+        // Rotate and scale the chroma.
+        let u_ = cos * u - sin * v;
+        let v_ = sin * u + cos * v;
+
+        // YUV -> RGB
+        let r_ = y + v_ / 0.877;
+        let b_ = y + u_ / 0.492;
+        let g_ = (y - 0.299 * r_ - 0.114 * b_) / 0.587;
+
+        pix[0] = clamp_u8(java_int(r_ + 0.5));
+        pix[1] = clamp_u8(java_int(g_ + 0.5));
+        pix[2] = clamp_u8(java_int(b_ + 0.5));
+    }
+}
@@ -0,0 +1,66 @@
+//! The distribution of the hues and chromas of an image, for plots showing
+//! how a transform moves the colors, e.g. how Color Stretch pulls the violet
+//! and sky blue of HNB apart.
+//!
+//! Hue, saturation and chroma are those of HSL computed from the encoded
+//! sRGB values, as in the legacy pipeline of the transforms. The chroma is
+//! the difference of the largest and smallest channel, from 0 to 1.
+
+use crate::image::BYTES_PER_PIXEL;
+use crate::transform_colors::rgb_to_hsl;
+
+/// Number of hue bins of [HueChroma::hue_histogram], of 2 degrees each.
+pub const HUE_BINS: usize = 180;
+
+/// Number of hue and chroma bins of [HueChroma::density].
+pub const DENSITY_HUE_BINS: usize = 72;
+pub const DENSITY_CHROMA_BINS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HueChroma {
+    /// The sum of the HSL saturations of the pixels in each hue bin, from 0
+    /// degrees. Gray pixels do not count.
+    pub hue_histogram: Vec<f32>,
+    /// The number of pixels in each bin of hue and chroma, row by row of
+    /// chroma bins from 0, with [DENSITY_HUE_BINS] hue bins from 0 degrees
+    /// per row.
+    pub density: Vec<f32>,
+    /// The number of pixels counted.
+    pub n_pixels: usize,
+}
+
+impl HueChroma {
+    /// The distribution of the RGBA `pixels`, of at most `max_pixels` evenly
+    /// spaced ones.
+    pub fn of_pixels(pixels: &[u8], max_pixels: usize) -> Self {
+        let n = pixels.len() / BYTES_PER_PIXEL;
+        let step = n.div_ceil(max_pixels.max(1)).max(1);
+        let mut hue_histogram = vec![0.0; HUE_BINS];
+        let mut density = vec![0.0; DENSITY_HUE_BINS * DENSITY_CHROMA_BINS];
+        let mut n_pixels = 0;
+        for pix in pixels.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            n_pixels += 1;
+            let [r, g, b] = [0, 1, 2].map(|c| pix[c] as f32 / 255.0);
+            let (h, s, _) = rgb_to_hsl(r, g, b);
+            let chroma = r.max(g).max(b) - r.min(g).min(b);
+            let bin = |degrees: f32, bins: usize| {
+                ((degrees / 360.0 * bins as f32) as usize).min(bins - 1)
+            };
+            if s > 0.0 {
+                hue_histogram[bin(h, HUE_BINS)] += s;
+            }
+            let c = ((chroma * DENSITY_CHROMA_BINS as f32) as usize).min(DENSITY_CHROMA_BINS - 1);
+            density[c * DENSITY_HUE_BINS + bin(h, DENSITY_HUE_BINS)] += 1.0;
+        }
+        Self {
+            hue_histogram,
+            density,
+            n_pixels,
+        }
+    }
+
+    /// The number of pixels in the bin of hue `h` and chroma `c`.
+    pub fn density_at(&self, h: usize, c: usize) -> f32 {
+        self.density[c * DENSITY_HUE_BINS + h]
+    }
+}
@@ -0,0 +1,149 @@
+//! Owned image types.
+
+use crate::color_transform::ColorTransform;
+
+/// Number of bytes per RGBA pixel.
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Errors creating images.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageError {
+    /// The length of the pixel buffer does not match the image dimensions.
+    BufferSize { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::BufferSize { expected, actual } => write!(
+                f,
+                "pixel buffer has {actual} bytes but the image dimensions need {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// An 8 bit RGBA image with row-major, tightly packed pixels.
+///
+/// This has the same memory layout as the data of a browser `ImageData`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Create a new, fully transparent black image.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize * BYTES_PER_PIXEL;
+        Self {
+            width,
+            height,
+            data: vec![0; len],
+        }
+    }
+
+    /// Create an image from a raw RGBA buffer.
+    pub fn from_raw(width: u32, height: u32, data: Vec<u8>) -> Result<Self, ImageError> {
+        let expected = width as usize * height as usize * BYTES_PER_PIXEL;
+        if data.len() != expected {
+            return Err(ImageError::BufferSize {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The raw RGBA buffer.
+    pub fn as_raw(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The raw RGBA buffer, mutable.
+    pub fn as_raw_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Consume the image and return the raw RGBA buffer.
+    pub fn into_raw(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The RGBA value of the pixel at column `x` and row `y`.
+    ///
+    /// Panics if the coordinates are outside the image.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(x < self.width && y < self.height);
+        let i = (y as usize * self.width as usize + x as usize) * BYTES_PER_PIXEL;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    /// Reduce the image so that its larger side has at most `max_size`
+    /// pixels, by averaging blocks of `factor` x `factor` pixels. Returns the
+    /// reduced image, which is opaque, and the factor.
+    pub(crate) fn block_average(&self, max_size: u32) -> (Self, usize) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let factor = width.max(height).div_ceil(max_size.max(1) as usize).max(1);
+        let (sw, sh) = (width.div_ceil(factor), height.div_ceil(factor));
+        let mut sums = vec![[0u32; 4]; sw * sh];
+        for (i, pix) in self.data.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+            let (x, y) = (i % width / factor, i / width / factor);
+            let sum = &mut sums[y * sw + x];
+            for c in 0..3 {
+                sum[c] += pix[c] as u32;
+            }
+            sum[3] += 1;
+        }
+        let data = sums
+            .iter()
+            .flat_map(|s| {
+                let mean = |c: usize| (s[c] / s[3].max(1)) as u8;
+                [mean(0), mean(1), mean(2), 255]
+            })
+            .collect();
+        let reduced = Self {
+            width: sw as u32,
+            height: sh as u32,
+            data,
+        };
+        (reduced, factor)
+    }
+
+    /// Return a copy of this image with `transform` applied.
+    pub fn transformed(&self, transform: &dyn ColorTransform) -> Self {
+        let mut result = self.clone();
+        transform.apply(&mut result.data);
+        result
+    }
+}
+
+/// The file name, without extension, for the output of view `slug` applied to
+/// the input file `fname`, e.g. `plate-rotated` for `plate.jpg`.
+pub fn output_basename(fname: &str, slug: &str) -> String {
+    let stem = std::path::Path::new(fname)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(fname);
+    format!("{stem}-{slug}")
+}
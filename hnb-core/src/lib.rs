@@ -0,0 +1,27 @@
+//! Platform independent image processing for digital image based colorimetry.
+//!
+//! Everything here operates on raw RGBA pixel buffers (`&mut [u8]` with four
+//! bytes per pixel) or on [image::RgbaImage] and does not depend on a browser,
+//! so the exact algorithms used by the web app can be tested, benchmarked and
+//! reused natively.
+
+pub mod autotune;
+pub mod calibration;
+pub mod chromaticity;
+pub mod classify;
+pub mod color_transform;
+pub mod colorspace;
+pub mod cube;
+pub mod detect;
+pub mod fiji;
+pub mod hue_chroma;
+pub mod image;
+pub mod lut;
+pub mod optimize;
+pub mod perceptual;
+pub mod point_cloud;
+pub mod roi;
+pub mod simd;
+pub mod stats;
+pub mod transform_colors;
+pub mod white_balance;
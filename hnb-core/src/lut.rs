@@ -0,0 +1,412 @@
+//! Precomputed 3D lookup tables for color transforms.
+//!
+//! Every [ColorTransform] maps an 8 bit RGB value to another, independently of
+//! the other pixels. So instead of doing the (often trigonometric) color math
+//! for the millions of pixels of a photo, the transform can be evaluated once
+//! for the nodes of a 3D grid and then applied to the image with a fast
+//! lookup. A [Lut3d] on a coarse grid (e.g. 33 or 65 nodes per axis) is
+//! interpolated between nodes, a [FullLut] stores all 256³ colors and gives
+//! exactly the same result as applying the transform directly.
+
+use crate::color_transform::ColorTransform;
+
+/// How values between the nodes of a [Lut3d] are computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    Trilinear,
+    Tetrahedral,
+}
+
+/// How a transform is applied to the pixels of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Processing {
+    /// Evaluate the transform for every pixel.
+    Direct,
+    /// Interpolate in a [Lut3d] with `size` nodes per axis.
+    Grid {
+        size: u16,
+        interpolation: Interpolation,
+    },
+    /// Look up every color in a [FullLut].
+    Full,
+}
+
+/// Every pixel is evaluated by default, as the grids are approximations:
+/// near grays, hue transforms are off by tens of levels.
+impl Default for Processing {
+    fn default() -> Self {
+        Processing::Direct
+    }
+}
+
+impl std::fmt::Display for Processing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Processing::Direct => write!(f, "direct (every pixel)"),
+            Processing::Grid {
+                size,
+                interpolation,
+            } => {
+                let interp = match interpolation {
+                    Interpolation::Trilinear => "trilinear",
+                    Interpolation::Tetrahedral => "tetrahedral",
+                };
+                write!(f, "{size}³ LUT, {interp}")
+            }
+            Processing::Full => write!(f, "256³ LUT (exact)"),
+        }
+    }
+}
+
+/// A 3D lookup table with `size` nodes per axis.
+///
+/// The nodes span the input domain, 0..=1 unless set otherwise with
+/// [Lut3d::with_domain]. The output values are RGB in 0..=1. The nodes are
+/// ordered with red changing fastest, then green, then blue, as in `.cube`
+/// files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    /// Three values per node.
+    values: Vec<f32>,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl Lut3d {
+    /// Create a LUT from node values. `values` must hold `3 * size³` values.
+    ///
+    /// Panics if `size` is smaller than 2 or `values` has the wrong length.
+    pub fn from_values(size: usize, values: Vec<f32>) -> Self {
+        assert!(size >= 2, "a LUT needs at least 2 nodes per axis");
+        assert_eq!(values.len(), 3 * size * size * size);
+        Self {
+            size,
+            values,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        }
+    }
+
+    /// Set the input values of the first and last node of each axis. Inputs
+    /// outside of the domain are clamped to it.
+    ///
+    /// Panics unless `min < max` for all channels.
+    pub fn with_domain(mut self, min: [f32; 3], max: [f32; 3]) -> Self {
+        assert!((0..3).all(|c| min[c] < max[c]), "empty LUT domain");
+        self.domain_min = min;
+        self.domain_max = max;
+        self
+    }
+
+    /// Evaluate `transform` at the nodes of a grid with `size` nodes per axis.
+    ///
+    /// Node coordinates are rounded to the nearest 8 bit value, as the
+    /// transforms operate on 8 bit images.
+    pub fn from_transform(transform: &dyn ColorTransform, size: usize) -> Self {
+        assert!((2..=256).contains(&size));
+        let level = |i: usize| ((i * 255) as f32 / (size - 1) as f32).round() as u8;
+        let mut data = Vec::with_capacity(4 * size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.extend_from_slice(&[level(r), level(g), level(b), 255]);
+                }
+            }
+        }
+        transform.apply(&mut data);
+        let values = data
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .map(|v| v as f32 / 255.0)
+            .collect();
+        Self::from_values(size, values)
+    }
+
+    /// Number of nodes per axis.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Output values of all nodes, three per node, red changing fastest.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// Input values of the first node of each axis.
+    pub fn domain_min(&self) -> [f32; 3] {
+        self.domain_min
+    }
+
+    /// Input values of the last node of each axis.
+    pub fn domain_max(&self) -> [f32; 3] {
+        self.domain_max
+    }
+
+    /// Output RGB value of the node with the given indices.
+    #[inline]
+    pub fn node(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        let i = 3 * ((b * self.size + g) * self.size + r);
+        [self.values[i], self.values[i + 1], self.values[i + 2]]
+    }
+
+    /// For every 8 bit value of channel `c`, the index of the lower node and
+    /// the fraction of the distance to the next node.
+    fn node_table(&self, c: usize) -> Vec<(usize, f32)> {
+        let max = (self.size - 1) as f32;
+        let (lo, hi) = (self.domain_min[c], self.domain_max[c]);
+        (0..256)
+            .map(|v| {
+                let x = ((v as f32 / 255.0 - lo) / (hi - lo)).clamp(0.0, 1.0);
+                let pos = x * max;
+                let i = (pos.floor() as usize).min(self.size - 2);
+                (i, pos - i as f32)
+            })
+            .collect()
+    }
+
+    /// Apply the LUT to a raw RGBA pixel buffer. Alpha is left unchanged.
+    pub fn apply(&self, data: &mut [u8], interpolation: Interpolation) {
+        let tables = [self.node_table(0), self.node_table(1), self.node_table(2)];
+        // Offsets, in values, to the neighboring nodes along each axis.
+        let dr = 3;
+        let dg = 3 * self.size;
+        let db = 3 * self.size * self.size;
+        let v = &self.values;
+        for pix in data.chunks_exact_mut(4) {
+            let (ir, fr) = tables[0][pix[0] as usize];
+            let (ig, fg) = tables[1][pix[1] as usize];
+            let (ib, fb) = tables[2][pix[2] as usize];
+            let base = 3 * ((ib * self.size + ig) * self.size + ir);
+            for c in 0..3 {
+                let at = |offset: usize| v[base + offset + c];
+                let c000 = at(0);
+                let c111 = at(dr + dg + db);
+                let out = match interpolation {
+                    Interpolation::Tetrahedral => {
+                        if fr > fg {
+                            if fg > fb {
+                                (1.0 - fr) * c000
+                                    + (fr - fg) * at(dr)
+                                    + (fg - fb) * at(dr + dg)
+                                    + fb * c111
+                            } else if fr > fb {
+                                (1.0 - fr) * c000
+                                    + (fr - fb) * at(dr)
+                                    + (fb - fg) * at(dr + db)
+                                    + fg * c111
+                            } else {
+                                (1.0 - fb) * c000
+                                    + (fb - fr) * at(db)
+                                    + (fr - fg) * at(dr + db)
+                                    + fg * c111
+                            }
+                        } else if fb > fg {
+                            (1.0 - fb) * c000
+                                + (fb - fg) * at(db)
+                                + (fg - fr) * at(dg + db)
+                                + fr * c111
+                        } else if fb > fr {
+                            (1.0 - fg) * c000
+                                + (fg - fb) * at(dg)
+                                + (fb - fr) * at(dg + db)
+                                + fr * c111
+                        } else {
+                            (1.0 - fg) * c000
+                                + (fg - fr) * at(dg)
+                                + (fr - fb) * at(dr + dg)
+                                + fb * c111
+                        }
+                    }
+                    Interpolation::Trilinear => {
+                        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+                        let c00 = lerp(c000, at(dr), fr);
+                        let c10 = lerp(at(dg), at(dr + dg), fr);
+                        let c01 = lerp(at(db), at(dr + db), fr);
+                        let c11 = lerp(at(dg + db), c111, fr);
+                        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+                    }
+                };
+                pix[c] = (out * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// A lookup table with an entry for each of the 256³ 8 bit RGB colors.
+///
+/// This needs 48 MiB of memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FullLut {
+    /// Three bytes per color, indexed by `(b << 16) | (g << 8) | r`.
+    values: Vec<u8>,
+}
+
+impl FullLut {
+    pub fn from_transform(transform: &dyn ColorTransform) -> Self {
+        let mut data = Vec::with_capacity(4 << 24);
+        for b in 0..=255u8 {
+            for g in 0..=255u8 {
+                for r in 0..=255u8 {
+                    data.extend_from_slice(&[r, g, b, 255]);
+                }
+            }
+        }
+        transform.apply(&mut data);
+        let values = data
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+        Self { values }
+    }
+
+    /// Apply the LUT to a raw RGBA pixel buffer. Alpha is left unchanged.
+    pub fn apply(&self, data: &mut [u8]) {
+        for pix in data.chunks_exact_mut(4) {
+            let i = 3 * ((pix[2] as usize) << 16 | (pix[1] as usize) << 8 | pix[0] as usize);
+            pix[..3].copy_from_slice(&self.values[i..i + 3]);
+        }
+    }
+}
+
+/// A transform compiled to a lookup table.
+enum Compiled {
+    Grid(Lut3d, Interpolation),
+    Full(FullLut),
+}
+
+impl Compiled {
+    fn n_bytes(&self) -> usize {
+        match self {
+            Compiled::Grid(lut, _) => lut.values.len() * std::mem::size_of::<f32>(),
+            Compiled::Full(lut) => lut.values.len(),
+        }
+    }
+
+    fn apply(&self, data: &mut [u8]) {
+        match self {
+            Compiled::Grid(lut, interpolation) => lut.apply(data, *interpolation),
+            Compiled::Full(lut) => lut.apply(data),
+        }
+    }
+}
+
+struct CacheEntry {
+    key: String,
+    compiled: Compiled,
+    last_used: u64,
+}
+
+/// Lookup tables of recently used transforms and parameter sets.
+///
+/// The least recently used tables are dropped when the total size exceeds a
+/// memory budget.
+pub struct LutCache {
+    entries: Vec<CacheEntry>,
+    max_bytes: usize,
+    clock: u64,
+}
+
+impl Default for LutCache {
+    /// A cache holding up to 256 MiB of tables.
+    fn default() -> Self {
+        Self::new(256 << 20)
+    }
+}
+
+impl LutCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            max_bytes,
+            clock: 0,
+        }
+    }
+
+    /// Apply `transform` to a raw RGBA pixel buffer as set by `processing`,
+    /// building and caching a lookup table if needed.
+    pub fn apply(
+        &mut self,
+        transform: &dyn ColorTransform,
+        processing: Processing,
+        data: &mut [u8],
+    ) {
+        if transform.lookup_table().is_some() {
+            // Nothing to gain, and resampling would lose accuracy.
+            transform.apply(data);
+            return;
+        }
+        let key = match processing {
+            Processing::Direct => {
+                transform.apply(data);
+                return;
+            }
+            Processing::Grid { .. } if transform.requires_direct() => {
+                transform.apply(data);
+                return;
+            }
+            Processing::Grid {
+                size,
+                interpolation,
+            } => format!("{}|{size}|{interpolation:?}", transform.cache_key()),
+            Processing::Full => format!("{}|full", transform.cache_key()),
+        };
+        self.clock += 1;
+
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.key == key) {
+            entry.last_used = self.clock;
+            entry.compiled.apply(data);
+            return;
+        }
+
+        let compiled = match processing {
+            Processing::Grid {
+                size,
+                interpolation,
+            } => Compiled::Grid(
+                Lut3d::from_transform(transform, size as usize),
+                interpolation,
+            ),
+            Processing::Full => Compiled::Full(FullLut::from_transform(transform)),
+            Processing::Direct => unreachable!(),
+        };
+        compiled.apply(data);
+        self.entries.push(CacheEntry {
+            key,
+            compiled,
+            last_used: self.clock,
+        });
+        self.evict();
+    }
+
+    /// Drop least recently used entries until within the memory budget. The
+    /// most recently used entry is always kept.
+    fn evict(&mut self) {
+        while self.entries.len() > 1
+            && self
+                .entries
+                .iter()
+                .map(|e| e.compiled.n_bytes())
+                .sum::<usize>()
+                > self.max_bytes
+        {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(i, _)| i)
+                .unwrap();
+            self.entries.remove(oldest);
+        }
+    }
+
+    /// Number of cached tables.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,201 @@
+//! Tuning Color Stretch to separate positive from negative reactions.
+//!
+//! Given pixels of regions with known results, e.g. the controls of one or
+//! more images, [optimize_stretch] searches the stretch center, radius and
+//! saturation gain for which the two groups are furthest apart in CIELAB
+//! relative to the spread of their colors, as measured by [separation].
+
+use crate::classify::Control;
+use crate::color_transform::{ColorStretch, ColorTransform};
+use crate::colorspace::{linear_to_lab, srgb8_to_linear};
+use crate::image::BYTES_PER_PIXEL;
+
+/// The RGBA pixels of a region with a known result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelledPixels {
+    pub control: Control,
+    pub pixels: Vec<u8>,
+}
+
+/// At most `max_pixels` pixels of the RGBA buffer `pixels`, evenly spaced.
+pub fn subsample(pixels: &[u8], max_pixels: usize) -> Vec<u8> {
+    let n = pixels.len() / BYTES_PER_PIXEL;
+    if n <= max_pixels {
+        return pixels.to_vec();
+    }
+    (0..max_pixels)
+        .flat_map(|i| {
+            let j = i * n / max_pixels * BYTES_PER_PIXEL;
+            pixels[j..j + BYTES_PER_PIXEL].iter().copied()
+        })
+        .collect()
+}
+
+/// The pixels of all regions of each group, ready to be transformed.
+struct Groups {
+    positive: Vec<u8>,
+    negative: Vec<u8>,
+}
+
+impl Groups {
+    fn new(samples: &[LabelledPixels]) -> Option<Self> {
+        let group = |control| {
+            samples
+                .iter()
+                .filter(|s| s.control == control)
+                .flat_map(|s| s.pixels.iter().copied())
+                .collect::<Vec<u8>>()
+        };
+        let groups = Self {
+            positive: group(Control::Positive),
+            negative: group(Control::Negative),
+        };
+        if groups.positive.len() < BYTES_PER_PIXEL || groups.negative.len() < BYTES_PER_PIXEL {
+            return None;
+        }
+        Some(groups)
+    }
+
+    fn separation(&self, transform: &dyn ColorTransform) -> f32 {
+        let p = LabMoments::of_transformed(&self.positive, transform);
+        let n = LabMoments::of_transformed(&self.negative, transform);
+        let d2: f32 = (0..3).map(|i| (p.mean[i] - n.mean[i]).powi(2)).sum();
+        (d2 / (p.variance + n.variance).max(1e-6)).sqrt()
+    }
+}
+
+/// Mean and total variance of CIELAB colors.
+struct LabMoments {
+    mean: [f32; 3],
+    /// Sum of the variances of L*, a* and b*.
+    variance: f32,
+}
+
+impl LabMoments {
+    fn of_transformed(pixels: &[u8], transform: &dyn ColorTransform) -> Self {
+        let mut data = pixels.to_vec();
+        transform.apply(&mut data);
+        let mut sum = [0.0f64; 3];
+        let mut sum2 = 0.0f64;
+        for pix in data.chunks_exact(BYTES_PER_PIXEL) {
+            let lab = linear_to_lab(srgb8_to_linear([pix[0], pix[1], pix[2]]));
+            for (s, v) in sum.iter_mut().zip(lab) {
+                *s += v as f64;
+            }
+            sum2 += lab.iter().map(|&v| (v as f64).powi(2)).sum::<f64>();
+        }
+        let n = (data.len() / BYTES_PER_PIXEL) as f64;
+        let mean = sum.map(|s| s / n);
+        let variance = sum2 / n - mean.iter().map(|m| m * m).sum::<f64>();
+        Self {
+            mean: mean.map(|m| m as f32),
+            variance: variance.max(0.0) as f32,
+        }
+    }
+}
+
+/// How well `transform` separates the positive from the negative samples:
+/// the CIELAB distance between the mean colors of the two groups divided by
+/// the root of the summed variances of their pixels. `None` without samples
+/// of both kinds.
+pub fn separation(transform: &dyn ColorTransform, samples: &[LabelledPixels]) -> Option<f32> {
+    Some(Groups::new(samples)?.separation(transform))
+}
+
+/// The result of [optimize_stretch].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StretchFit {
+    pub transform: ColorStretch,
+    /// The [separation] with the optimized parameters.
+    pub separation: f32,
+    /// The [separation] with the starting parameters.
+    pub initial_separation: f32,
+}
+
+/// Lower and upper limits of the radius and the saturation gain in the
+/// search. Without saturation, hues would be irrelevant.
+const RADIUS_RANGE: (f32, f32) = (0.0, 0.95);
+const SATURATION_RANGE: (f32, f32) = (1.0, 10.0);
+
+/// Limit of the number of steps of the pattern search.
+const MAX_PATTERN_ITERATIONS: usize = 200;
+
+/// Find the center hue, radius and saturation gain of Color Stretch which
+/// maximize the [separation] of the samples, keeping the pipeline of
+/// `start`. `None` without samples of both kinds.
+///
+/// A coarse grid is searched first, then the best point is refined by a
+/// pattern search with shrinking steps. The result is never worse than
+/// `start`. To be fast enough to run interactively, regions should be
+/// [subsample]d to a few thousand pixels in total.
+pub fn optimize_stretch(start: &ColorStretch, samples: &[LabelledPixels]) -> Option<StretchFit> {
+    let groups = Groups::new(samples)?;
+    let with = |[center_hue, radius, saturation]: [f32; 3]| ColorStretch {
+        center_hue: center_hue.rem_euclid(1.0),
+        radius: radius.clamp(RADIUS_RANGE.0, RADIUS_RANGE.1),
+        saturation: saturation.clamp(SATURATION_RANGE.0, SATURATION_RANGE.1),
+        pipeline: start.pipeline,
+    };
+    let score = |x: [f32; 3]| groups.separation(&with(x));
+
+    let initial = [start.center_hue, start.radius, start.saturation];
+    let initial_separation = score(initial);
+    let (mut best, mut best_score) = (initial, initial_separation);
+    for i in 0..20 {
+        for radius in [0.2, 0.5, 0.7, 0.85, 0.95] {
+            for saturation in [1.0, 2.0, 4.0, 8.0] {
+                let x = [i as f32 / 20.0, radius, saturation];
+                let s = score(x);
+                if s > best_score {
+                    (best, best_score) = (x, s);
+                }
+            }
+        }
+    }
+
+    let mut steps = [0.025, 0.05, 1.0];
+    let min_steps = [0.001, 0.002, 0.02];
+    for _ in 0..MAX_PATTERN_ITERATIONS {
+        if steps.iter().zip(min_steps).all(|(s, min)| *s < min) {
+            break;
+        }
+        let mut improved = false;
+        for i in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut x = best;
+                x[i] += sign * steps[i];
+                let x = {
+                    let t = with(x);
+                    [t.center_hue, t.radius, t.saturation]
+                };
+                let s = score(x);
+                if s > best_score {
+                    (best, best_score) = (x, s);
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            for s in steps.iter_mut() {
+                *s /= 2.0;
+            }
+        }
+    }
+
+    // Round to the precision of the parameter inputs, unless that is worse
+    // than the start.
+    let rounded = [
+        (best[0] * 1000.0).round() / 1000.0,
+        (best[1] * 1000.0).round() / 1000.0,
+        (best[2] * 100.0).round() / 100.0,
+    ];
+    let rounded_score = score(rounded);
+    if rounded_score >= initial_separation {
+        (best, best_score) = (rounded, rounded_score);
+    }
+    Some(StretchFit {
+        transform: with(best),
+        separation: best_score,
+        initial_separation,
+    })
+}
@@ -0,0 +1,119 @@
+//! Hue rotation and stretch in the perceptual OKLCh and CIE LCh(ab) spaces.
+//!
+//! In contrast to HSL, these spaces are designed so that equal steps of hue
+//! and chroma look roughly equally large. Scaling the chroma easily leaves the
+//! sRGB gamut, so the chroma is reduced, keeping lightness and hue, to the
+//! largest value which can be displayed.
+
+use crate::colorspace::{
+    in_gamut, lab_to_lch, lab_to_linear, lch_to_lab, linear_to_lab, linear_to_oklab,
+    linear_to_srgb8, oklab_to_linear, srgb8_to_linear,
+};
+
+/// Number of bisection steps to find the largest in-gamut chroma.
+const GAMUT_STEPS: usize = 12;
+
+/// A cylindrical (lightness, chroma, hue) color space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LchSpace {
+    /// OKLCh, the cylindrical form of OKLab.
+    Oklch,
+    /// CIE LCh(ab), the cylindrical form of CIE L*a*b* (D65).
+    CieLch,
+}
+
+impl LchSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LchSpace::Oklch => "OKLCh",
+            LchSpace::CieLch => "CIE LCh",
+        }
+    }
+
+    /// Linear sRGB to (lightness, chroma, hue in degrees).
+    pub fn from_linear(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            LchSpace::Oklch => lab_to_lch(linear_to_oklab(rgb)),
+            LchSpace::CieLch => lab_to_lch(linear_to_lab(rgb)),
+        }
+    }
+
+    /// (lightness, chroma, hue in degrees) to linear sRGB.
+    pub fn to_linear(&self, lch: [f32; 3]) -> [f32; 3] {
+        match self {
+            LchSpace::Oklch => oklab_to_linear(lch_to_lab(lch)),
+            LchSpace::CieLch => lab_to_linear(lch_to_lab(lch)),
+        }
+    }
+
+    /// The hue angle, in degrees, of the blue used as the Color Stretch
+    /// center (HSL hue 0.6 at full saturation, sRGB 0, 102, 255).
+    pub fn default_center_hue(&self) -> f32 {
+        match self {
+            LchSpace::Oklch => 261.0,
+            LchSpace::CieLch => 293.0,
+        }
+    }
+
+    /// Convert to linear sRGB, reducing the chroma if needed to stay in gamut.
+    pub fn to_linear_in_gamut(&self, lch: [f32; 3]) -> [f32; 3] {
+        let rgb = self.to_linear(lch);
+        if in_gamut(rgb) {
+            return rgb;
+        }
+        let (mut lo, mut hi) = (0.0, lch[1]);
+        for _ in 0..GAMUT_STEPS {
+            let mid = (lo + hi) / 2.0;
+            if in_gamut(self.to_linear([lch[0], mid, lch[2]])) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        self.to_linear([lch[0], lo, lch[2]])
+    }
+}
+
+/// Apply `f` to the (lightness, chroma, hue) values of each pixel of an sRGB
+/// buffer. Alpha is left unchanged.
+fn map_lch<F>(data: &mut [u8], space: LchSpace, f: F)
+where
+    F: Fn([f32; 3]) -> [f32; 3],
+{
+    for pix in data.chunks_exact_mut(4) {
+        let lch = space.from_linear(srgb8_to_linear([pix[0], pix[1], pix[2]]));
+        let rgb = linear_to_srgb8(space.to_linear_in_gamut(f(lch)));
+        pix[..3].copy_from_slice(&rgb);
+    }
+}
+
+/// Rotate the hue by `degrees` and multiply the chroma by `chroma_gain`.
+///
+/// Operates on raw sRGB pixel buffer. The chroma is limited to the sRGB gamut.
+pub fn lch_rotate(data: &mut [u8], space: LchSpace, degrees: f32, chroma_gain: f32) {
+    map_lch(data, space, |[l, c, h]| [l, c * chroma_gain, h + degrees]);
+}
+
+/// Stretch hues away from a center and multiply the chroma by `chroma_gain`.
+///
+/// Operates on raw sRGB pixel buffer. This is the Color Stretch operation with
+/// the hue taken from `space` instead of HSL: hues are placed on a unit circle
+/// and the new hue is the angle as seen from a center point at distance
+/// `radius` from the origin in the direction of the hue angle `center_hue`
+/// (degrees). The chroma is limited to the sRGB gamut.
+pub fn lch_stretch(
+    data: &mut [u8],
+    space: LchSpace,
+    center_hue: f32,
+    radius: f32,
+    chroma_gain: f32,
+) {
+    let center = center_hue.to_radians();
+    let cx = radius * center.cos();
+    let cy = radius * center.sin();
+    map_lch(data, space, |[l, c, h]| {
+        let h = h.to_radians();
+        let new_h = (h.sin() - cy).atan2(h.cos() - cx).to_degrees();
+        [l, c * chroma_gain, new_h]
+    });
+}
@@ -0,0 +1,271 @@
+//! Clouds of the colors of an image in a 3D color space, drawn in software
+//! from a camera orbiting the space, as in the Color Inspector 3D plugin of
+//! FIJI.
+//!
+//! The positions are in a space of side 2 centered on the origin, with the
+//! lightness, or blue in RGB, along the vertical `z` axis. The points are
+//! drawn with an orthographic projection and a depth buffer.
+
+use std::f32::consts::TAU;
+
+use crate::colorspace::{linear_to_lab, linear_to_oklab, srgb8_to_linear};
+use crate::image::BYTES_PER_PIXEL;
+use crate::transform_colors::rgb_to_hsl;
+
+/// The a and b of CIE L*a*b* and OKLab at the edge of the space.
+const LAB_RANGE: f32 = 128.0;
+const OKLAB_RANGE: f32 = 0.32;
+
+/// Number of segments of the circles of the frames.
+const CIRCLE_SEGMENTS: usize = 48;
+
+/// The color space of a cloud.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CloudSpace {
+    /// The RGB cube, black at the bottom front left corner.
+    #[default]
+    Rgb,
+    /// The HSL cylinder, with the hue around and the saturation from the
+    /// axis.
+    Hsl,
+    /// CIE L*a*b*.
+    Lab,
+    /// OKLab.
+    Oklab,
+}
+
+impl CloudSpace {
+    pub const ALL: [CloudSpace; 4] = [
+        CloudSpace::Rgb,
+        CloudSpace::Hsl,
+        CloudSpace::Lab,
+        CloudSpace::Oklab,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CloudSpace::Rgb => "RGB",
+            CloudSpace::Hsl => "HSL",
+            CloudSpace::Lab => "L*a*b*",
+            CloudSpace::Oklab => "OKLab",
+        }
+    }
+
+    /// The position of an sRGB color.
+    pub fn position(&self, rgb: [u8; 3]) -> [f32; 3] {
+        let unit = |v: f32| 2.0 * v - 1.0;
+        match self {
+            CloudSpace::Rgb => rgb.map(|v| unit(v as f32 / 255.0)),
+            CloudSpace::Hsl => {
+                let [r, g, b] = rgb.map(|v| v as f32 / 255.0);
+                let (h, s, l) = rgb_to_hsl(r, g, b);
+                let (sin, cos) = h.to_radians().sin_cos();
+                [s * cos, s * sin, unit(l)]
+            }
+            CloudSpace::Lab => {
+                let [l, a, b] = linear_to_lab(srgb8_to_linear(rgb));
+                [a / LAB_RANGE, b / LAB_RANGE, unit(l / 100.0)]
+            }
+            CloudSpace::Oklab => {
+                let [l, a, b] = linear_to_oklab(srgb8_to_linear(rgb));
+                [a / OKLAB_RANGE, b / OKLAB_RANGE, unit(l)]
+            }
+        }
+    }
+
+    /// The line segments outlining the space.
+    pub fn frame(&self) -> Vec<[[f32; 3]; 2]> {
+        match self {
+            CloudSpace::Rgb => {
+                let mut edges = Vec::new();
+                for axis in 0..3 {
+                    for (u, v) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                        let mut start = [0.0; 3];
+                        let (i, j) = ((axis + 1) % 3, (axis + 2) % 3);
+                        start[i] = u;
+                        start[j] = v;
+                        start[axis] = -1.0;
+                        let mut end = start;
+                        end[axis] = 1.0;
+                        edges.push([start, end]);
+                    }
+                }
+                edges
+            }
+            CloudSpace::Hsl => {
+                let mut edges = circle(-1.0);
+                edges.extend(circle(0.0));
+                edges.extend(circle(1.0));
+                edges.push([[0.0, 0.0, -1.0], [0.0, 0.0, 1.0]]);
+                edges.push([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]);
+                edges
+            }
+            CloudSpace::Lab | CloudSpace::Oklab => {
+                let mut edges = circle(0.0);
+                edges.push([[0.0, 0.0, -1.0], [0.0, 0.0, 1.0]]);
+                edges.push([[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]]);
+                edges.push([[0.0, -1.0, 0.0], [0.0, 1.0, 0.0]]);
+                edges
+            }
+        }
+    }
+
+    /// The names of the axes and where to write them.
+    pub fn labels(&self) -> Vec<(&'static str, [f32; 3])> {
+        match self {
+            CloudSpace::Rgb => vec![
+                ("R", [1.0, -1.0, -1.0]),
+                ("G", [-1.0, 1.0, -1.0]),
+                ("B", [-1.0, -1.0, 1.0]),
+            ],
+            CloudSpace::Hsl => vec![
+                ("L", [0.0, 0.0, 1.0]),
+                ("H 0°", [1.0, 0.0, 0.0]),
+                ("H 120°", [-0.5, 0.866, 0.0]),
+                ("H 240°", [-0.5, -0.866, 0.0]),
+            ],
+            CloudSpace::Lab | CloudSpace::Oklab => vec![
+                ("L", [0.0, 0.0, 1.0]),
+                ("+a", [1.0, 0.0, 0.0]),
+                ("-a", [-1.0, 0.0, 0.0]),
+                ("+b", [0.0, 1.0, 0.0]),
+                ("-b", [0.0, -1.0, 0.0]),
+            ],
+        }
+    }
+}
+
+/// A horizontal circle of radius 1 at height `z`.
+fn circle(z: f32) -> Vec<[[f32; 3]; 2]> {
+    let point = |i: usize| {
+        let (sin, cos) = (i as f32 * TAU / CIRCLE_SEGMENTS as f32).sin_cos();
+        [cos, sin, z]
+    };
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| [point(i), point(i + 1)])
+        .collect()
+}
+
+/// A point of a cloud.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloudPoint {
+    pub position: [f32; 3],
+    /// The color it is drawn with.
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PointCloud {
+    pub space: CloudSpace,
+    pub points: Vec<CloudPoint>,
+}
+
+impl PointCloud {
+    pub fn new(space: CloudSpace) -> Self {
+        Self {
+            space,
+            points: Vec::new(),
+        }
+    }
+
+    /// Add at most `max_points` evenly spaced pixels of the RGBA `pixels`,
+    /// drawn with their own color or with `color` if given, e.g. to show the
+    /// region they come from.
+    pub fn add_pixels(&mut self, pixels: &[u8], max_points: usize, color: Option<[u8; 3]>) {
+        let n = pixels.len() / BYTES_PER_PIXEL;
+        let step = n.div_ceil(max_points.max(1)).max(1);
+        for pix in pixels.chunks_exact(BYTES_PER_PIXEL).step_by(step) {
+            let rgb = [pix[0], pix[1], pix[2]];
+            self.points.push(CloudPoint {
+                position: self.space.position(rgb),
+                color: color.unwrap_or(rgb),
+            });
+        }
+    }
+
+    /// Draw the points as squares of side `point_size` on an RGBA image of
+    /// `background` color, the nearest point in front.
+    pub fn render(
+        &self,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+        point_size: usize,
+        background: [u8; 3],
+    ) -> Vec<u8> {
+        let mut image = Vec::with_capacity(width * height * BYTES_PER_PIXEL);
+        for _ in 0..width * height {
+            image.extend_from_slice(&[background[0], background[1], background[2], 255]);
+        }
+        let mut depth = vec![f32::NEG_INFINITY; width * height];
+        let half = point_size as f32 / 2.0;
+        // The pixels covered by a square from `start`, in an image of side `n`.
+        let span = |start: f32, n: usize| {
+            let start = start.round() as isize;
+            let end = start + point_size as isize;
+            (start.clamp(0, n as isize) as usize)..(end.clamp(0, n as isize) as usize)
+        };
+        for point in self.points.iter() {
+            let (x, y, z) = camera.project(point.position, width, height);
+            for py in span(y - half, height) {
+                for px in span(x - half, width) {
+                    let i = py * width + px;
+                    if z > depth[i] {
+                        depth[i] = z;
+                        image[i * BYTES_PER_PIXEL..i * BYTES_PER_PIXEL + 3]
+                            .copy_from_slice(&point.color);
+                    }
+                }
+            }
+        }
+        image
+    }
+}
+
+/// A camera orbiting the center of the space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// Turn around the vertical axis, in degrees.
+    pub yaw: f32,
+    /// Height above the horizontal plane, in degrees from -90 to 90.
+    pub pitch: f32,
+    /// 1 fits the whole space in the image.
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            yaw: -30.0,
+            pitch: 20.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Turn the camera by `yaw` and `pitch` degrees.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(360.0);
+        self.pitch = (self.pitch + pitch).clamp(-90.0, 90.0);
+    }
+
+    /// The position in an image of `width` by `height` pixels, with `y`
+    /// down, and the depth, larger nearer the camera.
+    pub fn project(&self, position: [f32; 3], width: usize, height: usize) -> (f32, f32, f32) {
+        let [x, y, z] = position;
+        let (sin, cos) = self.yaw.to_radians().sin_cos();
+        let right = x * cos - y * sin;
+        let toward = -(x * sin + y * cos);
+        let (sin, cos) = self.pitch.to_radians().sin_cos();
+        let up = z * cos - toward * sin;
+        let depth = toward * cos + z * sin;
+        // The corners of the cube are at a distance of sqrt(3).
+        let scale = self.zoom * width.min(height) as f32 / 2.0 / 3f32.sqrt();
+        (
+            width as f32 / 2.0 + right * scale,
+            height as f32 / 2.0 - up * scale,
+            depth,
+        )
+    }
+}
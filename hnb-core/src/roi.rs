@@ -0,0 +1,131 @@
+//! Regions of interest in an image.
+//!
+//! Coordinates are in image pixels with the origin at the top left corner of
+//! the image, so the center of the pixel at column `x` and row `y` is at
+//! `(x + 0.5, y + 0.5)`. A pixel belongs to a region if its center does.
+
+use crate::classify::Control;
+use crate::image::BYTES_PER_PIXEL;
+
+/// The outline of a region of interest.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// An axis aligned rectangle between two corners, in any order.
+    Rect {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+    },
+    Circle {
+        cx: f32,
+        cy: f32,
+        r: f32,
+    },
+    /// A closed polygon through the vertices. Self intersecting polygons use
+    /// the even-odd rule.
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Shape {
+    /// Whether the point `(x, y)` is inside the shape.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        match self {
+            Shape::Rect { x0, y0, x1, y1 } => {
+                x0.min(*x1) <= x && x <= x0.max(*x1) && y0.min(*y1) <= y && y <= y0.max(*y1)
+            }
+            Shape::Circle { cx, cy, r } => (x - cx).powi(2) + (y - cy).powi(2) <= r * r,
+            Shape::Polygon(vertices) => {
+                let mut inside = false;
+                let mut prev = match vertices.last() {
+                    Some(v) => *v,
+                    None => return false,
+                };
+                for &(vx, vy) in vertices.iter() {
+                    let (px, py) = prev;
+                    if (vy > y) != (py > y) && x < (px - vx) * (y - vy) / (py - vy) + vx {
+                        inside = !inside;
+                    }
+                    prev = (vx, vy);
+                }
+                inside
+            }
+        }
+    }
+
+    /// The bounding box as `(x_min, y_min, x_max, y_max)`.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match self {
+            Shape::Rect { x0, y0, x1, y1 } => (x0.min(*x1), y0.min(*y1), x0.max(*x1), y0.max(*y1)),
+            Shape::Circle { cx, cy, r } => (cx - r, cy - r, cx + r, cy + r),
+            Shape::Polygon(vertices) => vertices.iter().fold(
+                (
+                    f32::INFINITY,
+                    f32::INFINITY,
+                    f32::NEG_INFINITY,
+                    f32::NEG_INFINITY,
+                ),
+                |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            ),
+        }
+    }
+
+    /// The center of the bounding box.
+    pub fn center(&self) -> (f32, f32) {
+        let (x0, y0, x1, y1) = self.bounds();
+        ((x0 + x1) / 2.0, (y0 + y1) / 2.0)
+    }
+
+    /// The indices, in row-major order, of the pixels of a `width` x `height`
+    /// image inside the shape.
+    pub fn pixel_indices(&self, width: u32, height: u32) -> Vec<usize> {
+        let (x0, y0, x1, y1) = self.bounds();
+        if !(x0 <= x1 && y0 <= y1) {
+            return Vec::new();
+        }
+        // Pixels whose centers may be inside the bounding box.
+        let pixel_range = |lo: f32, hi: f32, n: u32| {
+            let first = (lo - 0.5).ceil().max(0.0) as u32;
+            let last = ((hi - 0.5).floor() + 1.0).clamp(0.0, n as f32) as u32;
+            first..last
+        };
+        let mut result = Vec::new();
+        for y in pixel_range(y0, y1, height) {
+            for x in pixel_range(x0, x1, width) {
+                if self.contains(x as f32 + 0.5, y as f32 + 0.5) {
+                    result.push(y as usize * width as usize + x as usize);
+                }
+            }
+        }
+        result
+    }
+
+    /// The RGBA values of the pixels inside the shape, from the RGBA buffer
+    /// `data` of a `width` x `height` image.
+    pub fn extract_pixels(&self, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut result = Vec::new();
+        for i in self.pixel_indices(width, height) {
+            result.extend_from_slice(&data[i * BYTES_PER_PIXEL..(i + 1) * BYTES_PER_PIXEL]);
+        }
+        result
+    }
+}
+
+/// A labelled region of interest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Roi {
+    pub label: String,
+    pub shape: Shape,
+    /// Whether the region is a control with a known result.
+    pub control: Option<Control>,
+}
+
+impl Roi {
+    pub fn new(label: impl Into<String>, shape: Shape) -> Self {
+        Self {
+            label: label.into(),
+            shape,
+            control: None,
+        }
+    }
+}
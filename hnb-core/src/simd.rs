@@ -0,0 +1,563 @@
+//! Vectorised HSL kernels processing four pixels at a time.
+//!
+//! When compiled for `wasm32` with `-C target-feature=+simd128`, [F32x4] maps
+//! to WebAssembly SIMD instructions. For other builds it is a plain array
+//! processed lane by lane, which keeps the kernels testable natively; there
+//! the public functions in [crate::transform_colors] use their scalar code,
+//! see [ENABLED].
+//!
+//! The kernels avoid branches: every pixel goes through all cases of the HSL
+//! model and the result is chosen with lane masks. They agree with the scalar
+//! functions within one 8 bit step.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::transform_colors::{linear_to_srgb, srgb_decode_table, stretch_center};
+
+/// Whether [crate::transform_colors] uses the kernels of this module.
+pub const ENABLED: bool = cfg!(all(target_arch = "wasm32", target_feature = "simd128"));
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod backend {
+    use core::arch::wasm32::*;
+
+    pub type F32s = v128;
+    pub type Mask = v128;
+
+    #[inline]
+    pub fn splat(v: f32) -> F32s {
+        f32x4_splat(v)
+    }
+    #[inline]
+    pub fn from_array(a: [f32; 4]) -> F32s {
+        f32x4(a[0], a[1], a[2], a[3])
+    }
+    #[inline]
+    pub fn to_array(v: F32s) -> [f32; 4] {
+        [
+            f32x4_extract_lane::<0>(v),
+            f32x4_extract_lane::<1>(v),
+            f32x4_extract_lane::<2>(v),
+            f32x4_extract_lane::<3>(v),
+        ]
+    }
+    #[inline]
+    pub fn add(a: F32s, b: F32s) -> F32s {
+        f32x4_add(a, b)
+    }
+    #[inline]
+    pub fn sub(a: F32s, b: F32s) -> F32s {
+        f32x4_sub(a, b)
+    }
+    #[inline]
+    pub fn mul(a: F32s, b: F32s) -> F32s {
+        f32x4_mul(a, b)
+    }
+    #[inline]
+    pub fn div(a: F32s, b: F32s) -> F32s {
+        f32x4_div(a, b)
+    }
+    #[inline]
+    pub fn neg(a: F32s) -> F32s {
+        f32x4_neg(a)
+    }
+    #[inline]
+    pub fn min(a: F32s, b: F32s) -> F32s {
+        f32x4_min(a, b)
+    }
+    #[inline]
+    pub fn max(a: F32s, b: F32s) -> F32s {
+        f32x4_max(a, b)
+    }
+    #[inline]
+    pub fn abs(a: F32s) -> F32s {
+        f32x4_abs(a)
+    }
+    #[inline]
+    pub fn floor(a: F32s) -> F32s {
+        f32x4_floor(a)
+    }
+    #[inline]
+    pub fn lt(a: F32s, b: F32s) -> Mask {
+        f32x4_lt(a, b)
+    }
+    #[inline]
+    pub fn eq(a: F32s, b: F32s) -> Mask {
+        f32x4_eq(a, b)
+    }
+    #[inline]
+    pub fn select(mask: Mask, a: F32s, b: F32s) -> F32s {
+        v128_bitselect(a, b, mask)
+    }
+    #[inline]
+    pub fn load_rgb(pixels: &[u8; 16]) -> [F32s; 3] {
+        // SAFETY: `pixels` has the 16 bytes loaded, which need no alignment.
+        let v = unsafe { v128_load(pixels.as_ptr() as *const v128) };
+        // Move a channel of each pixel to the low byte of its lane. Indices
+        // out of range give 0 for the other bytes.
+        let channel = |c: u8| {
+            let indices = u8x16(
+                c,
+                16,
+                16,
+                16,
+                c + 4,
+                16,
+                16,
+                16,
+                c + 8,
+                16,
+                16,
+                16,
+                c + 12,
+                16,
+                16,
+                16,
+            );
+            f32x4_convert_u32x4(u8x16_swizzle(v, indices))
+        };
+        [channel(0), channel(1), channel(2)]
+    }
+    #[inline]
+    pub fn store_rgb(pixels: &mut [u8; 16], rgb: [F32s; 3]) {
+        let byte = |v: F32s| {
+            let v = f32x4_min(f32x4_max(v, f32x4_splat(0.0)), f32x4_splat(255.0));
+            u32x4_trunc_sat_f32x4(f32x4_nearest(v))
+        };
+        let ptr = pixels.as_mut_ptr() as *mut v128;
+        // SAFETY: `pixels` has the 16 bytes loaded and stored, which need no
+        // alignment.
+        let alpha = v128_and(unsafe { v128_load(ptr) }, u32x4_splat(0xff00_0000));
+        let v = v128_or(
+            v128_or(byte(rgb[0]), i32x4_shl(byte(rgb[1]), 8)),
+            v128_or(i32x4_shl(byte(rgb[2]), 16), alpha),
+        );
+        unsafe { v128_store(ptr, v) }
+    }
+    #[inline]
+    pub fn to_indices(v: F32s) -> [usize; 4] {
+        let v = u32x4_trunc_sat_f32x4(v);
+        [
+            u32x4_extract_lane::<0>(v) as usize,
+            u32x4_extract_lane::<1>(v) as usize,
+            u32x4_extract_lane::<2>(v) as usize,
+            u32x4_extract_lane::<3>(v) as usize,
+        ]
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+mod backend {
+    pub type F32s = [f32; 4];
+    pub type Mask = [bool; 4];
+
+    #[inline]
+    fn map(a: F32s, f: impl Fn(f32) -> f32) -> F32s {
+        [f(a[0]), f(a[1]), f(a[2]), f(a[3])]
+    }
+    #[inline]
+    fn zip(a: F32s, b: F32s, f: impl Fn(f32, f32) -> f32) -> F32s {
+        [f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2]), f(a[3], b[3])]
+    }
+
+    #[inline]
+    pub fn splat(v: f32) -> F32s {
+        [v; 4]
+    }
+    #[inline]
+    pub fn from_array(a: [f32; 4]) -> F32s {
+        a
+    }
+    #[inline]
+    pub fn to_array(v: F32s) -> [f32; 4] {
+        v
+    }
+    #[inline]
+    pub fn add(a: F32s, b: F32s) -> F32s {
+        zip(a, b, |a, b| a + b)
+    }
+    #[inline]
+    pub fn sub(a: F32s, b: F32s) -> F32s {
+        zip(a, b, |a, b| a - b)
+    }
+    #[inline]
+    pub fn mul(a: F32s, b: F32s) -> F32s {
+        zip(a, b, |a, b| a * b)
+    }
+    #[inline]
+    pub fn div(a: F32s, b: F32s) -> F32s {
+        zip(a, b, |a, b| a / b)
+    }
+    #[inline]
+    pub fn neg(a: F32s) -> F32s {
+        map(a, |a| -a)
+    }
+    #[inline]
+    pub fn min(a: F32s, b: F32s) -> F32s {
+        zip(a, b, f32::min)
+    }
+    #[inline]
+    pub fn max(a: F32s, b: F32s) -> F32s {
+        zip(a, b, f32::max)
+    }
+    #[inline]
+    pub fn abs(a: F32s) -> F32s {
+        map(a, f32::abs)
+    }
+    #[inline]
+    pub fn floor(a: F32s) -> F32s {
+        map(a, f32::floor)
+    }
+    #[inline]
+    pub fn lt(a: F32s, b: F32s) -> Mask {
+        [a[0] < b[0], a[1] < b[1], a[2] < b[2], a[3] < b[3]]
+    }
+    #[inline]
+    pub fn eq(a: F32s, b: F32s) -> Mask {
+        [a[0] == b[0], a[1] == b[1], a[2] == b[2], a[3] == b[3]]
+    }
+    #[inline]
+    pub fn select(mask: Mask, a: F32s, b: F32s) -> F32s {
+        std::array::from_fn(|i| if mask[i] { a[i] } else { b[i] })
+    }
+    #[inline]
+    pub fn load_rgb(pixels: &[u8; 16]) -> [F32s; 3] {
+        std::array::from_fn(|c| std::array::from_fn(|i| pixels[4 * i + c] as f32))
+    }
+    #[inline]
+    pub fn store_rgb(pixels: &mut [u8; 16], rgb: [F32s; 3]) {
+        for (c, v) in rgb.iter().enumerate() {
+            for (i, v) in v.iter().enumerate() {
+                pixels[4 * i + c] = v.clamp(0.0, 255.0).round() as u8;
+            }
+        }
+    }
+    #[inline]
+    pub fn to_indices(v: F32s) -> [usize; 4] {
+        v.map(|v| v as usize)
+    }
+}
+
+/// Four `f32` lanes.
+#[derive(Clone, Copy, Debug)]
+pub struct F32x4(backend::F32s);
+
+/// The result of a lane-wise comparison of two [F32x4].
+#[derive(Clone, Copy, Debug)]
+pub struct Mask4(backend::Mask);
+
+impl F32x4 {
+    #[inline]
+    pub fn splat(v: f32) -> Self {
+        Self(backend::splat(v))
+    }
+    #[inline]
+    pub fn from_array(a: [f32; 4]) -> Self {
+        Self(backend::from_array(a))
+    }
+    #[inline]
+    pub fn to_array(self) -> [f32; 4] {
+        backend::to_array(self.0)
+    }
+    #[inline]
+    pub fn min(self, other: Self) -> Self {
+        Self(backend::min(self.0, other.0))
+    }
+    #[inline]
+    pub fn max(self, other: Self) -> Self {
+        Self(backend::max(self.0, other.0))
+    }
+    #[inline]
+    pub fn abs(self) -> Self {
+        Self(backend::abs(self.0))
+    }
+    #[inline]
+    pub fn floor(self) -> Self {
+        Self(backend::floor(self.0))
+    }
+    #[inline]
+    pub fn clamp(self, lo: f32, hi: f32) -> Self {
+        self.max(Self::splat(lo)).min(Self::splat(hi))
+    }
+    #[inline]
+    pub fn lt(self, other: Self) -> Mask4 {
+        Mask4(backend::lt(self.0, other.0))
+    }
+    #[inline]
+    pub fn eq(self, other: Self) -> Mask4 {
+        Mask4(backend::eq(self.0, other.0))
+    }
+    /// The red, green and blue values of four RGBA pixels, in 0..=255.
+    #[inline]
+    pub fn load_rgb(pixels: &[u8; 16]) -> [Self; 3] {
+        backend::load_rgb(pixels).map(Self)
+    }
+    /// Store red, green and blue values in 0..=255 into four RGBA pixels,
+    /// rounded and clamped, leaving alpha unchanged.
+    #[inline]
+    pub fn store_rgb(pixels: &mut [u8; 16], rgb: [Self; 3]) {
+        backend::store_rgb(pixels, rgb.map(|v| v.0))
+    }
+    /// The lanes truncated to indices, for values in `0..=u32::MAX`.
+    #[inline]
+    pub fn to_indices(self) -> [usize; 4] {
+        backend::to_indices(self.0)
+    }
+    /// `x - m * floor(x / m)`, in `0..m` for positive `m`.
+    #[inline]
+    pub fn rem_euclid(self, m: f32) -> Self {
+        let m = Self::splat(m);
+        self - m * (self / m).floor()
+    }
+}
+
+impl Mask4 {
+    /// Lanes of `a` where the mask is set, lanes of `b` elsewhere.
+    #[inline]
+    pub fn select(self, a: F32x4, b: F32x4) -> F32x4 {
+        F32x4(backend::select(self.0, a.0, b.0))
+    }
+}
+
+macro_rules! impl_op {
+    ($trait:ident, $method:ident) => {
+        impl $trait for F32x4 {
+            type Output = Self;
+            #[inline]
+            fn $method(self, rhs: Self) -> Self {
+                Self(backend::$method(self.0, rhs.0))
+            }
+        }
+    };
+}
+
+impl_op!(Add, add);
+impl_op!(Sub, sub);
+impl_op!(Mul, mul);
+impl_op!(Div, div);
+
+impl Neg for F32x4 {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        Self(backend::neg(self.0))
+    }
+}
+
+/// RGB in 0..=1 to hue (degrees in 0..360), saturation and lightness, the
+/// vectorised [crate::transform_colors::rgb_to_hsl].
+#[inline]
+pub fn rgb_to_hsl_x4(r: F32x4, g: F32x4, b: F32x4) -> (F32x4, F32x4, F32x4) {
+    let zero = F32x4::splat(0.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let sum = max + min;
+    let l = sum * F32x4::splat(0.5);
+    let d = max - min;
+    // For grays `d` is 0 and the divisions give NaN or infinity, which is
+    // replaced at the end.
+    let s = F32x4::splat(0.5)
+        .lt(l)
+        .select(d / (F32x4::splat(2.0) - sum), d / sum);
+    let h_r = (g - b) / d + g.lt(b).select(F32x4::splat(6.0), zero);
+    let h_g = (b - r) / d + F32x4::splat(2.0);
+    let h_b = (r - g) / d + F32x4::splat(4.0);
+    let h = max.eq(r).select(h_r, max.eq(g).select(h_g, h_b)) * F32x4::splat(60.0);
+    let gray = d.eq(zero);
+    (gray.select(zero, h), gray.select(zero, s), l)
+}
+
+/// Hue (degrees, any angle), saturation and lightness to RGB, the vectorised
+/// [crate::transform_colors::hsl_to_rgb].
+///
+/// Uses the equivalent form `l - a * clamp(min(k - 3, 9 - k), -1, 1)` with
+/// `k = (n + h / 30) mod 12` and `a = s * min(l, 1 - l)` for the channels
+/// `n` = 0 (red), 8 (green) and 4 (blue), which needs no branches.
+#[inline]
+pub fn hsl_to_rgb_x4(h: F32x4, s: F32x4, l: F32x4) -> (F32x4, F32x4, F32x4) {
+    let one = F32x4::splat(1.0);
+    let a = s * l.min(one - l);
+    let h12 = h / F32x4::splat(30.0);
+    let channel = |n: f32| {
+        let k = (F32x4::splat(n) + h12).rem_euclid(12.0);
+        let t = (k - F32x4::splat(3.0))
+            .min(F32x4::splat(9.0) - k)
+            .clamp(-1.0, 1.0);
+        l - a * t
+    };
+    (channel(0.0), channel(8.0), channel(4.0))
+}
+
+/// Sine and cosine of angles in radians.
+#[inline]
+pub fn sin_cos_x4(x: F32x4) -> (F32x4, F32x4) {
+    use std::f32::consts::{FRAC_PI_2, PI, TAU};
+    // Reduce to -pi..pi, then reflect to -pi/2..=pi/2 where the series
+    // converge quickly. The reflection keeps the sine and negates the cosine.
+    let x = (x + F32x4::splat(PI)).rem_euclid(TAU) - F32x4::splat(PI);
+    let hi = F32x4::splat(FRAC_PI_2).lt(x);
+    let lo = x.lt(F32x4::splat(-FRAC_PI_2));
+    let x = hi.select(F32x4::splat(PI) - x, lo.select(F32x4::splat(-PI) - x, x));
+    let cos_sign = hi.select(
+        F32x4::splat(-1.0),
+        lo.select(F32x4::splat(-1.0), F32x4::splat(1.0)),
+    );
+
+    let x2 = x * x;
+    let c = |v: f32| F32x4::splat(v);
+    // Taylor series, the error is below 1e-7 on -pi/2..=pi/2.
+    let sin = x
+        * (c(1.0)
+            + x2 * (c(-1.0 / 6.0)
+                + x2 * (c(1.0 / 120.0)
+                    + x2 * (c(-1.0 / 5040.0)
+                        + x2 * (c(1.0 / 362_880.0) + x2 * c(-1.0 / 39_916_800.0))))));
+    let cos = c(1.0)
+        + x2 * (c(-0.5)
+            + x2 * (c(1.0 / 24.0)
+                + x2 * (c(-1.0 / 720.0)
+                    + x2 * (c(1.0 / 40320.0)
+                        + x2 * (c(-1.0 / 3_628_800.0) + x2 * c(1.0 / 479_001_600.0))))));
+    (sin, cos * cos_sign)
+}
+
+/// The angle of the point (x, y) in radians, in -pi..=pi, like [f32::atan2].
+#[inline]
+pub fn atan2_x4(y: F32x4, x: F32x4) -> F32x4 {
+    use std::f32::consts::{FRAC_PI_2, PI};
+    let c = |v: f32| F32x4::splat(v);
+    let ax = x.abs();
+    let ay = y.abs();
+    let big = ax.max(ay);
+    let small = ax.min(ay);
+    // 0/0 for the origin, where the angle is 0 by convention.
+    let a = big.eq(c(0.0)).select(c(0.0), small / big);
+    let s = a * a;
+    // Minimax polynomial for atan on 0..=1, error below 1e-5 radians.
+    let r = a
+        * (c(0.999_977_26)
+            + s * (c(-0.332_623_47)
+                + s * (c(0.193_543_46)
+                    + s * (c(-0.116_432_87) + s * (c(0.052_653_32) + s * c(-0.011_721_2))))));
+    let r = ax.lt(ay).select(c(FRAC_PI_2) - r, r);
+    let r = x.lt(c(0.0)).select(c(PI) - r, r);
+    y.lt(c(0.0)).select(-r, r)
+}
+
+/// Apply `f` to the HSL values of four pixels at a time.
+///
+/// With `decode`, the 8 bit values are decoded with that table (e.g. the sRGB
+/// transfer function) before computing HSL and the results are encoded with
+/// [SrgbEncoder]. Without it, the HSL values are computed from the 8 bit
+/// values scaled to 0..=1. Alpha is left unchanged.
+fn map_hsl_x4<F>(data: &mut [u8], decode: Option<&[f32; 256]>, f: F)
+where
+    F: Fn(F32x4, F32x4, F32x4) -> (F32x4, F32x4, F32x4),
+{
+    let encoder = decode.map(|_| SrgbEncoder::new());
+    let process = |pixels: &mut [u8; 16]| {
+        // There are no gather instructions, so the table is read lane by
+        // lane.
+        let [r, g, b] = match decode {
+            Some(table) => [0, 1, 2].map(|c| {
+                F32x4::from_array(std::array::from_fn(|i| table[pixels[4 * i + c] as usize]))
+            }),
+            None => F32x4::load_rgb(pixels).map(|v| v * F32x4::splat(1.0 / 255.0)),
+        };
+        let (h, s, l) = rgb_to_hsl_x4(r, g, b);
+        let (h, s, l) = f(h, s, l);
+        let (r, g, b) = hsl_to_rgb_x4(h, s, l);
+        let rgb = match &encoder {
+            Some(encoder) => [r, g, b].map(|v| encoder.encode(v)),
+            None => [r, g, b].map(|v| v * F32x4::splat(255.0)),
+        };
+        F32x4::store_rgb(pixels, rgb);
+    };
+
+    let mut chunks = data.chunks_exact_mut(16);
+    for chunk in &mut chunks {
+        process(chunk.try_into().unwrap());
+    }
+    let rest = chunks.into_remainder();
+    if !rest.is_empty() {
+        let mut padded = [0u8; 16];
+        padded[..rest.len()].copy_from_slice(rest);
+        process(&mut padded);
+        rest.copy_from_slice(&padded[..rest.len()]);
+    }
+}
+
+/// Encodes linear light to 8 bit sRGB by looking up the nearest of
+/// [SrgbEncoder::STEPS] evenly spaced values.
+///
+/// Where the transfer function is steepest, near black, consecutive entries
+/// are 0.8 levels apart, so the result is within one level of
+/// [crate::transform_colors::linear_to_srgb].
+struct SrgbEncoder {
+    table: Vec<u8>,
+}
+
+impl SrgbEncoder {
+    const STEPS: usize = 4096;
+
+    fn new() -> Self {
+        let last = (Self::STEPS - 1) as f32;
+        let table = (0..Self::STEPS)
+            .map(|i| (linear_to_srgb(i as f32 / last) * 255.0).round() as u8)
+            .collect();
+        Self { table }
+    }
+
+    /// The encoded values in 0..=255, clamping to the gamut.
+    #[inline]
+    fn encode(&self, v: F32x4) -> F32x4 {
+        let last = (Self::STEPS - 1) as f32;
+        let indices = (v.clamp(0.0, 1.0) * F32x4::splat(last) + F32x4::splat(0.5)).to_indices();
+        F32x4::from_array(indices.map(|i| self.table[i] as f32))
+    }
+}
+
+/// The coordinates of the Color Stretch center.
+fn stretch_center_x4(center_hue: f32, radius: f32) -> (F32x4, F32x4) {
+    let (cx, cy) = stretch_center(center_hue, radius);
+    (F32x4::splat(cx), F32x4::splat(cy))
+}
+
+/// The new hue, in degrees, of Color Stretch for hues in degrees.
+#[inline]
+fn stretch_hue_x4(h: F32x4, cx: F32x4, cy: F32x4) -> F32x4 {
+    let (sin, cos) = sin_cos_x4(h * F32x4::splat(std::f32::consts::PI / 180.0));
+    atan2_x4(sin - cy, cos - cx) * F32x4::splat(180.0 / std::f32::consts::PI)
+}
+
+/// Vectorised [crate::transform_colors::saturate_and_rotate].
+pub fn saturate_and_rotate(data: &mut [u8], degrees: f32, saturation: f32) {
+    let (degrees, saturation) = (F32x4::splat(degrees), F32x4::splat(saturation));
+    map_hsl_x4(data, None, |h, s, l| (h + degrees, s * saturation, l));
+}
+
+/// Vectorised [crate::transform_colors::color_stretch].
+pub fn color_stretch(data: &mut [u8], center_hue: f32, radius: f32, saturation: f32) {
+    let (cx, cy) = stretch_center_x4(center_hue, radius);
+    let saturation = F32x4::splat(saturation);
+    map_hsl_x4(data, Some(&srgb_decode_table()), |h, s, l| {
+        (stretch_hue_x4(h, cx, cy), s * saturation, l)
+    });
+}
+
+/// Vectorised [crate::transform_colors::saturate_and_rotate_linear].
+pub fn saturate_and_rotate_linear(data: &mut [u8], degrees: f32, saturation: f32) {
+    let (degrees, saturation) = (F32x4::splat(degrees), F32x4::splat(saturation));
+    map_hsl_x4(data, Some(&srgb_decode_table()), |h, s, l| {
+        (h + degrees, (s * saturation).min(F32x4::splat(1.0)), l)
+    });
+}
+
+/// Vectorised [crate::transform_colors::color_stretch_encoded].
+pub fn color_stretch_encoded(data: &mut [u8], center_hue: f32, radius: f32, saturation: f32) {
+    let (cx, cy) = stretch_center_x4(center_hue, radius);
+    let saturation = F32x4::splat(saturation);
+    map_hsl_x4(data, None, |h, s, l| {
+        (stretch_hue_x4(h, cx, cy), s * saturation, l)
+    });
+}
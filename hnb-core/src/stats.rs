@@ -0,0 +1,156 @@
+//! Color statistics of sets of pixels, e.g. of a region of interest.
+
+use crate::colorspace::{linear_to_lab, srgb8_to_linear};
+use crate::image::BYTES_PER_PIXEL;
+use crate::transform_colors::rgb_to_hsl;
+
+/// Mean, median and (population) standard deviation of some values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub mean: f32,
+    pub median: f32,
+    pub std: f32,
+}
+
+impl Summary {
+    /// Summarize `values`, which are reordered. Returns `None` if empty.
+    pub fn of(values: &mut [f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+        let var = values
+            .iter()
+            .map(|&v| (v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some(Self {
+            mean: mean as f32,
+            median: median(values),
+            std: var.sqrt() as f32,
+        })
+    }
+}
+
+/// The median of non-empty `values`, which are reordered.
+fn median(values: &mut [f32]) -> f32 {
+    let n = values.len();
+    let (below, upper, _) = values.select_nth_unstable_by(n / 2, f32::total_cmp);
+    let upper = *upper;
+    if n % 2 == 1 {
+        upper
+    } else {
+        let lower = below.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        (lower + upper) / 2.0
+    }
+}
+
+/// Statistics of angles in degrees, e.g. hues.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CircularSummary {
+    /// The mean direction, in 0..360.
+    pub mean: f32,
+    /// The median, in 0..360, taken as the median of the angles measured
+    /// relative to the mean direction.
+    pub median: f32,
+    /// The circular standard deviation `sqrt(-2 ln R)`, in degrees.
+    pub std: f32,
+    /// The length `R` of the mean resultant vector, from 0 (angles spread
+    /// all around the circle) to 1 (all angles equal).
+    pub resultant_length: f32,
+}
+
+impl CircularSummary {
+    /// Summarize `degrees`. Returns `None` if empty.
+    pub fn of(degrees: &[f32]) -> Option<Self> {
+        if degrees.is_empty() {
+            return None;
+        }
+        let (sin, cos) = degrees.iter().fold((0.0f64, 0.0f64), |(s, c), &d| {
+            let a = (d as f64).to_radians();
+            (s + a.sin(), c + a.cos())
+        });
+        let n = degrees.len() as f64;
+        let resultant_length = ((sin * sin + cos * cos).sqrt() / n).min(1.0);
+        let mean = sin.atan2(cos).to_degrees().rem_euclid(360.0) as f32;
+        let mut relative: Vec<f32> = degrees
+            .iter()
+            .map(|&d| (d - mean + 180.0).rem_euclid(360.0) - 180.0)
+            .collect();
+        let median = (mean + median(&mut relative)).rem_euclid(360.0);
+        Some(Self {
+            mean,
+            median,
+            std: (-2.0 * resultant_length.ln()).sqrt().to_degrees() as f32,
+            resultant_length: resultant_length as f32,
+        })
+    }
+}
+
+/// Color statistics of a set of pixels.
+///
+/// RGB values are the 8 bit sRGB values. Hue (degrees) and saturation (0..=1)
+/// are those of the HSL model computed from the encoded values, as used by
+/// Color Rotate. CIE L*a*b* and chroma are for D65.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorStats {
+    pub pixel_count: usize,
+    pub rgb: [Summary; 3],
+    /// Statistics of the hue of the pixels with a saturation above 0. `None`
+    /// if all pixels are gray.
+    pub hue: Option<CircularSummary>,
+    pub saturation: Summary,
+    pub lab: [Summary; 3],
+    pub chroma: Summary,
+}
+
+impl ColorStats {
+    /// Compute the statistics of an RGBA buffer. Returns `None` if empty.
+    pub fn of_pixels(data: &[u8]) -> Option<Self> {
+        let n = data.len() / BYTES_PER_PIXEL;
+        if n == 0 {
+            return None;
+        }
+        let mut rgb = [
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+        ];
+        let mut lab = [
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+        ];
+        let mut hue = Vec::with_capacity(n);
+        let mut saturation = Vec::with_capacity(n);
+        let mut chroma = Vec::with_capacity(n);
+        for pix in data.chunks_exact(BYTES_PER_PIXEL) {
+            let [r, g, b] = [pix[0], pix[1], pix[2]];
+            for c in 0..3 {
+                rgb[c].push(pix[c] as f32);
+            }
+            let (h, s, _) = rgb_to_hsl(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+            if s > 0.0 {
+                hue.push(h);
+            }
+            saturation.push(s);
+            let l = linear_to_lab(srgb8_to_linear([r, g, b]));
+            for c in 0..3 {
+                lab[c].push(l[c]);
+            }
+            chroma.push(l[1].hypot(l[2]));
+        }
+        let summarize = |values: &mut Vec<f32>| Summary::of(values).unwrap();
+        let [r, g, b] = &mut rgb;
+        let [l, a, bb] = &mut lab;
+        Some(Self {
+            pixel_count: n,
+            rgb: [summarize(r), summarize(g), summarize(b)],
+            hue: CircularSummary::of(&hue),
+            saturation: summarize(&mut saturation),
+            lab: [summarize(l), summarize(a), summarize(bb)],
+            chroma: summarize(&mut chroma),
+        })
+    }
+}
@@ -0,0 +1,297 @@
+use palette::Pixel;
+
+use crate::simd;
+
+/// Perform a saturation increase and rotation of colors.
+///
+/// Operates on raw pixel buffer. The hue is rotated by `degrees` and the
+/// saturation multiplied by `saturation`. The Color Rotate defaults are a 4x
+/// saturation and 180 degree rotation.
+///
+/// I learned, via discussion with the authors of Kellner et al. 2020 that, to
+/// perform the "colorswitch" operation manually, they open the image in FIJI,
+/// opened "Plugins -> Color Inspector 3D" then used the slider to set "Color
+/// Rotation" equal to 180 degrees. In a later addition to the procedure, they
+/// additionally set the "Saturation" slider to 4.0. I have done this
+/// transformation myself on test images and compared the results. Additionally,
+/// I inspected the source code of the Color Inspector 3D plugin by Barthel.
+/// Based on these investigations, I wrote the below transformation.
+pub fn saturate_and_rotate(data: &mut [u8], degrees: f32, saturation: f32) {
+    if simd::ENABLED {
+        simd::saturate_and_rotate(data, degrees, saturation)
+    } else {
+        saturate_and_rotate_scalar(data, degrees, saturation)
+    }
+}
+
+/// [saturate_and_rotate] without the kernels of [simd], e.g. to test them.
+pub fn saturate_and_rotate_scalar(data: &mut [u8], degrees: f32, saturation: f32) {
+    // Technically, it is probably wrong to load the data as linear, as the
+    // images are probably in sRGB. However, this gives a better match to the
+    // results (visually inspected) of operations with "Color Inspector 3D" by
+    // Kai Uwe Barthel.
+
+    // Apparently [it is not specified what colorspace browsers use to draw
+    // images in the canvas
+    // element](https://wiki.whatwg.org/wiki/CanvasColorSpace).
+
+    let color_buffer: &mut [palette::rgb::Rgba<palette::encoding::Linear<_>, u8>] =
+        Pixel::from_raw_slice_mut(data);
+
+    for pix in color_buffer.iter_mut() {
+        // See
+        // https://github.com/erisir/FIJI/blob/a30ce62566b7a441bc315c8fff365b9985779b27/src-plugins/Color_Inspector_3D/src/main/java/Color_Inspector_3D.java#L4391-L4472
+
+        let rgb: palette::rgb::Rgb<_, u8> = pix.color;
+        let rgb_f32: palette::rgb::Rgb<_, f32> = rgb.into_format();
+
+        use palette::ConvertInto;
+        let mut hsl_f32: palette::Hsl<palette::encoding::Srgb, f32> = rgb_f32.convert_into();
+
+        hsl_f32.hue = palette::RgbHue::from_degrees(hsl_f32.hue.to_degrees() + degrees);
+
+        hsl_f32.saturation *= saturation;
+
+        let rgb_f32: palette::rgb::Rgb<_, f32> = hsl_f32.convert_into();
+        let rgb_u8: palette::rgb::Rgb<_, u8> = rgb_f32.into_format();
+        pix.color = rgb_u8;
+    }
+}
+
+/// Stretch hues away from a center point and increase saturation.
+///
+/// Operates on raw pixel buffer. Hues are placed on a unit circle and the new
+/// hue is the angle as seen from a center point at distance `radius` from the
+/// origin in the direction of `center_hue` (a fraction of a full turn). The
+/// saturation is multiplied by `saturation`. The pixels are loaded as sRGB,
+/// so the HSL values are computed from linear light.
+pub fn color_stretch(data: &mut [u8], center_hue: f32, radius: f32, saturation: f32) {
+    if simd::ENABLED {
+        simd::color_stretch(data, center_hue, radius, saturation)
+    } else {
+        color_stretch_scalar(data, center_hue, radius, saturation)
+    }
+}
+
+/// [color_stretch] without the kernels of [simd], e.g. to test them.
+pub fn color_stretch_scalar(data: &mut [u8], center_hue: f32, radius: f32, saturation: f32) {
+    // Apparently [it is not specified what colorspace browsers use to draw
+    // images in the canvas
+    // element](https://wiki.whatwg.org/wiki/CanvasColorSpace).
+
+    let color_buffer: &mut [palette::rgb::Srgba<u8>] = Pixel::from_raw_slice_mut(data);
+
+    let pi2 = std::f32::consts::PI * 2.0;
+    let cx = radius * (center_hue * pi2).cos();
+    let cy = radius * (center_hue * pi2).sin();
+
+    for pix in color_buffer.iter_mut() {
+        // See
+        // https://github.com/erisir/FIJI/blob/a30ce62566b7a441bc315c8fff365b9985779b27/src-plugins/Color_Inspector_3D/src/main/java/Color_Inspector_3D.java#L4391-L4472
+
+        let rgb: palette::rgb::Rgb<_, u8> = pix.color;
+        let rgb_f32: palette::rgb::Rgb<_, f32> = rgb.into_format();
+
+        use palette::ConvertInto;
+        let mut hsl_f32: palette::Hsl<palette::encoding::Srgb, f32> = rgb_f32.convert_into();
+
+        // Get hue in radians
+        let hue = hsl_f32.hue.to_radians();
+        let hue_x = hue.cos();
+        let hue_y = hue.sin();
+
+        let dx = hue_x - cx;
+        let dy = hue_y - cy;
+
+        let hue_stretch = dy.atan2(dx);
+
+        hsl_f32.hue = palette::RgbHue::from_radians(hue_stretch);
+
+        hsl_f32.saturation *= saturation;
+
+        let rgb_f32: palette::rgb::Rgb<_, f32> = hsl_f32.convert_into();
+        let rgb_u8: palette::rgb::Rgb<_, u8> = rgb_f32.into_format();
+        pix.color = rgb_u8;
+    }
+}
+
+/// Decode an sRGB encoded value in 0..=1 to linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear light value in 0..=1 with the sRGB transfer function.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Table to decode every 8 bit sRGB value to linear light.
+pub(crate) fn srgb_decode_table() -> [f32; 256] {
+    let mut table = [0.0; 256];
+    for (i, v) in table.iter_mut().enumerate() {
+        *v = srgb_to_linear(i as f32 / 255.0);
+    }
+    table
+}
+
+/// Encode linear light to an 8 bit sRGB value, clamping to the gamut.
+#[inline]
+fn encode_srgb_u8(v: f32) -> u8 {
+    (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8
+}
+
+/// Convert RGB in 0..=1 to hue (degrees in 0..360), saturation and lightness.
+///
+/// This is the usual HSL model. It does not care about the encoding of its
+/// input, so callers decide whether the HSL values are computed from encoded
+/// or from linear light values.
+pub fn rgb_to_hsl(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    if max == min {
+        return (0.0, 0.0, l);
+    }
+    let d = max - min;
+    let s = if l > 0.5 {
+        d / (2.0 - max - min)
+    } else {
+        d / (max + min)
+    };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h * 60.0, s, l)
+}
+
+/// Convert hue (degrees), saturation and lightness to RGB, the inverse of
+/// [rgb_to_hsl]. The hue may be any angle.
+pub fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (r + m, g + m, b + m)
+}
+
+/// The new hue, in degrees, of Color Stretch for a hue in degrees.
+///
+/// `cx` and `cy` are the coordinates of the stretch center.
+#[inline]
+pub(crate) fn stretch_hue(hue_degrees: f32, cx: f32, cy: f32) -> f32 {
+    let hue = hue_degrees.to_radians();
+    let dx = hue.cos() - cx;
+    let dy = hue.sin() - cy;
+    dy.atan2(dx).to_degrees()
+}
+
+/// The coordinates of the Color Stretch center.
+pub(crate) fn stretch_center(center_hue: f32, radius: f32) -> (f32, f32) {
+    let angle = center_hue * std::f32::consts::PI * 2.0;
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+/// Apply `f` to the HSL values of each pixel of an RGBA buffer.
+///
+/// With `decode`, each 8 bit value is decoded with that table (the sRGB
+/// transfer function) so the HSL values are computed from linear light, and
+/// the result is re-encoded. Without it, the HSL values are computed from the
+/// encoded 8 bit values scaled to 0..=1. The results are clamped to the gamut
+/// and alpha is left unchanged.
+fn map_hsl<F>(data: &mut [u8], decode: Option<&[f32; 256]>, f: F)
+where
+    F: Fn(f32, f32, f32) -> (f32, f32, f32),
+{
+    let load = |v: u8| match decode {
+        Some(table) => table[v as usize],
+        None => v as f32 / 255.0,
+    };
+    let store = |v: f32| match decode {
+        Some(_) => encode_srgb_u8(v),
+        None => (v.clamp(0.0, 1.0) * 255.0).round() as u8,
+    };
+    for pix in data.chunks_exact_mut(4) {
+        let (h, s, l) = rgb_to_hsl(load(pix[0]), load(pix[1]), load(pix[2]));
+        let (h, s, l) = f(h, s, l);
+        let (r, g, b) = hsl_to_rgb(h, s, l);
+        pix[0] = store(r);
+        pix[1] = store(g);
+        pix[2] = store(b);
+    }
+}
+
+/// Colorimetrically correct variant of [saturate_and_rotate].
+///
+/// The pixels are treated as sRGB. They are decoded to linear light, the hue
+/// is rotated by `degrees` and the saturation multiplied by `saturation` (but
+/// limited to 1, the edge of the HSL model) and the result is re-encoded as
+/// sRGB.
+///
+/// Of the two pipelines, [saturate_and_rotate] is the one doing the HSL work
+/// on the sRGB encoded values: it never decodes them, so its HSL values are
+/// those most image software shows. This variant computes HSL from linear
+/// light instead. [color_stretch] and [color_stretch_encoded] are the same
+/// pair the other way round.
+pub fn saturate_and_rotate_linear(data: &mut [u8], degrees: f32, saturation: f32) {
+    if simd::ENABLED {
+        simd::saturate_and_rotate_linear(data, degrees, saturation)
+    } else {
+        saturate_and_rotate_linear_scalar(data, degrees, saturation)
+    }
+}
+
+/// [saturate_and_rotate_linear] without the kernels of [simd], e.g. to test them.
+pub fn saturate_and_rotate_linear_scalar(data: &mut [u8], degrees: f32, saturation: f32) {
+    let decode = srgb_decode_table();
+    map_hsl(data, Some(&decode), |h, s, l| {
+        (h + degrees, (s * saturation).min(1.0), l)
+    });
+}
+
+/// Variant of [color_stretch] computing HSL from the sRGB encoded values.
+///
+/// The 8 bit values are scaled to 0..=1 without decoding them, like
+/// [saturate_and_rotate] does, the hue is stretched as in [color_stretch]
+/// and the saturation multiplied by `saturation`. [color_stretch] computes
+/// HSL from linear light, so the hues and saturations differ, most for dark
+/// colors.
+pub fn color_stretch_encoded(data: &mut [u8], center_hue: f32, radius: f32, saturation: f32) {
+    if simd::ENABLED {
+        simd::color_stretch_encoded(data, center_hue, radius, saturation)
+    } else {
+        color_stretch_encoded_scalar(data, center_hue, radius, saturation)
+    }
+}
+
+/// [color_stretch_encoded] without the kernels of [simd], e.g. to test them.
+pub fn color_stretch_encoded_scalar(
+    data: &mut [u8],
+    center_hue: f32,
+    radius: f32,
+    saturation: f32,
+) {
+    let (cx, cy) = stretch_center(center_hue, radius);
+    map_hsl(data, None, |h, s, l| {
+        (stretch_hue(h, cx, cy), s * saturation, l)
+    });
+}
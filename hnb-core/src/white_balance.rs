@@ -0,0 +1,296 @@
+//! White balance: adapting a photo taken under another light to the D65
+//! white of sRGB.
+//!
+//! The color of a neutral area, e.g. the tube rack or a paper card, is the
+//! color of the light. A chromatic adaptation transform (CAT) maps it to
+//! D65 by scaling the responses of a cone-like space, which keeps the
+//! relations between the other colors closer to what the eye sees than
+//! scaling R, G and B. Warm indoor light otherwise shifts the hues of HNB
+//! enough to change the call.
+//!
+//! Without a neutral area, [estimate_illuminant] estimates the color of the
+//! light from statistics of the whole image.
+
+use crate::color_transform::{ColorTransform, Parameter};
+use crate::colorspace::{
+    invert, linear_to_xyz, map_linear, mul, mul_matrices, xyz_to_xy, Matrix3, D65_XYZ, SRGB_TO_XYZ,
+    XYZ_TO_SRGB,
+};
+use crate::image::{RgbaImage, BYTES_PER_PIXEL};
+use crate::transform_colors::srgb_decode_table;
+
+/// The cone-like space in which the white is adapted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Adaptation {
+    /// The Bradford transform, as in ICC color management.
+    #[default]
+    Bradford,
+    /// The transform of the CAM16 color appearance model (Li et al., 2017).
+    Cat16,
+}
+
+impl Adaptation {
+    pub const ALL: [Adaptation; 2] = [Adaptation::Bradford, Adaptation::Cat16];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Adaptation::Bradford => "Bradford",
+            Adaptation::Cat16 => "CAT16",
+        }
+    }
+
+    /// XYZ to the cone-like responses.
+    fn matrix(&self) -> Matrix3 {
+        match self {
+            Adaptation::Bradford => [
+                [0.8951, 0.2664, -0.1614],
+                [-0.7502, 1.7135, 0.0367],
+                [0.0389, -0.0685, 1.0296],
+            ],
+            Adaptation::Cat16 => [
+                [0.401_288, 0.650_173, -0.051_461],
+                [-0.250_268, 1.204_414, 0.045_854],
+                [-0.002_079, 0.048_952, 0.953_127],
+            ],
+        }
+    }
+}
+
+/// Chromatic adaptation of a photo from the color of its light to D65.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhiteBalance {
+    /// The linear sRGB color of a neutral area in the photo.
+    pub illuminant: [f32; 3],
+    pub adaptation: Adaptation,
+    /// Linear sRGB to the adapted linear sRGB.
+    matrix: Matrix3,
+}
+
+impl WhiteBalance {
+    /// The adaptation which makes the linear sRGB color `illuminant` a gray
+    /// of the same luminance. `None` if it is black or far outside the
+    /// colors of light.
+    pub fn new(illuminant: [f32; 3], adaptation: Adaptation) -> Option<Self> {
+        let xyz = linear_to_xyz(illuminant);
+        if xyz[1] <= 1e-4 {
+            return None;
+        }
+        let cat = adaptation.matrix();
+        // Both whites with luminance 1, so that the luminance is kept.
+        let source = mul(&cat, xyz.map(|v| v / xyz[1]));
+        let target = mul(&cat, D65_XYZ);
+        if source.iter().any(|&v| v <= 1e-4) {
+            return None;
+        }
+        let mut scale = [[0.0; 3]; 3];
+        for i in 0..3 {
+            scale[i][i] = target[i] / source[i];
+        }
+        let adapt = mul_matrices(&invert(&cat)?, &mul_matrices(&scale, &cat));
+        let matrix = mul_matrices(&XYZ_TO_SRGB, &mul_matrices(&adapt, &SRGB_TO_XYZ));
+        Some(Self {
+            illuminant,
+            adaptation,
+            matrix,
+        })
+    }
+
+    /// The adaptation for the mean color of the RGBA pixels of a neutral
+    /// area.
+    pub fn from_pixels(pixels: &[u8], adaptation: Adaptation) -> Option<Self> {
+        Self::new(mean_linear(pixels)?, adaptation)
+    }
+
+    /// The CIE 1931 xy chromaticity of the light.
+    pub fn chromaticity(&self) -> (f32, f32) {
+        xyz_to_xy(linear_to_xyz(self.illuminant))
+    }
+
+    /// Adapt a linear sRGB color. The result may be outside the gamut.
+    #[inline]
+    pub fn correct(&self, rgb: [f32; 3]) -> [f32; 3] {
+        mul(&self.matrix, rgb)
+    }
+}
+
+/// The mean linear sRGB color of RGBA pixels, `None` without pixels.
+pub fn mean_linear(pixels: &[u8]) -> Option<[f32; 3]> {
+    let decode = srgb_decode_table();
+    let n = pixels.len() / BYTES_PER_PIXEL;
+    if n == 0 {
+        return None;
+    }
+    let mut sum = [0.0f64; 3];
+    for pix in pixels.chunks_exact(BYTES_PER_PIXEL) {
+        for c in 0..3 {
+            sum[c] += decode[pix[c] as usize] as f64;
+        }
+    }
+    Some(sum.map(|s| (s / n as f64) as f32))
+}
+
+impl ColorTransform for WhiteBalance {
+    fn name(&self) -> &str {
+        "White balanced"
+    }
+    fn slug(&self) -> &str {
+        "white-balance"
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        Vec::new()
+    }
+    fn set_parameter(&mut self, _slug: &str, _value: f32) {}
+    fn apply(&self, data: &mut [u8]) {
+        map_linear(data, |rgb| self.correct(rgb));
+    }
+    fn summary(&self) -> String {
+        let (x, y) = self.chromaticity();
+        format!("{}, white x {x:.4} y {y:.4}", self.adaptation.name())
+    }
+    fn cache_key(&self) -> String {
+        format!("{}:{:?}", self.slug(), self.matrix)
+    }
+}
+
+/// A method to estimate the color of the light from the whole image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IlluminantEstimator {
+    /// The mean color, assuming the scene is gray on average (Buchsbaum,
+    /// 1980).
+    GreyWorld,
+    /// The brightest value of each channel, assuming the brightest surfaces
+    /// are white (max-RGB, Land, 1977).
+    WhitePatch,
+    /// The Minkowski norm of the colors, between gray world and white patch
+    /// (Finlayson & Trezzi, 2004).
+    ShadesOfGrey,
+    /// The Minkowski norm of the color gradients, assuming the edges are
+    /// gray on average (van de Weijer et al., 2007).
+    GreyEdge,
+}
+
+impl IlluminantEstimator {
+    pub const ALL: [IlluminantEstimator; 4] = [
+        IlluminantEstimator::GreyWorld,
+        IlluminantEstimator::WhitePatch,
+        IlluminantEstimator::ShadesOfGrey,
+        IlluminantEstimator::GreyEdge,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IlluminantEstimator::GreyWorld => "Grey world",
+            IlluminantEstimator::WhitePatch => "White patch",
+            IlluminantEstimator::ShadesOfGrey => "Shades of grey",
+            IlluminantEstimator::GreyEdge => "Grey edge",
+        }
+    }
+
+    /// Short identifier, e.g. for the command line.
+    pub fn slug(&self) -> &'static str {
+        match self {
+            IlluminantEstimator::GreyWorld => "grey-world",
+            IlluminantEstimator::WhitePatch => "white-patch",
+            IlluminantEstimator::ShadesOfGrey => "shades-of-grey",
+            IlluminantEstimator::GreyEdge => "grey-edge",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.slug() == slug)
+    }
+}
+
+/// The larger side of the reduced image the light is estimated from. The
+/// reduction also averages out noise, which matters for white patch.
+const ESTIMATION_SIZE: u32 = 512;
+
+/// Pixels with a channel at least this bright are left out, as clipped
+/// values do not show the color of the light.
+const CLIPPED: u8 = 250;
+
+/// The exponent of the Minkowski norms of shades of grey and grey edge.
+const MINKOWSKI_NORM: f64 = 6.0;
+
+/// Estimate the color of the light, in linear sRGB with the largest channel
+/// 1. `None` if the image has no usable pixels.
+pub fn estimate_illuminant(image: &RgbaImage, estimator: IlluminantEstimator) -> Option<[f32; 3]> {
+    let (reduced, _) = image.block_average(ESTIMATION_SIZE);
+    let decode = srgb_decode_table();
+    let (width, height) = (reduced.width() as usize, reduced.height() as usize);
+    let mut linear = Vec::with_capacity(width * height);
+    let mut valid = Vec::with_capacity(width * height);
+    for pix in reduced.as_raw().chunks_exact(BYTES_PER_PIXEL) {
+        linear.push([0, 1, 2].map(|c| decode[pix[c] as usize] as f64));
+        valid.push(pix[..3].iter().all(|&v| v < CLIPPED));
+    }
+    let usable = || {
+        linear
+            .iter()
+            .zip(valid.iter())
+            .filter(|(_, &v)| v)
+            .map(|(rgb, _)| *rgb)
+    };
+    let minkowski = |values: &mut dyn Iterator<Item = [f64; 3]>, p: f64| {
+        let (mut sum, mut n) = ([0.0; 3], 0);
+        for rgb in values {
+            for c in 0..3 {
+                sum[c] += rgb[c].powf(p);
+            }
+            n += 1;
+        }
+        (n > 0).then(|| sum.map(|s| (s / n as f64).powf(1.0 / p)))
+    };
+    let estimate = match estimator {
+        IlluminantEstimator::GreyWorld => minkowski(&mut usable(), 1.0)?,
+        IlluminantEstimator::ShadesOfGrey => minkowski(&mut usable(), MINKOWSKI_NORM)?,
+        IlluminantEstimator::WhitePatch => {
+            usable().reduce(|a, b| [0, 1, 2].map(|c| a[c].max(b[c])))?
+        }
+        IlluminantEstimator::GreyEdge => {
+            if width < 3 || height < 3 {
+                return None;
+            }
+            // Smooth with a 3x3 binomial filter, then take the gradient
+            // magnitude of each channel by central differences.
+            let at = |x: usize, y: usize| linear[y * width + x];
+            let mut smooth = vec![[0.0; 3]; width * height];
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let mut sum = [0.0; 3];
+                    for (dy, wy) in [(0, 1.0), (1, 2.0), (2, 1.0)] {
+                        for (dx, wx) in [(0, 1.0), (1, 2.0), (2, 1.0)] {
+                            let rgb = at(x + dx - 1, y + dy - 1);
+                            for c in 0..3 {
+                                sum[c] += wx * wy * rgb[c] / 16.0;
+                            }
+                        }
+                    }
+                    smooth[y * width + x] = sum;
+                }
+            }
+            let mut gradients = (2..height - 2).flat_map(|y| (2..width - 2).map(move |x| (x, y)));
+            let mut edges = std::iter::from_fn(|| loop {
+                let (x, y) = gradients.next()?;
+                let near_clipped = [(0, 0), (1, 0), (0, 1)].iter().any(|&(dx, dy)| {
+                    !valid[(y + dy) * width + x + dx] || !valid[(y - dy) * width + x - dx]
+                });
+                if near_clipped {
+                    continue;
+                }
+                let s = |x: usize, y: usize| smooth[y * width + x];
+                return Some([0, 1, 2].map(|c| {
+                    let gx = (s(x + 1, y)[c] - s(x - 1, y)[c]) / 2.0;
+                    let gy = (s(x, y + 1)[c] - s(x, y - 1)[c]) / 2.0;
+                    gx.hypot(gy)
+                }));
+            });
+            minkowski(&mut edges, MINKOWSKI_NORM)?
+        }
+    };
+    let max = estimate.iter().cloned().fold(0.0, f64::max);
+    if max <= 0.0 || estimate.iter().any(|&v| v <= 0.0) {
+        return None;
+    }
+    Some(estimate.map(|v| (v / max) as f32))
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{angle_distance, hue_of, pixels};
+use hnb_core::{
+    autotune::{auto_stretch, hue_clusters},
+    color_transform::{ColorStretch, ColorTransform, HslPipeline},
+};
+
+#[test]
+fn finds_the_main_hues() {
+    let (blue, violet) = ([60, 130, 220], [150, 70, 200]);
+    let data = pixels(&[(blue, 300), (violet, 200), ([128, 128, 128], 2000)]);
+    let clusters = hue_clusters(&data, 2, HslPipeline::Legacy);
+    assert_eq!(clusters.len(), 2);
+    assert!(angle_distance(clusters[0].hue * 360.0, hue_of(blue)) < 1.0);
+    assert!(angle_distance(clusters[1].hue * 360.0, hue_of(violet)) < 1.0);
+    assert!((clusters[0].weight + clusters[1].weight - 1.0).abs() < 1e-4);
+
+    assert!(hue_clusters(&pixels(&[([90, 90, 90], 10)]), 2, HslPipeline::Legacy).is_empty());
+}
+
+#[test]
+fn center_pushes_the_clusters_apart() {
+    let (blue, violet) = ([60, 130, 220], [150, 70, 200]);
+    let data = pixels(&[(blue, 300), (violet, 200), ([200, 200, 190], 2000)]);
+    let start = ColorStretch::default();
+    let auto = auto_stretch(&data, &start).unwrap();
+    assert_eq!(auto.transform.radius, start.radius);
+
+    // Between the two hues, on the shorter arc.
+    let center = auto.transform.center_hue * 360.0;
+    let (h1, h2) = (hue_of(blue), hue_of(violet));
+    assert!(
+        angle_distance(center, h1) + angle_distance(center, h2) <= angle_distance(h1, h2) + 1.0,
+        "{auto:?}"
+    );
+
+    // The stretched colors are further apart than the original ones.
+    let mut out = pixels(&[(blue, 1), (violet, 1)]);
+    auto.transform.apply(&mut out);
+    let stretched = angle_distance(
+        hue_of([out[0], out[1], out[2]]),
+        hue_of([out[4], out[5], out[6]]),
+    );
+    assert!(stretched > angle_distance(h1, h2) + 30.0, "{stretched}");
+
+    // A single hue is not enough.
+    assert!(auto_stretch(&pixels(&[(blue, 10)]), &start).is_none());
+}
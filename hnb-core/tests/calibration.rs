@@ -0,0 +1,167 @@
+use hnb_core::{
+    calibration::{
+        delta_e, detect_chart, CcmModel, ChartDetection, ChartError, ChartLayout, ColorCorrection,
+    },
+    colorspace::{linear_to_srgb8, srgb8_to_linear},
+    image::RgbaImage,
+};
+
+/// The colors a camera with a bluish cast and muted primaries records.
+fn camera(rgb: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = srgb8_to_linear(rgb);
+    linear_to_srgb8([
+        0.75 * r + 0.12 * g + 0.02 * b,
+        0.06 * r + 0.8 * g + 0.08 * b,
+        0.05 * r + 0.15 * g + 0.95 * b,
+    ])
+}
+
+/// A photo of the chart with patches of side 50 pixels, 70 pixels apart,
+/// turned by `angle` degrees around the center of the image.
+fn photo_of_chart(layout: &ChartLayout, angle: f32) -> RgbaImage {
+    let (width, height) = (700, 560);
+    let mut image = RgbaImage::new(width, height);
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let pitch = 70.0;
+    for y in 0..height {
+        for x in 0..width {
+            // Chart coordinates, with the first patch centered at 0, 0.
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let u = cos * dx + sin * dy + pitch * (layout.cols - 1) as f32 / 2.0;
+            let v = -sin * dx + cos * dy + pitch * (layout.rows - 1) as f32 / 2.0;
+            let (col, row) = ((u / pitch).round(), (v / pitch).round());
+            let border = (-45.0..pitch * layout.cols as f32 - 25.0).contains(&u)
+                && (-45.0..pitch * layout.rows as f32 - 25.0).contains(&v);
+            let rgb = if (u - col * pitch).abs() < 25.0
+                && (v - row * pitch).abs() < 25.0
+                && (0.0..layout.cols as f32).contains(&col)
+                && (0.0..layout.rows as f32).contains(&row)
+            {
+                camera(layout.reference[row as usize * layout.cols + col as usize])
+            } else if border {
+                [20, 20, 20]
+            } else {
+                [180, 170, 150]
+            };
+            let noise = ((x * 7 + y * 13) % 3) as u8;
+            let i = 4 * (y * width + x) as usize;
+            for (pix, v) in image.as_raw_mut()[i..i + 3].iter_mut().zip(rgb) {
+                *pix = v.saturating_add(noise);
+            }
+        }
+    }
+    image
+}
+
+#[test]
+fn detects_turned_chart_and_corrects_the_colors() {
+    let layout = ChartLayout::color_checker_classic();
+    // Upside down and a little askew.
+    let image = photo_of_chart(&layout, 187.0);
+    let chart = detect_chart(&image, &layout).expect("chart found");
+
+    // The dark skin patch is at the bottom right.
+    let (x, y) = chart.corners[0];
+    assert!(x > 500.0 && y > 350.0, "{chart:?}");
+    assert_eq!(chart.centers.len(), 24);
+    assert!((chart.patch_size - 35.0).abs() < 3.0, "{chart:?}");
+
+    let before = delta_e(&chart.measured, &layout.reference);
+    let ccm = ColorCorrection::fit(&chart.measured, &layout.reference, CcmModel::Linear).unwrap();
+    let corrected: Vec<[f32; 3]> = chart.measured.iter().map(|&m| ccm.correct(m)).collect();
+    let after = delta_e(&corrected, &layout.reference);
+    assert!(before.0 > 5.0, "{before:?}");
+    assert!(after.0 < 1.5, "{after:?}");
+}
+
+#[test]
+fn no_chart_in_plain_image() {
+    let layout = ChartLayout::color_checker_classic();
+    let mut image = RgbaImage::new(300, 200);
+    for pix in image.as_raw_mut().chunks_exact_mut(4) {
+        pix.copy_from_slice(&[200, 190, 180, 255]);
+    }
+    assert_eq!(detect_chart(&image, &layout), None);
+}
+
+#[test]
+fn measures_chart_from_corners() {
+    let layout = ChartLayout::color_checker_classic();
+    let image = photo_of_chart(&layout, 0.0);
+    // Patch centers of the untilted chart.
+    let (x0, y0) = (350.0 - 175.0, 280.0 - 105.0);
+    let corners = [
+        (x0, y0),
+        (x0 + 350.0, y0),
+        (x0 + 350.0, y0 + 210.0),
+        (x0, y0 + 210.0),
+    ];
+    let chart = ChartDetection::from_corners(&image, &layout, corners);
+    assert_eq!(chart.centers[7], (x0 + 70.0, y0 + 70.0));
+    assert_eq!(chart.patch_size, 35.0);
+    let expected: Vec<[f32; 3]> = layout
+        .reference
+        .iter()
+        .map(|&rgb| srgb8_to_linear(camera(rgb)))
+        .collect();
+    for (m, e) in chart.measured.iter().zip(expected) {
+        for c in 0..3 {
+            assert!((m[c] - e[c]).abs() < 0.02, "{m:?} {e:?}");
+        }
+    }
+}
+
+#[test]
+fn root_polynomial_fits_nonlinear_response() {
+    let layout = ChartLayout::color_checker_classic();
+    // A camera with a gamma different from sRGB and a color cast.
+    let measured: Vec<[f32; 3]> = layout
+        .reference
+        .iter()
+        .map(|&rgb| {
+            let [r, g, b] = srgb8_to_linear(rgb);
+            [0.9 * r.powf(0.8), g.powf(0.8), 0.7 * b.powf(0.8) + 0.1 * g]
+        })
+        .collect();
+    let error = |model| {
+        let ccm = ColorCorrection::fit(&measured, &layout.reference, model).unwrap();
+        let corrected: Vec<[f32; 3]> = measured.iter().map(|&m| ccm.correct(m)).collect();
+        delta_e(&corrected, &layout.reference).0
+    };
+    assert!(error(CcmModel::RootPolynomial) < error(CcmModel::Linear));
+
+    // Too few patches for the model.
+    assert_eq!(
+        ColorCorrection::fit(
+            &measured[..5],
+            &layout.reference[..5],
+            CcmModel::RootPolynomial
+        ),
+        None
+    );
+}
+
+#[test]
+fn parses_chart_description() {
+    let text = "# A small card\n2 2\n255, 0, 0\n0 255 0\n\n0\t0\t255\n128 128 128\n";
+    let layout = ChartLayout::parse("Card", text).unwrap();
+    assert_eq!((layout.rows, layout.cols), (2, 2));
+    assert_eq!(layout.reference[2], [0, 0, 255]);
+
+    assert_eq!(
+        ChartLayout::parse("Card", "2 2\n255 0 0\n"),
+        Err(ChartError::WrongPatchCount {
+            expected: 4,
+            actual: 1
+        })
+    );
+    assert!(matches!(
+        ChartLayout::parse("Card", "2 2\n255 0 300\n"),
+        Err(ChartError::InvalidLine { line: 2, .. })
+    ));
+    assert_eq!(
+        ChartLayout::parse("Card", "# nothing\n"),
+        Err(ChartError::MissingSize)
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{close, pixels, SKY_BLUE, VIOLET};
+use hnb_core::{
+    chromaticity::{
+        display_color, is_visible, srgb_gamut, white_point, ControlSeparation, Diagram,
+        RegionChromaticity, SPECTRAL_LOCUS,
+    },
+    classify::ControlReference,
+    colorspace::{linear_to_xyz, srgb8_to_linear, xyz_to_xy},
+    stats::ColorStats,
+};
+
+/// Tolerance of the chromaticity coordinates.
+const XY_TOLERANCE: f32 = 2e-3;
+
+#[test]
+fn gamut_white_and_locus() {
+    let [r, g, b] = srgb_gamut();
+    assert!(close(r.into(), [0.64, 0.33], XY_TOLERANCE), "{r:?}");
+    assert!(close(g.into(), [0.30, 0.60], XY_TOLERANCE), "{g:?}");
+    assert!(close(b.into(), [0.15, 0.06], XY_TOLERANCE), "{b:?}");
+    assert!(close(white_point().into(), [0.3127, 0.3290], XY_TOLERANCE));
+    assert!(close(
+        Diagram::UvPrime.from_xy(white_point()).into(),
+        [0.1978, 0.4683],
+        XY_TOLERANCE
+    ));
+
+    // The sRGB gamut is inside the locus, which is inside the diagrams.
+    for xy in [r, g, b, white_point()] {
+        assert!(is_visible(xy), "{xy:?}");
+    }
+    assert!(!is_visible((0.1, 0.1)));
+    assert_eq!(display_color(white_point()), [255, 255, 255]);
+    assert_eq!(display_color(r), [255, 0, 0]);
+    assert!(!is_visible((0.6, 0.15)));
+    for diagram in Diagram::ALL {
+        let (width, height) = diagram.extent();
+        for &(nm, x, y) in SPECTRAL_LOCUS.iter() {
+            let (u, v) = diagram.from_xy((x, y));
+            assert!(u > 0.0 && u < width && v > 0.0 && v < height, "{nm}");
+            assert!(
+                close(diagram.to_xy((u, v)).into(), [x, y], XY_TOLERANCE),
+                "{nm}"
+            );
+        }
+    }
+}
+
+#[test]
+fn regions_and_controls() {
+    // Too dark to count in the cloud, but in the mean.
+    let pixels = pixels(&[(SKY_BLUE, 100), ([2, 1, 3], 100)]);
+    let region = RegionChromaticity::of_pixels(&pixels, 50).unwrap();
+    assert_eq!(region.points.len(), 25);
+    let xy = xyz_to_xy(linear_to_xyz(srgb8_to_linear(SKY_BLUE)));
+    assert!(close(region.points[0].into(), xy.into(), XY_TOLERANCE));
+    assert!(close(region.mean.into(), xy.into(), XY_TOLERANCE));
+    assert_eq!(RegionChromaticity::of_pixels(&[], 10), None);
+
+    let stats = |rgb: [u8; 3]| ColorStats::of_pixels(&[rgb[0], rgb[1], rgb[2], 255]).unwrap();
+    let (positive, negative) = (stats(SKY_BLUE), stats(VIOLET));
+    let reference = ControlReference::new([&positive], [&negative]).unwrap();
+    let controls = ControlSeparation::new(&reference);
+    assert!(
+        close(controls.positive.into(), xy.into(), XY_TOLERANCE),
+        "{controls:?}"
+    );
+    assert_eq!(controls.delta_e, reference.separation());
+    assert!(controls.delta_e > 20.0);
+    let d_xy = controls.distance(Diagram::Xy);
+    let d_uv = controls.distance(Diagram::UvPrime);
+    assert!(d_xy > 0.02 && d_uv > 0.02 && d_xy != d_uv, "{d_xy} {d_uv}");
+}
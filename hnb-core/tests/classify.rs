@@ -0,0 +1,108 @@
+mod common;
+
+use common::{SKY_BLUE, VIOLET};
+use hnb_core::{
+    classify::{
+        classify, classify_by_controls, classify_regions, Call, CallCounts, ClassifierParams,
+        Control, ControlReference,
+    },
+    stats::ColorStats,
+};
+
+fn stats_of(rgb: [u8; 3]) -> ColorStats {
+    ColorStats::of_pixels(&[rgb[0], rgb[1], rgb[2], 255].repeat(4)).unwrap()
+}
+
+#[test]
+fn calls_hnb_colors() {
+    let params = ClassifierParams::default();
+    let sky_blue = classify(&stats_of(SKY_BLUE), &params);
+    assert_eq!(sky_blue.call, Call::Positive);
+    assert!(sky_blue.confidence > 0.5, "{sky_blue:?}");
+    let violet = classify(&stats_of(VIOLET), &params);
+    assert_eq!(violet.call, Call::Negative);
+    assert!(violet.confidence > 0.5, "{violet:?}");
+    // Gray, and a hue HNB does not have.
+    for rgb in [[128, 128, 130], [200, 180, 40]] {
+        let c = classify(&stats_of(rgb), &params);
+        assert_eq!(c.call, Call::Inconclusive, "{rgb:?}");
+        assert_eq!(c.confidence, 0.0);
+    }
+}
+
+#[test]
+fn boundary_is_configurable() {
+    let violet = stats_of(VIOLET);
+    let hue = classify(&violet, &ClassifierParams::default()).hue;
+    let mut params = ClassifierParams::default();
+    params.set_parameter("boundary_hue", hue + 20.0);
+    assert_eq!(classify(&violet, &params).call, Call::Positive);
+    params.set_parameter("margin", 25.0);
+    assert_eq!(classify(&violet, &params).call, Call::Inconclusive);
+    assert_eq!(params.parameters()[1].value, 25.0);
+}
+
+#[test]
+fn summary_line() {
+    let calls = [Call::Positive, Call::Negative, Call::Positive];
+    assert_eq!(
+        CallCounts::of(&calls).to_string(),
+        "2 positive / 1 negative / 0 inconclusive"
+    );
+}
+
+#[test]
+fn calls_relative_to_controls() {
+    // Dim, warm lighting: all colors are darker and more red than usual, so
+    // the fixed hue boundary misses the positive control.
+    let positive = stats_of([120, 100, 130]);
+    let negative = stats_of([130, 70, 110]);
+    let params = ClassifierParams::default();
+    assert_ne!(classify(&positive, &params).call, Call::Positive);
+    let reference = ControlReference::new([&positive], [&negative]).unwrap();
+
+    let c = classify_by_controls(&positive, &reference, &params);
+    assert_eq!(c.call, Call::Positive);
+    assert!((c.score.unwrap() - 1.0).abs() < 1e-3, "{c:?}");
+    assert!((c.confidence - 1.0).abs() < 1e-3, "{c:?}");
+    assert_eq!(
+        classify_by_controls(&negative, &reference, &params).call,
+        Call::Negative
+    );
+    // Halfway between the controls.
+    let middle = stats_of([125, 85, 120]);
+    let c = classify_by_controls(&middle, &reference, &params);
+    assert_eq!(c.call, Call::Inconclusive, "{c:?}");
+    // Far from both controls.
+    let yellow = stats_of([200, 180, 40]);
+    let c = classify_by_controls(&yellow, &reference, &params);
+    assert_eq!(c.call, Call::Inconclusive, "{c:?}");
+}
+
+#[test]
+fn controls_of_both_kinds_are_needed() {
+    let positive = stats_of(SKY_BLUE);
+    assert!(ControlReference::new([&positive], []).is_none());
+    assert!(ControlReference::new([&positive], [&positive]).is_none());
+}
+
+#[test]
+fn controls_are_not_called() {
+    let (positive, negative) = (stats_of(SKY_BLUE), stats_of(VIOLET));
+    let samples = [
+        stats_of([95, 155, 215]),
+        stats_of([125, 85, 165]),
+        stats_of([110, 120, 195]),
+    ];
+    let mut regions = vec![
+        (Some(Control::Positive), Some(&positive)),
+        (Some(Control::Negative), Some(&negative)),
+    ];
+    regions.extend(samples.iter().map(|s| (None, Some(s))));
+    let (calls, reference) = classify_regions(&regions, &ClassifierParams::default());
+    assert!(reference.is_some());
+    assert_eq!(calls[..2], [None, None]);
+    let counts = CallCounts::of(calls.iter().flatten().map(|c| &c.call));
+    assert_eq!(counts.positive + counts.negative + counts.inconclusive, 3);
+    assert_eq!((counts.positive, counts.negative), (1, 1));
+}
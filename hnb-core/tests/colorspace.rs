@@ -0,0 +1,66 @@
+mod common;
+
+use common::close;
+use hnb_core::{
+    colorspace::{
+        lab_to_lch, lab_to_linear, lch_to_lab, linear_to_lab, linear_to_oklab, oklab_to_linear,
+        srgb8_to_linear,
+    },
+    perceptual::{lch_rotate, lch_stretch, LchSpace},
+};
+
+#[test]
+fn white_is_neutral() {
+    let white = [1.0, 1.0, 1.0];
+    assert!(close(linear_to_lab(white), [100.0, 0.0, 0.0], 0.01));
+    assert!(close(linear_to_oklab(white), [1.0, 0.0, 0.0], 1e-3));
+}
+
+#[test]
+fn round_trips() {
+    for r in (0..=255).step_by(51) {
+        for g in (0..=255).step_by(51) {
+            for b in (0..=255).step_by(51) {
+                let rgb = srgb8_to_linear([r, g, b]);
+                assert!(close(lab_to_linear(linear_to_lab(rgb)), rgb, 1e-4));
+                assert!(close(oklab_to_linear(linear_to_oklab(rgb)), rgb, 1e-4));
+                let lab = linear_to_lab(rgb);
+                assert!(close(lch_to_lab(lab_to_lch(lab)), lab, 1e-3));
+            }
+        }
+    }
+}
+
+#[test]
+fn lch_rotate_without_change_is_identity() {
+    let orig: Vec<u8> = (0..=255u8)
+        .step_by(5)
+        .flat_map(|v| [v, 255 - v, v / 2, 255])
+        .collect();
+    for space in [LchSpace::Oklch, LchSpace::CieLch] {
+        let mut data = orig.clone();
+        lch_rotate(&mut data, space, 0.0, 1.0);
+        for (a, b) in orig.iter().zip(data.iter()) {
+            assert!(a.abs_diff(*b) <= 1, "{space:?}: {a} != {b}");
+        }
+    }
+}
+
+#[test]
+fn chroma_gain_keeps_lightness() {
+    // Violet and sky blue as in HNB reactions.
+    let orig = vec![132, 86, 160, 255, 96, 150, 205, 255];
+    for space in [LchSpace::Oklch, LchSpace::CieLch] {
+        let mut data = orig.clone();
+        lch_stretch(&mut data, space, space.default_center_hue(), 0.8, 4.0);
+        for (a, b) in orig.chunks_exact(4).zip(data.chunks_exact(4)) {
+            let la = space.from_linear(srgb8_to_linear([a[0], a[1], a[2]]))[0];
+            let lb = space.from_linear(srgb8_to_linear([b[0], b[1], b[2]]))[0];
+            let tol = match space {
+                LchSpace::Oklch => 0.01,
+                LchSpace::CieLch => 1.0,
+            };
+            assert!((la - lb).abs() < tol, "{space:?}: {la} != {lb}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers shared by the integration tests. Each test file uses only some.
+#![allow(dead_code)]
+
+use hnb_core::transform_colors::rgb_to_hsl;
+
+/// The color of a positive HNB sample, as 8 bit sRGB.
+pub const SKY_BLUE: [u8; 3] = [90, 160, 220];
+/// The color of a negative HNB sample, as 8 bit sRGB.
+pub const VIOLET: [u8; 3] = [130, 80, 170];
+
+/// `n` opaque RGBA pixels of each color.
+pub fn pixels(colors: &[([u8; 3], usize)]) -> Vec<u8> {
+    colors
+        .iter()
+        .flat_map(|&(rgb, n)| [rgb[0], rgb[1], rgb[2], 255].repeat(n))
+        .collect()
+}
+
+/// HSL hue in degrees of an 8 bit color.
+pub fn hue_of(rgb: [u8; 3]) -> f32 {
+    let [r, g, b] = rgb.map(|v| v as f32 / 255.0);
+    rgb_to_hsl(r, g, b).0
+}
+
+/// Distance of two angles in degrees, from 0 to 180.
+pub fn angle_distance(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+/// Whether no component of `a` and `b` differs by more than `tolerance`.
+pub fn close<const N: usize>(a: [f32; N], b: [f32; N], tolerance: f32) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .all(|(x, y)| (x - y).abs() <= tolerance)
+}
@@ -0,0 +1,115 @@
+use hnb_core::{
+    color_transform::{ColorRotate, ColorTransform},
+    cube::{parse_cube, transform_to_cube, write_cube, CubeLut, DEFAULT_CUBE_SIZE},
+    lut::Lut3d,
+};
+
+/// Data lines of a `.cube` file, i.e. lines starting with a number.
+fn data_lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter(|l| l.starts_with(|c: char| c.is_ascii_digit() || c == '-'))
+        .collect()
+}
+
+#[test]
+fn write_cube_layout() {
+    let size = 2;
+    // Identity: red changes fastest.
+    let values: Vec<f32> = (0..8)
+        .flat_map(|i| [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2) as f32])
+        .collect();
+    let text = write_cube(
+        &Lut3d::from_values(size, values),
+        "say \"hi\"",
+        &["first".into()],
+    );
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("# first"));
+    assert_eq!(lines.next(), Some("TITLE \"say 'hi'\""));
+    assert_eq!(lines.next(), Some("LUT_3D_SIZE 2"));
+    let data = data_lines(&text);
+    assert_eq!(data.len(), 8);
+    assert_eq!(data[1], "1.000000 0.000000 0.000000");
+    assert_eq!(data[6], "0.000000 1.000000 1.000000");
+}
+
+#[test]
+fn transform_to_cube_has_all_nodes() {
+    let text = transform_to_cube(&ColorRotate::default(), DEFAULT_CUBE_SIZE);
+    assert!(text.contains(&format!("LUT_3D_SIZE {DEFAULT_CUBE_SIZE}")));
+    assert!(text.contains("TITLE \"Color Rotated (180 deg, 4x saturation)\""));
+    assert!(text.is_ascii());
+    let data = data_lines(&text);
+    assert_eq!(data.len(), DEFAULT_CUBE_SIZE.pow(3));
+    // Black and white are kept.
+    assert_eq!(data[0], "0.000000 0.000000 0.000000");
+    assert_eq!(data[data.len() - 1], "1.000000 1.000000 1.000000");
+}
+
+#[test]
+fn parse_round_trip() {
+    let transform = ColorRotate::default();
+    let text = transform_to_cube(&transform, 17);
+    let lut = CubeLut::parse("rotated.cube", &text).unwrap();
+    assert_eq!(lut.name(), "LUT Color Rotated (180 deg, 4x saturation)");
+    let expected = Lut3d::from_transform(&transform, 17);
+    assert_eq!(lut.lut().size(), expected.size());
+    for (a, b) in lut.lut().values().iter().zip(expected.values()) {
+        assert!((a - b).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn parse_domain() {
+    // Identity on the domain 0..=2, of which 8 bit inputs use the lower half.
+    let text = "LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n\
+        0 0 0\n2 0 0\n0 2 0\n2 2 0\n0 0 2\n2 0 2\n0 2 2\n2 2 2\n";
+    let lut = CubeLut::parse("x.cube", text).unwrap();
+    let mut data = vec![255, 0, 51, 255];
+    lut.apply(&mut data);
+    assert_eq!(data, vec![255, 0, 51, 255]);
+    let cube = parse_cube(text).unwrap();
+    assert_eq!(cube.lut.domain_max(), [2.0; 3]);
+    assert_eq!(cube.title, None);
+}
+
+#[test]
+fn parse_errors() {
+    let data_2 = "0 0 0\n".repeat(8);
+    let cases = [
+        (
+            format!("LUT_3D_SIZE 2\n{}", "0 0 0\n".repeat(7)),
+            "expected 8 data lines for LUT_3D_SIZE, found 7",
+        ),
+        (data_2.clone(), "line 1: data before LUT_3D_SIZE"),
+        ("TITLE \"x\"\n".to_string(), "no LUT_3D_SIZE given"),
+        (
+            "LUT_1D_SIZE 4\n".to_string(),
+            "line 1: 1D LUTs are not supported, only 3D LUTs",
+        ),
+        (
+            "LUT_3D_SIZE 1\n".to_string(),
+            "line 1: LUT_3D_SIZE must be a number from 2 to 256",
+        ),
+        (
+            format!("# hi\n\nLUT_3D_SIZE 2\n0 0 x\n{data_2}"),
+            "line 4: expected 3 numbers for a data line",
+        ),
+        (
+            format!("LUT_3D_SIZE 2\n{data_2}TITLE \"x\"\n"),
+            "line 10: keyword TITLE after the data",
+        ),
+        (
+            format!("FOO 1\nLUT_3D_SIZE 2\n{data_2}"),
+            "line 1: unknown keyword FOO",
+        ),
+        (
+            format!("LUT_3D_SIZE 2\nDOMAIN_MIN 0 1 0\nDOMAIN_MAX 1 1 1\n{data_2}"),
+            "DOMAIN_MIN must be smaller than DOMAIN_MAX",
+        ),
+    ];
+    for (text, message) in cases {
+        let err = parse_cube(&text).unwrap_err();
+        assert_eq!(err.to_string(), message, "{text}");
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{SKY_BLUE, VIOLET};
+use hnb_core::{
+    detect::{detect_tubes, DetectionParams},
+    image::RgbaImage,
+    roi::Shape,
+};
+
+fn fill_disk(image: &mut RgbaImage, cx: f32, cy: f32, r: f32, rgb: [u8; 3]) {
+    let width = image.width() as usize;
+    let disk = Shape::Circle { cx, cy, r };
+    for i in disk.pixel_indices(image.width(), image.height()) {
+        let (x, y) = (i % width, i / width);
+        // Some texture, as in photos.
+        let noise = ((x * 7 + y * 13) % 5) as u8;
+        let pix = &mut image.as_raw_mut()[4 * i..4 * i + 3];
+        for c in 0..3 {
+            pix[c] = rgb[c].saturating_add(noise);
+        }
+    }
+}
+
+#[test]
+fn detects_tubes_in_reading_order() {
+    let mut image = RgbaImage::new(800, 500);
+    for pix in image.as_raw_mut().chunks_exact_mut(4) {
+        pix.copy_from_slice(&[235, 235, 230, 255]);
+    }
+    // Two rows of four, the second row not quite level.
+    let mut expected = Vec::new();
+    for row in 0..2 {
+        for col in 0..4 {
+            let cx = 120.0 + 180.0 * col as f32;
+            let cy = 130.0 + 230.0 * row as f32 + 12.0 * (col % 2) as f32 - 6.0 * row as f32;
+            let rgb = if (row + col) % 3 == 0 {
+                SKY_BLUE
+            } else {
+                VIOLET
+            };
+            fill_disk(&mut image, cx, cy, 40.0, rgb);
+            expected.push((cx, cy));
+        }
+    }
+    // Not HNB colored: a yellow and a red cap, and a blue speck too small.
+    fill_disk(&mut image, 30.0, 30.0, 20.0, [230, 200, 40]);
+    fill_disk(&mut image, 770.0, 470.0, 20.0, [200, 40, 40]);
+    fill_disk(&mut image, 770.0, 30.0, 2.0, SKY_BLUE);
+
+    let rois = detect_tubes(&image, &DetectionParams::default());
+    assert_eq!(rois.len(), expected.len(), "{rois:?}");
+    for (i, (roi, (cx, cy))) in rois.iter().zip(expected).enumerate() {
+        assert_eq!(roi.label, format!("Tube {}", i + 1));
+        let (x, y) = roi.shape.center();
+        assert!((x - cx).abs() < 3.0 && (y - cy).abs() < 3.0, "{roi:?}");
+        // Well inside the liquid.
+        let (x0, y0, x1, y1) = roi.shape.bounds();
+        assert!(
+            x1 - x0 > 30.0 && x1 - x0 < 60.0 && y1 - y0 > 30.0,
+            "{roi:?}"
+        );
+    }
+}
+
+#[test]
+fn nothing_to_detect() {
+    let image = RgbaImage::new(64, 48);
+    assert!(detect_tubes(&image, &DetectionParams::default()).is_empty());
+    assert!(detect_tubes(&RgbaImage::new(0, 0), &DetectionParams::default()).is_empty());
+}
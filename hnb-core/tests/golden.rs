@@ -0,0 +1,191 @@
+//! Compare the color transforms against the regression image corpus in
+//! `tests/golden`, see the README.md there. It has no FIJI references yet.
+
+use std::path::{Path, PathBuf};
+
+use hnb_core::{color_transform::Registry, image::RgbaImage};
+
+const CHANNELS: [&str; 4] = ["R", "G", "B", "A"];
+
+/// One line of the manifest.
+struct Case {
+    input: String,
+    transform: String,
+    parameters: Vec<(String, f32)>,
+    expected: String,
+    tolerance: [u8; 4],
+    source: String,
+}
+
+/// Per channel differences between two images.
+struct ChannelReport {
+    max_diff: u8,
+    mean_diff: f64,
+    n_over_tolerance: usize,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn parse_manifest(text: &str) -> Vec<Case> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(cols.len(), 6, "expected 6 columns in \"{line}\"");
+            let parameters = if cols[2] == "-" {
+                vec![]
+            } else {
+                cols[2]
+                    .split(',')
+                    .map(|kv| {
+                        let (k, v) = kv.split_once('=').expect("expected name=value");
+                        (k.to_string(), v.parse().expect("invalid parameter value"))
+                    })
+                    .collect()
+            };
+            let tol: Vec<u8> = cols[4]
+                .split(',')
+                .map(|t| t.parse().expect("invalid tolerance"))
+                .collect();
+            Case {
+                input: cols[0].to_string(),
+                transform: cols[1].to_string(),
+                parameters,
+                expected: cols[3].to_string(),
+                tolerance: tol.try_into().expect("expected 4 tolerances"),
+                source: cols[5].to_string(),
+            }
+        })
+        .collect()
+}
+
+fn read_png(path: &Path) -> RgbaImage {
+    let file = std::fs::File::open(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    buf.truncate(info.buffer_size());
+    let data = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|c| [c[0], c[0], c[0], c[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => unreachable!("expanded by decoder"),
+    };
+    RgbaImage::from_raw(info.width, info.height, data).unwrap()
+}
+
+fn write_png(path: &Path, img: &RgbaImage) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(img.as_raw())
+        .unwrap();
+}
+
+fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: &[u8; 4]) -> [ChannelReport; 4] {
+    let n_pixels = actual.as_raw().len() / 4;
+    let mut sums = [0u64; 4];
+    let mut maxs = [0u8; 4];
+    let mut n_over = [0usize; 4];
+    for (a, e) in actual
+        .as_raw()
+        .chunks_exact(4)
+        .zip(expected.as_raw().chunks_exact(4))
+    {
+        for c in 0..4 {
+            let diff = a[c].abs_diff(e[c]);
+            sums[c] += diff as u64;
+            maxs[c] = maxs[c].max(diff);
+            if diff > tolerance[c] {
+                n_over[c] += 1;
+            }
+        }
+    }
+    std::array::from_fn(|c| ChannelReport {
+        max_diff: maxs[c],
+        mean_diff: sums[c] as f64 / n_pixels.max(1) as f64,
+        n_over_tolerance: n_over[c],
+    })
+}
+
+#[test]
+fn golden_images() {
+    let dir = golden_dir();
+    let bless = std::env::var_os("HNB_GOLDEN_BLESS").is_some();
+    let manifest = std::fs::read_to_string(dir.join("manifest.txt")).unwrap();
+    let cases = parse_manifest(&manifest);
+    assert!(!cases.is_empty());
+    let registry = Registry::default();
+
+    let mut failures = Vec::new();
+    for case in cases.iter() {
+        let mut transform = registry
+            .create(&case.transform)
+            .unwrap_or_else(|| panic!("unknown transform {}", case.transform));
+        for (name, value) in case.parameters.iter() {
+            assert!(
+                transform.parameters().iter().any(|p| p.slug == name),
+                "transform {} has no parameter {name}",
+                case.transform
+            );
+            transform.set_parameter(name, *value);
+        }
+        let input = read_png(&dir.join(&case.input));
+        let actual = input.transformed(transform.as_ref());
+
+        let expected_path = dir.join(&case.expected);
+        if bless && case.source == "hnb-core" {
+            write_png(&expected_path, &actual);
+            println!("wrote {}", expected_path.display());
+            continue;
+        }
+
+        let expected = read_png(&expected_path);
+        let label = format!(
+            "{} -> {} ({}, {})",
+            case.input,
+            case.expected,
+            transform.summary(),
+            case.source
+        );
+        if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+            failures.push(format!("{label}: image size differs"));
+            continue;
+        }
+        let report = compare(&actual, &expected, &case.tolerance);
+        let mut text = format!("{label}\n");
+        for (c, r) in report.iter().enumerate() {
+            text.push_str(&format!(
+                "    {}: max diff {:3}, mean diff {:7.4}, {} pixels over tolerance {}\n",
+                CHANNELS[c], r.max_diff, r.mean_diff, r.n_over_tolerance, case.tolerance[c]
+            ));
+        }
+        print!("{text}");
+        if report.iter().any(|r| r.n_over_tolerance > 0) {
+            failures.push(text);
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} golden image cases failed:\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("")
+    );
+}
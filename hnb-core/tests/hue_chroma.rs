@@ -0,0 +1,52 @@
+mod common;
+
+use common::{angle_distance, pixels, SKY_BLUE, VIOLET};
+use hnb_core::{
+    color_transform::{ColorStretch, ColorTransform},
+    hue_chroma::{HueChroma, DENSITY_CHROMA_BINS, DENSITY_HUE_BINS, HUE_BINS},
+    transform_colors::rgb_to_hsl,
+};
+
+/// The distance in degrees of the two largest peaks of the histogram.
+fn peak_distance(plot: &HueChroma) -> f32 {
+    let mut bins: Vec<usize> = (0..HUE_BINS).collect();
+    bins.sort_by(|&a, &b| plot.hue_histogram[b].total_cmp(&plot.hue_histogram[a]));
+    let degrees = |bin: usize| (bin as f32 + 0.5) * 360.0 / HUE_BINS as f32;
+    angle_distance(degrees(bins[0]), degrees(bins[1]))
+}
+
+#[test]
+fn stretch_pulls_hnb_hues_apart() {
+    let pixels = pixels(&[(VIOLET, 100), (SKY_BLUE, 100), ([128, 128, 128], 100)]);
+    let before = HueChroma::of_pixels(&pixels, usize::MAX);
+    assert_eq!(before.n_pixels, 300);
+    // Gray pixels are only in the density, at chroma 0.
+    let total: f32 = before.hue_histogram.iter().sum();
+    let saturation = |p: [u8; 3]| {
+        rgb_to_hsl(
+            p[0] as f32 / 255.0,
+            p[1] as f32 / 255.0,
+            p[2] as f32 / 255.0,
+        )
+        .1
+    };
+    let saturations = 100.0 * (saturation(VIOLET) + saturation(SKY_BLUE));
+    assert!((total - saturations).abs() < 1e-3, "{total}");
+    let gray: f32 = (0..DENSITY_HUE_BINS).map(|h| before.density_at(h, 0)).sum();
+    assert_eq!(gray, 100.0);
+    assert_eq!(before.density.len(), DENSITY_HUE_BINS * DENSITY_CHROMA_BINS);
+
+    let mut stretched = pixels.clone();
+    ColorStretch::default().apply(&mut stretched);
+    let after = HueChroma::of_pixels(&stretched, usize::MAX);
+    let (d0, d1) = (peak_distance(&before), peak_distance(&after));
+    assert!(d1 > d0 + 30.0, "{d0} -> {d1}");
+}
+
+#[test]
+fn subsamples_large_images() {
+    let pixels = pixels(&[(VIOLET, 1000)]);
+    let plot = HueChroma::of_pixels(&pixels, 100);
+    assert_eq!(plot.n_pixels, 100);
+    assert_eq!(plot.density.iter().sum::<f32>(), 100.0);
+}
@@ -0,0 +1,163 @@
+use hnb_core::{
+    color_transform::{ColorInspectorRotate, ColorRotate, ColorTransform, Parameter, Registry},
+    lut::{FullLut, Interpolation, Lut3d, LutCache, Processing},
+};
+
+/// Leaves all pixels unchanged.
+struct Identity;
+
+impl ColorTransform for Identity {
+    fn name(&self) -> &str {
+        "Identity"
+    }
+    fn slug(&self) -> &str {
+        "identity"
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![]
+    }
+    fn set_parameter(&mut self, _slug: &str, _value: f32) {}
+    fn apply(&self, _data: &mut [u8]) {}
+}
+
+/// Pseudo random opaque colors.
+fn random_colors(n: usize) -> Vec<u8> {
+    let mut state: u32 = 12345;
+    let mut data = Vec::with_capacity(4 * n);
+    for _ in 0..n {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let [r, g, b, _] = state.to_le_bytes();
+        data.extend_from_slice(&[r, g, b, 255]);
+    }
+    data
+}
+
+#[test]
+fn identity_lut_is_exact() {
+    let orig = random_colors(10_000);
+    for interpolation in [Interpolation::Tetrahedral, Interpolation::Trilinear] {
+        // With 18 nodes, every node lies exactly on an 8 bit value.
+        let lut = Lut3d::from_transform(&Identity, 18);
+        let mut data = orig.clone();
+        lut.apply(&mut data, interpolation);
+        assert_eq!(data, orig, "{interpolation:?}");
+    }
+}
+
+#[test]
+fn full_lut_matches_direct() {
+    let transform = ColorInspectorRotate::default();
+    let lut = FullLut::from_transform(&transform);
+    let mut direct = random_colors(10_000);
+    let mut looked_up = direct.clone();
+    transform.apply(&mut direct);
+    lut.apply(&mut looked_up);
+    assert_eq!(looked_up, direct);
+}
+
+#[test]
+fn fiji_rotation_is_never_interpolated() {
+    let transform = ColorInspectorRotate::default();
+    let mut cache = LutCache::default();
+    let mut direct = random_colors(10_000);
+    let mut processed = direct.clone();
+    transform.apply(&mut direct);
+    let processing = Processing::Grid {
+        size: 17,
+        interpolation: Interpolation::Trilinear,
+    };
+    cache.apply(&transform, processing, &mut processed);
+    assert_eq!(processed, direct);
+    assert_eq!(cache.len(), 0);
+}
+
+/// The largest difference of a channel between direct evaluation and a 65
+/// node tetrahedral grid, for the colors of [random_colors]. The grid is an
+/// approximation: the hue of nearly gray colors changes quickly between
+/// nodes, so transforms acting on hue are off by many levels there.
+const GRID_MAX_ERRORS: [(&str, u8); 9] = [
+    ("rotated", 5),
+    ("stretch", 57),
+    ("rotated-linear", 22),
+    ("stretch-encoded", 39),
+    ("fiji-rotated", 5),
+    ("oklch-rotated", 35),
+    ("oklch-stretch", 40),
+    ("lch-rotated", 26),
+    ("lch-stretch", 39),
+];
+
+#[test]
+fn grid_lut_error_is_bounded() {
+    let registry = Registry::default();
+    assert_eq!(registry.kinds().len(), GRID_MAX_ERRORS.len());
+    for kind in registry.kinds() {
+        let transform = (kind.create)();
+        let mut direct = random_colors(10_000);
+        let mut interpolated = direct.clone();
+        transform.apply(&mut direct);
+        Lut3d::from_transform(transform.as_ref(), 65)
+            .apply(&mut interpolated, Interpolation::Tetrahedral);
+        let (total, max) = direct
+            .iter()
+            .zip(interpolated.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .fold((0u64, 0u8), |(total, max), d| {
+                (total + d as u64, max.max(d))
+            });
+        let mean = total as f64 / direct.len() as f64;
+        assert!(mean < 2.0, "{}: mean difference {mean}", kind.slug);
+        let (_, bound) = GRID_MAX_ERRORS
+            .iter()
+            .find(|(slug, _)| *slug == kind.slug)
+            .unwrap_or_else(|| panic!("no bound for {}", kind.slug));
+        assert!(max <= *bound, "{}: max difference {max}", kind.slug);
+    }
+}
+
+#[test]
+fn alpha_is_unchanged() {
+    let lut = Lut3d::from_transform(&ColorRotate::default(), 17);
+    let mut data = vec![10, 200, 30, 0, 255, 255, 255, 77];
+    lut.apply(&mut data, Interpolation::Tetrahedral);
+    assert_eq!((data[3], data[7]), (0, 77));
+}
+
+#[test]
+fn cache_reuses_tables_per_parameter_set() {
+    let processing = Processing::Grid {
+        size: 17,
+        interpolation: Interpolation::Tetrahedral,
+    };
+    let mut cache = LutCache::default();
+    let mut transform = ColorRotate::default();
+    let mut data = random_colors(100);
+
+    cache.apply(&transform, processing, &mut data);
+    cache.apply(&transform, processing, &mut data);
+    assert_eq!(cache.len(), 1);
+
+    transform.set_parameter("degrees", 90.0);
+    cache.apply(&transform, processing, &mut data);
+    assert_eq!(cache.len(), 2);
+
+    cache.apply(&transform, Processing::Direct, &mut data);
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn cache_stays_within_budget() {
+    let processing = Processing::Grid {
+        size: 17,
+        interpolation: Interpolation::Tetrahedral,
+    };
+    let lut_bytes = 3 * 17 * 17 * 17 * 4;
+    let mut cache = LutCache::new(2 * lut_bytes);
+    let mut transform = ColorRotate::default();
+    let mut data = random_colors(100);
+    for degrees in [0.0, 90.0, 180.0, 270.0] {
+        transform.set_parameter("degrees", degrees);
+        cache.apply(&transform, processing, &mut data);
+    }
+    assert_eq!(cache.len(), 2);
+}
@@ -0,0 +1,100 @@
+use hnb_core::{
+    classify::Control,
+    color_transform::{ColorStretch, Preset, Registry},
+    optimize::{optimize_stretch, separation, subsample, LabelledPixels},
+};
+
+/// Pixels around `rgb`, with some noise.
+fn region(rgb: [u8; 3], seed: u32) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+    let mut noise = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state % 9) as i32 - 4
+    };
+    (0..500)
+        .flat_map(|_| {
+            let c = |v: u8, n: i32| (v as i32 + n).clamp(0, 255) as u8;
+            [
+                c(rgb[0], noise()),
+                c(rgb[1], noise()),
+                c(rgb[2], noise()),
+                255,
+            ]
+        })
+        .collect()
+}
+
+fn samples() -> Vec<LabelledPixels> {
+    vec![
+        LabelledPixels {
+            control: Control::Positive,
+            pixels: region([110, 100, 160], 1),
+        },
+        LabelledPixels {
+            control: Control::Positive,
+            pixels: region([105, 100, 165], 2),
+        },
+        LabelledPixels {
+            control: Control::Negative,
+            pixels: region([125, 95, 150], 3),
+        },
+        LabelledPixels {
+            control: Control::Negative,
+            pixels: region([130, 95, 155], 4),
+        },
+    ]
+}
+
+#[test]
+fn optimized_stretch_separates_better() {
+    let samples = samples();
+    let start = ColorStretch::default();
+    let fit = optimize_stretch(&start, &samples).unwrap();
+    assert!(fit.separation > 1.2 * fit.initial_separation, "{fit:?}");
+    assert_eq!(
+        fit.initial_separation,
+        separation(&start, &samples).unwrap()
+    );
+    let found = separation(&fit.transform, &samples).unwrap();
+    assert!((found - fit.separation).abs() < 1e-4);
+    assert!((0.0..1.0).contains(&fit.transform.center_hue));
+
+    // Only one kind of sample.
+    assert!(optimize_stretch(&start, &samples[..2]).is_none());
+}
+
+#[test]
+fn subsample_keeps_whole_pixels() {
+    let pixels: Vec<u8> = (0..40).collect();
+    assert_eq!(subsample(&pixels, 20), pixels);
+    assert_eq!(subsample(&pixels, 2), vec![0, 1, 2, 3, 20, 21, 22, 23]);
+}
+
+#[test]
+fn preset_round_trip() {
+    let registry = Registry::default();
+    let stretch = ColorStretch {
+        center_hue: 0.62,
+        radius: 0.85,
+        saturation: 5.5,
+        ..Default::default()
+    };
+    let preset = Preset::new("Strip\tA", &stretch);
+    assert_eq!(preset.name, "Strip A");
+    assert_eq!(
+        preset.cli_args(),
+        "-t stretch -s stretch.center_hue=0.62 -s stretch.radius=0.85 -s stretch.saturation=5.5"
+    );
+    let parsed = Preset::parse_line(&preset.to_line()).unwrap();
+    assert_eq!(parsed, preset);
+    let created = parsed.create(&registry).unwrap();
+    assert_eq!(
+        created.cache_key(),
+        "stretch:center_hue=0.62:radius=0.85:saturation=5.5"
+    );
+
+    assert!(Preset::parse_line("no tabs").is_err());
+    assert!(Preset::parse_line("a\tstretch\tradius=x").is_err());
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::close;
+use hnb_core::point_cloud::{Camera, CloudSpace, PointCloud};
+
+#[test]
+fn positions_in_each_space() {
+    assert_eq!(CloudSpace::Rgb.position([0, 0, 0]), [-1.0, -1.0, -1.0]);
+    assert_eq!(CloudSpace::Rgb.position([255, 255, 255]), [1.0, 1.0, 1.0]);
+    // Grays are on the axis, from black at the bottom to white at the top.
+    for space in [CloudSpace::Hsl, CloudSpace::Lab, CloudSpace::Oklab] {
+        assert!(
+            close(space.position([0, 0, 0]), [0.0, 0.0, -1.0], 0.01),
+            "{space:?}"
+        );
+        assert!(
+            close(space.position([255, 255, 255]), [0.0, 0.0, 1.0], 0.01),
+            "{space:?}"
+        );
+        let [x, y, _] = space.position([119, 119, 119]);
+        assert!(x.abs() < 0.01 && y.abs() < 0.01, "{space:?}");
+        // Red towards +a, or hue 0.
+        let [x, _, _] = space.position([255, 0, 0]);
+        assert!(x > 0.5, "{space:?}");
+        for rgb in [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]] {
+            let p = space.position(rgb);
+            assert!(p[0].hypot(p[1]) < 1.1, "{space:?} {rgb:?} {p:?}");
+        }
+    }
+}
+
+#[test]
+fn camera_orbits_the_space() {
+    let camera = Camera {
+        yaw: 0.0,
+        pitch: 0.0,
+        zoom: 1.0,
+    };
+    let (x, y, _) = camera.project([0.0, 0.0, 0.0], 200, 100);
+    assert_eq!((x, y), (100.0, 50.0));
+    // From the front, x to the right and z up.
+    let (x, y, _) = camera.project([1.0, 0.0, 0.0], 200, 100);
+    assert!(x > 100.0 && (y - 50.0).abs() < 1e-4);
+    let (_, y, _) = camera.project([0.0, 0.0, 1.0], 200, 100);
+    assert!(y < 50.0);
+    // From above, the top is nearest.
+    let mut above = camera;
+    above.orbit(0.0, 120.0);
+    assert_eq!(above.pitch, 90.0);
+    let (_, _, top) = above.project([0.0, 0.0, 1.0], 200, 100);
+    let (_, _, bottom) = above.project([0.0, 0.0, -1.0], 200, 100);
+    assert!(top > bottom);
+    // A quarter turn brings x to the front.
+    let mut turned = camera;
+    turned.orbit(-90.0, 0.0);
+    let (x, _, depth) = turned.project([1.0, 0.0, 0.0], 200, 100);
+    assert!((x - 100.0).abs() < 1e-4 && depth > 0.9, "{x} {depth}");
+}
+
+#[test]
+fn renders_nearest_point_in_front() {
+    let mut cloud = PointCloud::new(CloudSpace::Rgb);
+    // Black and white are on the line of sight from the front.
+    cloud.add_pixels(&[0, 0, 0, 255, 255, 255, 255, 255], 10, None);
+    assert_eq!(cloud.points.len(), 2);
+    let front = Camera {
+        yaw: 225.0,
+        pitch: 35.264_39,
+        zoom: 1.0,
+    };
+    let image = cloud.render(&front, 21, 21, 3, [9, 9, 9]);
+    assert_eq!(image.len(), 21 * 21 * 4);
+    let pixel = |x: usize, y: usize| &image[4 * (y * 21 + x)..4 * (y * 21 + x) + 4];
+    assert_eq!(pixel(0, 0), [9, 9, 9, 255]);
+    let behind = Camera {
+        yaw: 45.0,
+        pitch: -35.264_39,
+        zoom: 1.0,
+    };
+    let back = cloud.render(&behind, 21, 21, 3, [9, 9, 9]);
+    let center = |image: &[u8]| image[4 * (10 * 21 + 10)];
+    assert_ne!(center(&image), center(&back));
+    assert_eq!(center(&image).max(center(&back)), 255);
+}
+
+#[test]
+fn subsamples_and_colors_by_region() {
+    let mut cloud = PointCloud::new(CloudSpace::Lab);
+    cloud.add_pixels(&[10, 200, 30, 255].repeat(1000), 100, Some([255, 0, 0]));
+    assert_eq!(cloud.points.len(), 100);
+    assert!(cloud.points.iter().all(|p| p.color == [255, 0, 0]));
+}
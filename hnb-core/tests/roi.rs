@@ -0,0 +1,88 @@
+use hnb_core::{
+    image::RgbaImage,
+    roi::Shape,
+    stats::{CircularSummary, ColorStats, Summary},
+};
+
+#[test]
+fn shape_pixels() {
+    let rect = Shape::Rect {
+        x0: 4.0,
+        y0: 3.0,
+        x1: 1.0,
+        y1: 1.0,
+    };
+    // Columns 1..=3 and rows 1..=2.
+    assert_eq!(rect.pixel_indices(10, 10), vec![11, 12, 13, 21, 22, 23]);
+    // Clipped to the image.
+    assert_eq!(rect.pixel_indices(2, 2), vec![3]);
+
+    let circle = Shape::Circle {
+        cx: 50.0,
+        cy: 50.0,
+        r: 20.0,
+    };
+    let n = circle.pixel_indices(100, 100).len() as f32;
+    assert!((n - std::f32::consts::PI * 400.0).abs() < 10.0);
+
+    // Half of a 10x10 square.
+    let triangle = Shape::Polygon(vec![(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+    assert_eq!(triangle.pixel_indices(20, 20).len(), 45);
+    assert!(triangle.contains(2.0, 2.0));
+    assert!(!triangle.contains(6.0, 6.0));
+    assert_eq!(triangle.center(), (5.0, 5.0));
+    assert!(Shape::Polygon(vec![]).pixel_indices(5, 5).is_empty());
+}
+
+#[test]
+fn extract_pixels() {
+    let mut image = RgbaImage::new(3, 2);
+    for (i, pix) in image.as_raw_mut().chunks_exact_mut(4).enumerate() {
+        pix.copy_from_slice(&[i as u8, 0, 0, 255]);
+    }
+    let rect = Shape::Rect {
+        x0: 1.0,
+        y0: 0.0,
+        x1: 3.0,
+        y1: 1.0,
+    };
+    assert_eq!(
+        rect.extract_pixels(image.as_raw(), 3, 2),
+        vec![1, 0, 0, 255, 2, 0, 0, 255]
+    );
+}
+
+#[test]
+fn summaries() {
+    let s = Summary::of(&mut [4.0, 1.0, 3.0, 2.0]).unwrap();
+    assert_eq!(s.mean, 2.5);
+    assert_eq!(s.median, 2.5);
+    assert!((s.std - 1.25f32.sqrt()).abs() < 1e-6);
+    assert!(Summary::of(&mut []).is_none());
+
+    // Hues around red average to red, not cyan.
+    let c = CircularSummary::of(&[350.0, 10.0, 0.0, 5.0]).unwrap();
+    assert!(c.mean < 2.0 || c.mean > 358.0, "{c:?}");
+    assert!((c.median - 2.5).abs() < 1e-3, "{c:?}");
+    assert!(c.resultant_length > 0.98 && c.std < 10.0);
+    let spread = CircularSummary::of(&[0.0, 90.0, 180.0, 270.0]).unwrap();
+    assert!(spread.resultant_length < 1e-6);
+}
+
+#[test]
+fn color_stats() {
+    // Two pixels of pure blue and one gray.
+    let data = [0, 0, 255, 255, 0, 0, 255, 255, 128, 128, 128, 255];
+    let stats = ColorStats::of_pixels(&data).unwrap();
+    assert_eq!(stats.pixel_count, 3);
+    assert_eq!(stats.rgb[2].median, 255.0);
+    assert!((stats.rgb[0].mean - 128.0 / 3.0).abs() < 1e-4);
+    // The gray pixel has no hue.
+    let hue = stats.hue.unwrap();
+    assert!((hue.mean - 240.0).abs() < 1e-3 && hue.std < 1e-3);
+    assert_eq!(stats.saturation.median, 1.0);
+    // CIE L*a*b* of sRGB blue.
+    assert!((stats.lab[0].median - 32.30).abs() < 0.1);
+    assert!((stats.chroma.median - 133.8).abs() < 0.2);
+    assert!(ColorStats::of_pixels(&[]).is_none());
+}
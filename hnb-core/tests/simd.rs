@@ -0,0 +1,154 @@
+//! The vectorised kernels against the scalar functions.
+//!
+//! Natively the kernels are compiled lane by lane. The WebAssembly SIMD
+//! intrinsics are tested by running this file in a `wasm32` build with
+//! `-C target-feature=+simd128`, see `.github/workflows/check.yml`.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+use hnb_core::{
+    simd::{self, atan2_x4, hsl_to_rgb_x4, rgb_to_hsl_x4, sin_cos_x4, F32x4},
+    transform_colors::{self, hsl_to_rgb, rgb_to_hsl},
+};
+
+/// RGBA pixels covering the RGB cube in steps of 5, plus an incomplete
+/// group of four at the end.
+fn test_pixels() -> Vec<u8> {
+    let mut data = Vec::new();
+    for r in (0..=255).step_by(5) {
+        for g in (0..=255).step_by(5) {
+            for b in (0..=255).step_by(5) {
+                data.extend_from_slice(&[r, g, b, r ^ g]);
+            }
+        }
+    }
+    data.extend_from_slice(&[1, 2, 3, 4, 200, 100, 50, 25]);
+    data
+}
+
+fn max_diff(a: &[u8], b: &[u8]) -> u8 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap()
+}
+
+fn assert_matches_scalar(scalar: impl Fn(&mut [u8]), vectorised: impl Fn(&mut [u8])) {
+    let mut expected = test_pixels();
+    scalar(&mut expected);
+    let mut actual = test_pixels();
+    vectorised(&mut actual);
+    assert!(max_diff(&expected, &actual) <= 1);
+    // Alpha is unchanged.
+    let input = test_pixels();
+    assert!(actual
+        .iter()
+        .zip(&input)
+        .skip(3)
+        .step_by(4)
+        .all(|(a, b)| a == b));
+}
+
+#[test]
+fn hsl_kernels_match_scalar() {
+    let rgb = [
+        [0.0, 0.0, 0.0],
+        [1.0, 1.0, 1.0],
+        [0.5, 0.5, 0.5],
+        [1.0, 0.0, 0.0],
+        [0.2, 0.7, 0.1],
+        [0.9, 0.9, 0.3],
+        [0.1, 0.2, 0.8],
+        [0.6, 0.1, 0.55],
+    ];
+    for group in rgb.chunks(4) {
+        let lane = |c: usize| F32x4::from_array(std::array::from_fn(|i| group[i][c]));
+        let (h, s, l) = rgb_to_hsl_x4(lane(0), lane(1), lane(2));
+        let (h, s, l) = (h.to_array(), s.to_array(), l.to_array());
+        for (i, [r, g, b]) in group.iter().enumerate() {
+            let (eh, es, el) = rgb_to_hsl(*r, *g, *b);
+            assert!((h[i] - eh).abs() < 1e-3, "{h:?} {eh}");
+            assert!((s[i] - es).abs() < 1e-5 && (l[i] - el).abs() < 1e-6);
+
+            // Back with a hue outside 0..360 and oversaturated.
+            let back = hsl_to_rgb_x4(
+                F32x4::splat(eh - 420.0),
+                F32x4::splat(es * 3.0),
+                F32x4::splat(el),
+            );
+            let expected = hsl_to_rgb(eh - 420.0, es * 3.0, el);
+            assert!((back.0.to_array()[0] - expected.0).abs() < 1e-5);
+            assert!((back.1.to_array()[0] - expected.1).abs() < 1e-5);
+            assert!((back.2.to_array()[0] - expected.2).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn trig_kernels_match_std() {
+    let angles: Vec<f32> = (-40..=40).map(|i| i as f32 * 0.37).collect();
+    for group in angles.chunks_exact(4) {
+        let x = F32x4::from_array([group[0], group[1], group[2], group[3]]);
+        let (sin, cos) = sin_cos_x4(x);
+        for (i, a) in group.iter().enumerate() {
+            assert!((sin.to_array()[i] - a.sin()).abs() < 1e-5);
+            assert!((cos.to_array()[i] - a.cos()).abs() < 1e-5);
+        }
+        let (y, x) = (F32x4::from_array(group.try_into().unwrap()), cos);
+        let angle = atan2_x4(y, x).to_array();
+        for (i, a) in group.iter().enumerate() {
+            assert!((angle[i] - a.atan2(x.to_array()[i])).abs() < 2e-5);
+        }
+    }
+    assert_eq!(
+        atan2_x4(F32x4::splat(0.0), F32x4::splat(0.0)).to_array(),
+        [0.0; 4]
+    );
+}
+
+#[test]
+fn saturate_and_rotate_matches_scalar() {
+    for (degrees, saturation) in [(180.0, 4.0), (-75.0, 0.5), (400.0, 1.0)] {
+        assert_matches_scalar(
+            |d| transform_colors::saturate_and_rotate_scalar(d, degrees, saturation),
+            |d| simd::saturate_and_rotate(d, degrees, saturation),
+        );
+        assert_matches_scalar(
+            |d| transform_colors::saturate_and_rotate_linear_scalar(d, degrees, saturation),
+            |d| simd::saturate_and_rotate_linear(d, degrees, saturation),
+        );
+    }
+}
+
+#[test]
+fn color_stretch_matches_scalar() {
+    for (center_hue, radius, saturation) in [(0.0, 0.5, 4.0), (0.4, 0.9, 1.5), (0.75, 0.2, 1.0)] {
+        assert_matches_scalar(
+            |d| transform_colors::color_stretch_scalar(d, center_hue, radius, saturation),
+            |d| simd::color_stretch(d, center_hue, radius, saturation),
+        );
+        assert_matches_scalar(
+            |d| transform_colors::color_stretch_encoded_scalar(d, center_hue, radius, saturation),
+            |d| simd::color_stretch_encoded(d, center_hue, radius, saturation),
+        );
+    }
+}
+
+#[test]
+fn transforms_use_the_kernels_if_enabled() {
+    let mut expected = test_pixels();
+    if simd::ENABLED {
+        simd::color_stretch(&mut expected, 0.6, 0.8, 4.0);
+    } else {
+        transform_colors::color_stretch_scalar(&mut expected, 0.6, 0.8, 4.0);
+    }
+    let mut actual = test_pixels();
+    transform_colors::color_stretch(&mut actual, 0.6, 0.8, 4.0);
+    assert_eq!(actual, expected);
+}
+
+/// The wasm32 job of the CI must test the intrinsics, not the fallback.
+#[cfg(target_arch = "wasm32")]
+#[test]
+fn simd128_is_enabled() {
+    assert!(simd::ENABLED);
+}
@@ -0,0 +1,169 @@
+use hnb_core::{
+    color_transform::{ColorRotate, ColorTransform, Registry},
+    fiji::color_inspector_rotate,
+    image::{ImageError, RgbaImage},
+    transform_colors::{
+        color_stretch, color_stretch_encoded, hsl_to_rgb, linear_to_srgb, rgb_to_hsl,
+        saturate_and_rotate, saturate_and_rotate_linear, srgb_to_linear,
+    },
+};
+
+/// All 8 bit colors on a coarse grid, fully opaque.
+fn color_grid() -> Vec<u8> {
+    let mut data = Vec::new();
+    for r in (0..=255).step_by(15) {
+        for g in (0..=255).step_by(15) {
+            for b in (0..=255).step_by(15) {
+                data.extend_from_slice(&[r as u8, g as u8, b as u8, 255]);
+            }
+        }
+    }
+    data
+}
+
+#[test]
+fn rotate_without_change_is_identity() {
+    let orig = color_grid();
+    let mut data = orig.clone();
+    saturate_and_rotate(&mut data, 0.0, 1.0);
+    for (a, b) in orig.iter().zip(data.iter()) {
+        assert!((*a as i16 - *b as i16).abs() <= 1, "{a} != {b}");
+    }
+}
+
+#[test]
+fn grays_are_unchanged() {
+    let orig: Vec<u8> = (0..=255u8).flat_map(|v| vec![v, v, v, 255]).collect();
+    let mut rotated = orig.clone();
+    saturate_and_rotate(&mut rotated, 180.0, 4.0);
+    let mut stretched = orig.clone();
+    color_stretch(&mut stretched, 0.6, 0.8, 4.0);
+    for ((a, b), c) in orig.iter().zip(rotated.iter()).zip(stretched.iter()) {
+        assert!((*a as i16 - *b as i16).abs() <= 1);
+        assert!((*a as i16 - *c as i16).abs() <= 1);
+    }
+}
+
+#[test]
+fn fiji_rotate_without_change_is_exact_identity() {
+    let orig = color_grid();
+    let mut data = orig.clone();
+    color_inspector_rotate(&mut data, 0.0, 1.0);
+    assert_eq!(orig, data);
+}
+
+#[test]
+fn fiji_rotate_keeps_luma_of_grays() {
+    let orig: Vec<u8> = (0..=255u8).flat_map(|v| vec![v, v, v, 255]).collect();
+    let mut data = orig.clone();
+    color_inspector_rotate(&mut data, 180.0, 4.0);
+    assert_eq!(orig, data);
+}
+
+#[test]
+fn linearized_rotate_without_change_is_identity() {
+    let orig = color_grid();
+    let mut data = orig.clone();
+    saturate_and_rotate_linear(&mut data, 0.0, 1.0);
+    for (a, b) in orig.iter().zip(data.iter()) {
+        assert!((*a as i16 - *b as i16).abs() <= 1, "{a} != {b}");
+    }
+}
+
+#[test]
+fn encoded_stretch_works_on_encoded_values() {
+    let orig = color_grid();
+    for saturation in [0.5, 1.0, 4.0] {
+        // Without stretching, the hues stay and only the saturation changes,
+        // computed from the same encoded values as legacy Color Rotate.
+        let mut rotated = orig.clone();
+        let mut stretched = orig.clone();
+        saturate_and_rotate(&mut rotated, 0.0, saturation);
+        color_stretch_encoded(&mut stretched, 0.0, 0.0, saturation);
+        for (a, b) in rotated.iter().zip(stretched.iter()) {
+            assert!(a.abs_diff(*b) <= 1, "x{saturation}: {a} != {b}");
+        }
+    }
+
+    // Legacy Color Stretch computes HSL from linear light instead.
+    let mut linear = orig.clone();
+    let mut encoded = orig.clone();
+    color_stretch(&mut linear, 0.6, 0.8, 4.0);
+    color_stretch_encoded(&mut encoded, 0.6, 0.8, 4.0);
+    let changed = linear
+        .chunks_exact(4)
+        .zip(encoded.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > 1))
+        .count();
+    assert!(changed > orig.len() / 8, "{changed}");
+}
+
+#[test]
+fn hsl_round_trip() {
+    for rgb in color_grid().chunks_exact(4) {
+        let (r, g, b) = (
+            rgb[0] as f32 / 255.0,
+            rgb[1] as f32 / 255.0,
+            rgb[2] as f32 / 255.0,
+        );
+        let (h, s, l) = rgb_to_hsl(r, g, b);
+        assert!((0.0..360.0).contains(&h));
+        let (r2, g2, b2) = hsl_to_rgb(h, s, l);
+        assert!((r - r2).abs() < 1e-5 && (g - g2).abs() < 1e-5 && (b - b2).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn srgb_transfer_function_round_trip() {
+    for i in 0..=255 {
+        let v = i as f32 / 255.0;
+        assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn alpha_is_preserved() {
+    let mut data = vec![200, 50, 10, 17, 10, 50, 200, 0];
+    saturate_and_rotate(&mut data, 180.0, 4.0);
+    assert_eq!(data[3], 17);
+    assert_eq!(data[7], 0);
+}
+
+#[test]
+fn registry_creates_builtin_transforms() {
+    let registry = Registry::default();
+    let mut rotate = registry.create("rotated").unwrap();
+    assert_eq!(rotate.slug(), "rotated");
+    rotate.set_parameter("degrees", 90.0);
+    let degrees = rotate
+        .parameters()
+        .into_iter()
+        .find(|p| p.slug == "degrees")
+        .unwrap();
+    assert_eq!(degrees.value, 90.0);
+    assert!(registry.create("stretch").is_some());
+    assert!(registry.create("no-such-transform").is_none());
+}
+
+#[test]
+fn transformed_image_matches_buffer_transform() {
+    let data = color_grid();
+    let width = 18;
+    let height = (data.len() / 4 / width) as u32;
+    let img = RgbaImage::from_raw(width as u32, height, data.clone()).unwrap();
+    let rotate = ColorRotate::default();
+    let mut expected = data;
+    rotate.apply(&mut expected);
+    assert_eq!(img.transformed(&rotate).into_raw(), expected);
+}
+
+#[test]
+fn image_buffer_size_is_checked() {
+    assert_eq!(
+        RgbaImage::from_raw(2, 2, vec![0; 15]),
+        Err(ImageError::BufferSize {
+            expected: 16,
+            actual: 15
+        })
+    );
+}
@@ -0,0 +1,137 @@
+use hnb_core::{
+    color_transform::ColorTransform,
+    colorspace::{
+        correlated_color_temperature, linear_to_srgb8, linear_to_xyz, srgb8_to_linear, xyz_to_xy,
+    },
+    image::RgbaImage,
+    white_balance::{estimate_illuminant, Adaptation, IlluminantEstimator, WhiteBalance},
+};
+
+fn assert_close(a: [f32; 3], b: [f32; 3], tolerance: f32) {
+    for c in 0..3 {
+        assert!((a[c] - b[c]).abs() < tolerance, "{a:?} != {b:?}");
+    }
+}
+
+#[test]
+fn warm_white_becomes_neutral_gray() {
+    // A gray card under incandescent light.
+    let card = [0.6, 0.34, 0.12];
+    for adaptation in Adaptation::ALL {
+        let wb = WhiteBalance::new(card, adaptation).unwrap();
+        let [r, g, b] = wb.correct(card);
+        assert!(
+            (r - g).abs() < 1e-4 && (g - b).abs() < 1e-4,
+            "{adaptation:?}"
+        );
+        // With the same luminance.
+        assert!((linear_to_xyz([r, g, b])[1] - linear_to_xyz(card)[1]).abs() < 1e-4);
+        // Lighter and darker grays too.
+        let half = wb.correct(card.map(|v| v / 2.0));
+        assert_close(half, [r / 2.0; 3], 1e-4);
+        // Incandescent light is near 2850 K.
+        let cct = correlated_color_temperature(wb.chromaticity());
+        assert!((2000.0..3500.0).contains(&cct), "{cct}");
+    }
+}
+
+#[test]
+fn neutral_white_changes_nothing() {
+    let wb = WhiteBalance::new([0.5; 3], Adaptation::Cat16).unwrap();
+    assert_close(wb.correct([0.7, 0.2, 0.4]), [0.7, 0.2, 0.4], 1e-4);
+    let cct = correlated_color_temperature(wb.chromaticity());
+    assert!((cct - 6504.0).abs() < 20.0, "{cct}");
+
+    let mut data = vec![130, 80, 170, 255, 90, 160, 220, 255];
+    let before = data.clone();
+    wb.apply(&mut data);
+    assert_eq!(data, before);
+}
+
+#[test]
+fn white_from_pixels() {
+    let pixels = [200, 180, 150, 255, 210, 190, 160, 255];
+    let wb = WhiteBalance::from_pixels(&pixels, Adaptation::Bradford).unwrap();
+    let (a, b) = (
+        srgb8_to_linear([200, 180, 150]),
+        srgb8_to_linear([210, 190, 160]),
+    );
+    for c in 0..3 {
+        assert!((wb.illuminant[c] - (a[c] + b[c]) / 2.0).abs() < 1e-6);
+    }
+
+    assert_eq!(WhiteBalance::from_pixels(&[], Adaptation::Bradford), None);
+    assert_eq!(
+        WhiteBalance::from_pixels(&[0, 0, 0, 255], Adaptation::Bradford),
+        None
+    );
+}
+
+/// A scene of colored tiles which is gray on average, under a light with the
+/// linear sRGB color `light`.
+fn scene_under(light: [f32; 3]) -> RgbaImage {
+    let tiles: [[f32; 3]; 8] = [
+        [0.6, 0.2, 0.2],
+        [0.2, 0.6, 0.6],
+        [0.2, 0.6, 0.2],
+        [0.6, 0.2, 0.6],
+        [0.2, 0.2, 0.6],
+        [0.6, 0.6, 0.2],
+        [0.8, 0.8, 0.8],
+        [0.1, 0.1, 0.1],
+    ];
+    let size = 32;
+    let mut image = RgbaImage::new(8 * size, 8 * size);
+    let width = image.width();
+    for (i, pix) in image.as_raw_mut().chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i as u32 % width / size, i as u32 / width / size);
+        let tile = tiles[((x + 3 * y) % 8) as usize];
+        let rgb = linear_to_srgb8([0, 1, 2].map(|c| tile[c] * light[c]));
+        pix.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+    }
+    image
+}
+
+#[test]
+fn estimators_find_the_light() {
+    let light = [1.0, 0.75, 0.5];
+    let image = scene_under(light);
+    let expected = xyz_to_xy(linear_to_xyz(light));
+    for estimator in IlluminantEstimator::ALL {
+        let estimate = estimate_illuminant(&image, estimator).unwrap();
+        let (x, y) = xyz_to_xy(linear_to_xyz(estimate));
+        assert!(
+            (x - expected.0).abs() < 0.01 && (y - expected.1).abs() < 0.01,
+            "{estimator:?}: {estimate:?}"
+        );
+        assert_eq!(
+            IlluminantEstimator::from_slug(estimator.slug()),
+            Some(estimator)
+        );
+    }
+}
+
+#[test]
+fn no_estimate_without_usable_pixels() {
+    let mut image = RgbaImage::new(16, 16);
+    assert_eq!(
+        estimate_illuminant(&image, IlluminantEstimator::GreyWorld),
+        None
+    );
+    // All clipped.
+    for pix in image.as_raw_mut().chunks_exact_mut(4) {
+        pix.copy_from_slice(&[255, 255, 255, 255]);
+    }
+    assert_eq!(
+        estimate_illuminant(&image, IlluminantEstimator::WhitePatch),
+        None
+    );
+    // No edges.
+    for pix in image.as_raw_mut().chunks_exact_mut(4) {
+        pix.copy_from_slice(&[120, 100, 80, 255]);
+    }
+    assert_eq!(
+        estimate_illuminant(&image, IlluminantEstimator::GreyEdge),
+        None
+    );
+}